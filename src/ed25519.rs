@@ -0,0 +1,90 @@
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::instructions::{self, load_current_index, load_instruction_at},
+};
+
+solana_program::declare_id!("Ed25519SigVerify111111111111111111111111111");
+
+const SIGNATURE_OFFSETS_START: usize = 2;
+const SIGNATURE_OFFSETS_SERIALIZED_SIZE: usize = 14;
+const PUBKEY_SERIALIZED_SIZE: usize = 32;
+// Offsets pointing at the Ed25519 instruction itself rather than at another instruction
+const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+/// A `(public key, message)` pair whose signature the Ed25519 program verified
+type SignedMessage<'a> = (&'a [u8], &'a [u8]);
+
+/// Fails unless every `(signer, message)` pair was verified by an Ed25519 program
+/// instruction placed before the current instruction
+pub fn verify_signatures(
+    instructions_info: &AccountInfo,
+    expected: &[(&Pubkey, &[u8])],
+) -> ProgramResult {
+    if !instructions::check_id(instructions_info.key) {
+        msg!("Invalid instructions sysvar");
+        return Err(ProgramError::InvalidArgument);
+    }
+    let data = instructions_info.data.borrow();
+    let current_index = load_current_index(&data);
+
+    let mut verified = vec![false; expected.len()];
+    for index in 0..current_index {
+        let instruction = load_instruction_at(index as usize, &data)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        if instruction.program_id != id() {
+            continue;
+        }
+        for (pubkey, message) in signed_messages(&instruction.data)? {
+            for (i, (signer, expected_message)) in expected.iter().enumerate() {
+                if pubkey == signer.as_ref() && message == *expected_message {
+                    verified[i] = true;
+                }
+            }
+        }
+    }
+    if verified.contains(&false) {
+        msg!("Missing ed25519 signature");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(())
+}
+
+/// The signed messages carried by an Ed25519 program instruction
+fn signed_messages(data: &[u8]) -> Result<Vec<SignedMessage<'_>>, ProgramError> {
+    let count = *data.first().ok_or(ProgramError::InvalidInstructionData)? as usize;
+    let mut messages = Vec::with_capacity(count);
+    for i in 0..count {
+        let start = SIGNATURE_OFFSETS_START + i * SIGNATURE_OFFSETS_SERIALIZED_SIZE;
+        let signature_instruction_index = read_u16(data, start + 2)?;
+        let public_key_offset = read_u16(data, start + 4)? as usize;
+        let public_key_instruction_index = read_u16(data, start + 6)?;
+        let message_data_offset = read_u16(data, start + 8)? as usize;
+        let message_data_size = read_u16(data, start + 10)? as usize;
+        let message_instruction_index = read_u16(data, start + 12)?;
+        if signature_instruction_index != CURRENT_INSTRUCTION
+            || public_key_instruction_index != CURRENT_INSTRUCTION
+            || message_instruction_index != CURRENT_INSTRUCTION
+        {
+            msg!("Ed25519 offsets must point into the same instruction");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let pubkey = data
+            .get(public_key_offset..public_key_offset + PUBKEY_SERIALIZED_SIZE)
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        let message = data
+            .get(message_data_offset..message_data_offset + message_data_size)
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        messages.push((pubkey, message));
+    }
+    Ok(messages)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, ProgramError> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or(ProgramError::InvalidInstructionData)
+}
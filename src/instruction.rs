@@ -1,26 +1,738 @@
 use borsh::{BorshSerialize, BorshDeserialize};
+use solana_program::pubkey::Pubkey;
+
+use crate::state::{ChannelBalance, DutchAuctionTerms, EscrowTerms, Offer, OptionTerms, Payout, RingLeg};
 
 
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug)]
 pub enum EscrowInstruction {
-    /// Starts the trade by creating and populating an escrow account and transferring ownership of the given temp token account to the PDA
+    /// Opens the trade from either side by creating the escrow account and its vaults, the counterparty has to `Accept` before any deposit
     /// Accounts expected:
     ///
-    /// 0. `[signer]` The account of the person initializing the escrow
-    /// 1. `[writable]` Temporary token account that should be created prior to this instruction and owned by the initializer
-    /// 2. `[]` The initializer's token account for the token they will receive should the trade go through
-    /// 3. `[writable]` The escrow account, it will hold all necessary info about the trade.
-    /// 4. `[]` The rent sysvar
-    /// 5. `[]` The token program
+    /// 0. `[writable]` The escrow account, PDA of `["escrow", alice, bob, mint_x, mint_y, pass]`
+    /// 1. `[]` Mint x, deposited by alice
+    /// 2. `[]` Mint y, deposited by bob
+    /// 3. `[writable]` The vault for mint x, PDA of `["vault_x", alice, bob, mint_x, mint_y, pass]`
+    /// 4. `[writable]` The vault for mint y, PDA of `["vault_y", alice, bob, mint_x, mint_y, pass]`
+    /// 5. `[signer, writable]` The payer of the new accounts
+    /// 6. `[]` Alice, signer when initiating, and then writable to post a bond
+    /// 7. `[]` Bob, signer when initiating, and then writable to post a bond
+    /// 8. `[]` The token program
+    /// 9. `[]` The rent sysvar
+    /// 10. `[]` The system program
+    /// 11. `[]` The prerequisite escrow, only when one is named
     InitEscrow {
         amount_x: u64, //amounts[0]:x_val, amounts[1]:y_val, amounts[2]:pass
         amount_y: u64,
         pass: [u8; 32],
+        terms: Box<EscrowTerms>,
     },
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The escrow account
+    /// 1. `[writable]` The depositor's token account
+    /// 2. `[writable]` The vault of the depositor's leg
+    /// 3. `[signer]` Alice or bob
+    /// 4. `[]` The token program
+    /// 5. `[]` The clock sysvar, only for bonded escrows, which take no deposits after their deadline
     Deposit{
         pass: [u8; 32],
+        amount: u64, // can be a part of the leg, deposits add up until `amount_x`/`amount_y` is reached
     },
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The escrow account
+    /// 1. `[writable]` The taker's token account receiving the withdrawal, or the first payout recipient's
+    /// 2. `[writable]` The vault being withdrawn from
+    /// 3. `[signer]` The taker, writable to get their bond back with the payout
+    /// 4. `[]` The token program
+    /// 5. `[]` The clock sysvar, only for forwards and oracle-gated escrows
+    /// 6. `[]` The prerequisite escrow, only for chained escrows paying out. Refunds are deliberately not gated on it
+    /// 7. `[]` The price feed, only for oracle-gated escrows leaving `Committed`
+    /// 8. `[writable]` The token accounts of payout recipients 1.., in order, when the taker set payouts
     Withdrawal {
         pass: [u8; 32],
     },
+    /// Records the approval of one of the escrow approvers, releases are blocked until the threshold is met
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The escrow account
+    /// 1. `[signer]` The approver
+    Approve {
+        pass: [u8; 32],
+    },
+    /// Pays bob whatever has vested from a vesting escrow and was not claimed yet
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The escrow account
+    /// 1. `[writable]` Bob's token account for mint x
+    /// 2. `[writable]` The vault for mint x
+    /// 3. `[signer]` Bob
+    /// 4. `[]` The token program
+    /// 5. `[]` The clock sysvar
+    Claim {
+        pass: [u8; 32],
+    },
+    /// Creates a one-sided escrow and moves the sum of all milestone amounts into its vault
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The milestone escrow account, PDA of `["milestone", payer, payee, mint, pass]`
+    /// 1. `[]` The mint being paid
+    /// 2. `[writable]` The vault, PDA of `["vault", escrow]`
+    /// 3. `[signer, writable]` The payer
+    /// 4. `[]` The payee
+    /// 5. `[]` The arbiter allowed to release milestones besides the payer (the payer itself if there is none)
+    /// 6. `[writable]` The payer's token account for the mint
+    /// 7. `[]` The token program
+    /// 8. `[]` The rent sysvar
+    /// 9. `[]` The system program
+    InitMilestoneEscrow {
+        amounts: Vec<u64>, // up to `MAX_MILESTONES`
+        refund_after: i64,
+        pass: [u8; 32],
+    },
+    /// Pays a single milestone out of the vault to the payee
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The milestone escrow account
+    /// 1. `[writable]` The vault
+    /// 2. `[writable]` The payee's token account for the mint
+    /// 3. `[signer]` The payer or the arbiter
+    /// 4. `[]` The token program
+    ReleaseMilestone {
+        index: u8,
+        pass: [u8; 32],
+    },
+    /// Returns every unreleased milestone to the payer once `refund_after` has passed
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The milestone escrow account
+    /// 1. `[writable]` The vault
+    /// 2. `[writable]` The payer's token account for the mint
+    /// 3. `[signer]` The payer
+    /// 4. `[]` The token program
+    /// 5. `[]` The clock sysvar
+    RefundMilestones {
+        pass: [u8; 32],
+    },
+    /// Opens a payment stream and moves the initial budget into its vault
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The stream account, PDA of `["stream", payer, recipient, mint, pass]`
+    /// 1. `[]` The mint being streamed
+    /// 2. `[writable]` The vault, PDA of `["vault", stream]`
+    /// 3. `[signer, writable]` The payer
+    /// 4. `[]` The recipient
+    /// 5. `[writable]` The payer's token account for the mint
+    /// 6. `[]` The token program
+    /// 7. `[]` The rent sysvar
+    /// 8. `[]` The system program
+    /// 9. `[]` The clock sysvar
+    InitStream {
+        rate_per_second: u64,
+        amount: u64,
+        pass: [u8; 32],
+    },
+    /// Pays the recipient everything streamed so far that was not withdrawn yet
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The stream account
+    /// 1. `[writable]` The vault
+    /// 2. `[writable]` The recipient's token account for the mint
+    /// 3. `[signer]` The recipient
+    /// 4. `[]` The token program
+    /// 5. `[]` The clock sysvar
+    WithdrawStream {
+        pass: [u8; 32],
+    },
+    /// Adds budget to a running stream
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The stream account
+    /// 1. `[writable]` The vault
+    /// 2. `[writable]` The payer's token account for the mint
+    /// 3. `[signer]` The payer
+    /// 4. `[]` The token program
+    /// 5. `[]` The clock sysvar
+    TopUpStream {
+        amount: u64,
+        pass: [u8; 32],
+    },
+    /// Stops the stream, pays the recipient what is owed and refunds the unstreamed remainder to the payer
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The stream account
+    /// 1. `[writable]` The vault
+    /// 2. `[writable]` The payer's token account for the mint
+    /// 3. `[writable]` The recipient's token account for the mint
+    /// 4. `[signer]` The payer
+    /// 5. `[]` The token program
+    /// 6. `[]` The clock sysvar
+    CancelStream {
+        pass: [u8; 32],
+    },
+    /// Opens a crowdfunding campaign with an empty vault
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The campaign account, PDA of `["campaign", creator, mint, pass]`
+    /// 1. `[]` The mint being raised
+    /// 2. `[writable]` The vault, PDA of `["vault", campaign]`
+    /// 3. `[signer, writable]` The creator
+    /// 4. `[]` The token program
+    /// 5. `[]` The rent sysvar
+    /// 6. `[]` The system program
+    InitCampaign {
+        goal: u64,
+        deadline: i64,
+        pass: [u8; 32],
+    },
+    /// Adds to a campaign before its deadline and records it in the contributor's receipt
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The campaign account
+    /// 1. `[writable]` The vault
+    /// 2. `[writable]` The contributor's receipt, PDA of `["receipt", campaign, contributor]`, created on first contribution
+    /// 3. `[writable]` The contributor's token account for the mint
+    /// 4. `[signer, writable]` The contributor
+    /// 5. `[]` The token program
+    /// 6. `[]` The rent sysvar
+    /// 7. `[]` The system program
+    /// 8. `[]` The clock sysvar
+    Contribute {
+        amount: u64,
+        pass: [u8; 32],
+    },
+    /// Pays the whole vault to the creator once the deadline has passed with the goal met
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The campaign account
+    /// 1. `[writable]` The vault
+    /// 2. `[writable]` The creator's token account for the mint
+    /// 3. `[signer]` The creator
+    /// 4. `[]` The token program
+    /// 5. `[]` The clock sysvar
+    WithdrawCampaign {
+        pass: [u8; 32],
+    },
+    /// Returns a contributor's deposit once the deadline has passed without meeting the goal
+    /// Accounts expected:
+    ///
+    /// 0. `[]` The campaign account
+    /// 1. `[writable]` The vault
+    /// 2. `[writable]` The contributor's receipt
+    /// 3. `[writable]` The contributor's token account for the mint
+    /// 4. `[signer]` The contributor
+    /// 5. `[]` The token program
+    /// 6. `[]` The clock sysvar
+    Refund {
+        pass: [u8; 32],
+    },
+    /// Writes a covered call by locking the underlying X in the option vault
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The option account, PDA of `["option", writer, mint_x, mint_y, pass]`
+    /// 1. `[]` Mint x, the underlying
+    /// 2. `[]` Mint y, the premium and strike currency
+    /// 3. `[writable]` The vault for mint x, PDA of `["vault", option]`
+    /// 4. `[signer, writable]` The writer
+    /// 5. `[writable]` The writer's token account for mint x
+    /// 6. `[]` The token program
+    /// 7. `[]` The rent sysvar
+    /// 8. `[]` The system program
+    WriteOption {
+        terms: OptionTerms,
+        pass: [u8; 32],
+    },
+    /// Takes the option by paying the premium in Y straight to the writer
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The option account
+    /// 1. `[writable]` The buyer's token account for mint y
+    /// 2. `[writable]` The writer's token account for mint y
+    /// 3. `[signer]` The buyer
+    /// 4. `[]` The token program
+    /// 5. `[]` The clock sysvar
+    BuyOption {
+        pass: [u8; 32],
+    },
+    /// Pays the strike in Y to the writer and releases X to the holder, only inside the exercise window
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The option account
+    /// 1. `[writable]` The vault for mint x
+    /// 2. `[writable]` The holder's token account for mint x
+    /// 3. `[writable]` The holder's token account for mint y
+    /// 4. `[writable]` The writer's token account for mint y
+    /// 5. `[signer]` The holder
+    /// 6. `[]` The token program
+    /// 7. `[]` The clock sysvar
+    ExerciseOption {
+        pass: [u8; 32],
+    },
+    /// Returns X to the writer once the option expired unexercised
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The option account
+    /// 1. `[writable]` The vault for mint x
+    /// 2. `[writable]` The writer's token account for mint x
+    /// 3. `[signer]` The writer
+    /// 4. `[]` The token program
+    /// 5. `[]` The clock sysvar
+    ReclaimOption {
+        pass: [u8; 32],
+    },
+    /// Lends the principal of a loan escrow to alice once the collateral is deposited
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The escrow account
+    /// 1. `[writable]` Bob's token account for mint y
+    /// 2. `[writable]` Alice's token account for mint y
+    /// 3. `[signer]` Bob, the lender
+    /// 4. `[]` The token program
+    /// 5. `[]` The clock sysvar
+    Fund {
+        pass: [u8; 32],
+    },
+    /// Pays back principal plus interest to bob before the due date and returns alice's collateral
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The escrow account
+    /// 1. `[writable]` Alice's token account for mint y
+    /// 2. `[writable]` Bob's token account for mint y
+    /// 3. `[writable]` The vault for mint x
+    /// 4. `[writable]` Alice's token account for mint x
+    /// 5. `[signer]` Alice, the borrower
+    /// 6. `[]` The token program
+    /// 7. `[]` The clock sysvar
+    Repay {
+        pass: [u8; 32],
+    },
+    /// Hands the collateral to bob once the due date passed without repayment
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The escrow account
+    /// 1. `[writable]` The vault for mint x
+    /// 2. `[writable]` Bob's token account for mint x
+    /// 3. `[signer]` Bob, the lender
+    /// 4. `[]` The token program
+    /// 5. `[]` The clock sysvar
+    Liquidate {
+        pass: [u8; 32],
+    },
+    /// Takes a rental: posts bob's collateral in vault y, pays the fee to alice and hands the NFT to bob
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The escrow account
+    /// 1. `[writable]` Bob's token account for mint y
+    /// 2. `[writable]` The vault for mint y
+    /// 3. `[writable]` Alice's token account for mint y
+    /// 4. `[writable]` The vault for mint x
+    /// 5. `[writable]` Bob's token account for mint x
+    /// 6. `[signer]` Bob, the renter
+    /// 7. `[]` The token program
+    /// 8. `[]` The clock sysvar
+    Rent {
+        pass: [u8; 32],
+    },
+    /// Gives the NFT back to alice before the rental ends and returns bob's collateral
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The escrow account
+    /// 1. `[writable]` Bob's token account for mint x
+    /// 2. `[writable]` Alice's token account for mint x
+    /// 3. `[writable]` The vault for mint y
+    /// 4. `[writable]` Bob's token account for mint y
+    /// 5. `[signer]` Bob, the renter
+    /// 6. `[]` The token program
+    /// 7. `[]` The clock sysvar
+    ReturnRental {
+        pass: [u8; 32],
+    },
+    /// Pays bob's collateral to alice when the NFT was not returned in time
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The escrow account
+    /// 1. `[writable]` The vault for mint y
+    /// 2. `[writable]` Alice's token account for mint y
+    /// 3. `[signer]` Alice, the owner
+    /// 4. `[]` The token program
+    /// 5. `[]` The clock sysvar
+    ClaimCollateral {
+        pass: [u8; 32],
+    },
+    /// Submits the latest channel balance signed by both parties, checked against Ed25519 program
+    /// instructions earlier in the transaction. Opens or continues the challenge period
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The escrow account
+    /// 1. `[signer]` Alice or bob
+    /// 2. `[]` The instructions sysvar
+    /// 3. `[]` The clock sysvar
+    SubmitChannelState {
+        balance: ChannelBalance,
+        pass: [u8; 32],
+    },
+    /// Pays out both vaults according to the submitted balance once the challenge period is over
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The escrow account
+    /// 1. `[writable]` The vault for mint x
+    /// 2. `[writable]` The vault for mint y
+    /// 3. `[writable]` Alice's token account for mint x
+    /// 4. `[writable]` Alice's token account for mint y
+    /// 5. `[writable]` Bob's token account for mint x
+    /// 6. `[writable]` Bob's token account for mint y
+    /// 7. `[]` The token program
+    /// 8. `[]` The clock sysvar
+    SettleChannel {
+        pass: [u8; 32],
+    },
+    /// Settles an offer signed off-chain by the maker, checked against an Ed25519 program instruction
+    /// earlier in the transaction. The maker must have approved the delegate PDA for `amount_x` on the maker's X account
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The nonce account, PDA of `["nonce", maker, nonce]`, created here so the offer can only be taken once
+    /// 1. `[writable]` The maker's token account for mint x
+    /// 2. `[writable]` The maker's token account for mint y
+    /// 3. `[writable]` The taker's token account for mint x
+    /// 4. `[writable]` The taker's token account for mint y
+    /// 5. `[]` The delegate, PDA of `["delegate", maker]`
+    /// 6. `[signer, writable]` The taker
+    /// 7. `[]` The token program
+    /// 8. `[]` The instructions sysvar
+    /// 9. `[]` The clock sysvar
+    /// 10. `[]` The rent sysvar
+    /// 11. `[]` The system program
+    TakeOffer {
+        offer: Offer,
+    },
+    /// Creates an escrow without vaults. Each party instead approves the escrow account as delegate
+    /// for its amount on its own token account. The counterparty has to `Accept` before it can settle
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The escrow account, PDA of `["escrow", alice, bob, mint_x, mint_y, pass]`
+    /// 1. `[]` Mint x
+    /// 2. `[]` Mint y
+    /// 3. `[signer, writable]` The payer
+    /// 4. `[]` Alice, signer when initiating
+    /// 5. `[]` Bob, signer when initiating
+    /// 6. `[]` The rent sysvar
+    /// 7. `[]` The system program
+    InitDelegatedEscrow {
+        amount_x: u64,
+        amount_y: u64,
+        pass: [u8; 32],
+    },
+    /// Performs both delegated transfers of a delegated escrow, or neither if an allowance is missing
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The escrow account
+    /// 1. `[writable]` Alice's token account for mint x
+    /// 2. `[writable]` Alice's token account for mint y
+    /// 3. `[writable]` Bob's token account for mint x
+    /// 4. `[writable]` Bob's token account for mint y
+    /// 5. `[]` The token program
+    SettleDelegated {
+        pass: [u8; 32],
+    },
+    /// Puts `amount_x` of X up for auction and locks it in the auction's vault x
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The auction account, PDA of `["auction", seller, mint_x, mint_y, pass]`
+    /// 1. `[]` Mint x, the item being sold
+    /// 2. `[]` Mint y, the bidding currency
+    /// 3. `[writable]` The vault for mint x, PDA of `["vault_x", auction]`
+    /// 4. `[writable]` The vault for mint y, PDA of `["vault_y", auction]`
+    /// 5. `[signer, writable]` The seller
+    /// 6. `[writable]` The seller's token account for mint x
+    /// 7. `[]` The token program
+    /// 8. `[]` The rent sysvar
+    /// 9. `[]` The system program
+    InitAuction {
+        amount_x: u64,
+        reserve_price: u64,
+        min_increment: u64,
+        end_ts: i64,
+        pass: [u8; 32],
+    },
+    /// Escrows a higher bid in vault y and refunds the previous top bidder
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The auction account
+    /// 1. `[writable]` The vault for mint y
+    /// 2. `[writable]` The bidder's token account for mint y
+    /// 3. `[writable]` The previous top bidder's token account for mint y, ignored on the first bid
+    /// 4. `[signer]` The bidder
+    /// 5. `[]` The token program
+    /// 6. `[]` The clock sysvar
+    Bid {
+        amount: u64,
+        pass: [u8; 32],
+    },
+    /// After the end time pays the top bid to the seller and X to the winner, or returns X to the seller without bids
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The auction account
+    /// 1. `[writable]` The vault for mint x
+    /// 2. `[writable]` The vault for mint y
+    /// 3. `[writable]` The seller's token account for mint x
+    /// 4. `[writable]` The seller's token account for mint y
+    /// 5. `[writable]` The winner's token account for mint x, ignored without bids
+    /// 6. `[]` The token program
+    /// 7. `[]` The clock sysvar
+    SettleAuction {
+        pass: [u8; 32],
+    },
+    /// Puts `amount_x` of X up for a Dutch auction and locks it in the auction's vault
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The auction account, PDA of `["dutch_auction", seller, mint_x, mint_y, pass]`
+    /// 1. `[]` Mint x, the item being sold
+    /// 2. `[]` Mint y, the payment currency
+    /// 3. `[writable]` The vault for mint x, PDA of `["vault", auction]`
+    /// 4. `[signer, writable]` The seller
+    /// 5. `[writable]` The seller's token account for mint x
+    /// 6. `[]` The token program
+    /// 7. `[]` The rent sysvar
+    /// 8. `[]` The system program
+    InitDutchAuction {
+        terms: DutchAuctionTerms,
+        pass: [u8; 32],
+    },
+    /// Pays the current price in Y to the seller and sends X to the buyer, failing if it exceeds `max_price`
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The auction account
+    /// 1. `[writable]` The vault for mint x
+    /// 2. `[writable]` The buyer's token account for mint y
+    /// 3. `[writable]` The seller's token account for mint y
+    /// 4. `[writable]` The buyer's token account for mint x
+    /// 5. `[signer]` The buyer
+    /// 6. `[]` The token program
+    /// 7. `[]` The clock sysvar
+    BuyDutch {
+        max_price: u64,
+        pass: [u8; 32],
+    },
+    /// Returns X to the seller of an unsold Dutch auction
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The auction account
+    /// 1. `[writable]` The vault for mint x
+    /// 2. `[writable]` The seller's token account for mint x
+    /// 3. `[signer]` The seller
+    /// 4. `[]` The token program
+    CancelDutchAuction {
+        pass: [u8; 32],
+    },
+    /// Cancels a swap with the consent of both parties, returning every deposit and posted bond to its owner
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The escrow account
+    /// 1. `[writable]` The vault for mint x
+    /// 2. `[writable]` The vault for mint y
+    /// 3. `[writable]` Alice's token account for mint x
+    /// 4. `[writable]` Bob's token account for mint y
+    /// 5. `[signer]` Alice, writable to get the bond back
+    /// 6. `[signer]` Bob, writable to get the bond back
+    /// 7. `[]` The token program
+    MutualRefund {
+        pass: [u8; 32],
+    },
+    /// Proposes new amounts for an escrow nobody has deposited into yet, replacing any pending proposal
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The escrow account
+    /// 1. `[signer]` Alice or bob
+    Propose {
+        amount_x: u64,
+        amount_y: u64,
+        pass: [u8; 32],
+    },
+    /// Accepts an escrow opened by the counterparty, or the counterparty's pending proposal, which replaces the escrow amounts
+    /// `amount_x` and `amount_y` must match the amounts being accepted
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The escrow account
+    /// 1. `[signer]` The counterparty of the initiator or proposer, writable to post a bond
+    /// 2. `[]` The system program, only when accepting a bonded escrow
+    /// 3. `[]` The clock sysvar, only when accepting a bonded escrow
+    Accept {
+        amount_x: u64,
+        amount_y: u64,
+        pass: [u8; 32],
+    },
+    /// Splits what the signer withdraws from the escrow between up to `MAX_PAYOUTS` recipients, an empty list pays the signer alone
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The escrow account
+    /// 1. `[signer]` Alice or bob
+    SetPayouts {
+        recipients: Vec<Payout>, // basis points must add up to 10,000
+        pass: [u8; 32],
+    },
+    /// Funds a Merkle distribution of `total` and creates the bitmap tracking claimed leaves
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The distributor account, PDA of `["distributor", depositor, mint, pass]`
+    /// 1. `[]` The mint being distributed
+    /// 2. `[writable]` The vault, PDA of `["vault", distributor]`
+    /// 3. `[writable]` The claim bitmap, PDA of `["bitmap", distributor]`
+    /// 4. `[signer, writable]` The depositor
+    /// 5. `[writable]` The depositor's token account
+    /// 6. `[]` The token program
+    /// 7. `[]` The rent sysvar
+    /// 8. `[]` The system program
+    InitDistributor {
+        merkle_root: [u8; 32],
+        leaf_count: u32,
+        total: u64,
+        expiry: i64,
+        pass: [u8; 32],
+    },
+    /// Pays the leaf `(index, recipient, amount)` proven against the Merkle root to its recipient
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The distributor account
+    /// 1. `[writable]` The claim bitmap
+    /// 2. `[writable]` The vault
+    /// 3. `[writable]` The recipient's token account, its owner is the leaf recipient
+    /// 4. `[]` The token program
+    /// 5. `[]` The clock sysvar
+    ClaimDistribution {
+        index: u32,
+        amount: u64,
+        proof: Vec<[u8; 32]>,
+        pass: [u8; 32],
+    },
+    /// Returns whatever is left unclaimed to the depositor after the expiry
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The distributor account
+    /// 1. `[writable]` The vault
+    /// 2. `[writable]` The depositor's token account
+    /// 3. `[signer]` The depositor
+    /// 4. `[]` The token program
+    /// 5. `[]` The clock sysvar
+    ReclaimDistribution {
+        pass: [u8; 32],
+    },
+    /// Creates a ring swap between up to `MAX_RING_LEGS` participants
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The ring account, PDA of `["ring", creator, pass]`
+    /// 1. `[signer, writable]` The creator
+    /// 2. `[]` The rent sysvar
+    /// 3. `[]` The system program
+    InitRing {
+        legs: Vec<RingLeg>, // `owed_from` must map the legs onto each other with nobody owed their own deposit
+        pass: [u8; 32],
+    },
+    /// Deposits the leg at `index` into its vault, creating the vault on first use
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The ring account
+    /// 1. `[]` The mint of the leg
+    /// 2. `[writable]` The vault of the leg, PDA of `["ring_vault", ring, index]`
+    /// 3. `[writable]` The participant's token account for the mint of the leg
+    /// 4. `[signer, writable]` The participant
+    /// 5. `[]` The token program
+    /// 6. `[]` The rent sysvar
+    /// 7. `[]` The system program
+    RingDeposit {
+        index: u8,
+        pass: [u8; 32],
+    },
+    /// Pays the participant at `index` the deposit they are owed once everyone has deposited, refunds their own deposit before that
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The ring account
+    /// 1. `[writable]` The vault of the owed leg, or of the participant's own leg for a refund
+    /// 2. `[writable]` The participant's token account receiving the tokens
+    /// 3. `[signer]` The participant
+    /// 4. `[]` The token program
+    RingWithdraw {
+        index: u8,
+        pass: [u8; 32],
+    },
+    /// Settles several committed or agreed but unfunded escrows between the same two parties and mints at once.
+    /// Committed legs are paid out of their vaults towards the net amount per mint, the parties only cover the rest
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The first party
+    /// 1. `[signer]` The second party
+    /// 2. `[writable]` The first party's token account for the first escrow's mint x
+    /// 3. `[writable]` The first party's token account for the first escrow's mint y
+    /// 4. `[writable]` The second party's token account for the first escrow's mint x
+    /// 5. `[writable]` The second party's token account for the first escrow's mint y
+    /// 6. `[]` The token program
+    /// 7. `[writable]` The escrow accounts, one per pass and in the same order, each committed one followed by
+    ///    `[writable]` its vault for mint x and `[writable]` its vault for mint y
+    NetSettle {
+        passes: Vec<[u8; 32]>,
+    },
+    /// Locks the buyer's payment until the attestor confirms delivery or the deadline passes
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The delivery account, PDA of `["delivery", buyer, seller, mint, pass]`
+    /// 1. `[]` The mint of the payment
+    /// 2. `[writable]` The vault, PDA of `["vault", delivery]`
+    /// 3. `[signer, writable]` The buyer
+    /// 4. `[writable]` The buyer's token account
+    /// 5. `[]` The seller
+    /// 6. `[]` The token program
+    /// 7. `[]` The rent sysvar
+    /// 8. `[]` The system program
+    InitDelivery {
+        amount: u64,
+        attestor: Pubkey,
+        deadline: i64,
+        pass: [u8; 32],
+    },
+    /// Pays the seller before the deadline, given an Ed25519 program instruction earlier in the transaction
+    /// carrying the attestor's signature of the delivery message
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The delivery account
+    /// 1. `[writable]` The vault
+    /// 2. `[writable]` The seller's token account
+    /// 3. `[]` The instructions sysvar
+    /// 4. `[]` The token program
+    /// 5. `[]` The clock sysvar
+    ConfirmDelivery {
+        pass: [u8; 32],
+    },
+    /// Refunds the buyer once the deadline has passed without a confirmed delivery
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The delivery account
+    /// 1. `[writable]` The vault
+    /// 2. `[writable]` The buyer's token account
+    /// 3. `[]` The token program
+    /// 4. `[]` The clock sysvar
+    RefundDelivery {
+        pass: [u8; 32],
+    },
+    /// After the deposit deadline of a bonded escrow that is not funded on both sides, returns the claimant's bond,
+    /// and also pays them the counterparty's bond if only the claimant's leg is funded. Once both legs are
+    /// committed, returns the claimant's bond at any time
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The escrow account
+    /// 1. `[signer, writable]` Alice or bob
+    /// 2. `[]` The clock sysvar
+    ClaimBond {
+        pass: [u8; 32],
+    },
+    /// Starts closing a committed payment channel without the counterparty, from the deposit split as the
+    /// nonce 0 balance: alice keeps X and bob keeps Y unless a newer co-signed state is submitted before the
+    /// challenge period ends
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The escrow account
+    /// 1. `[signer]` Alice or bob
+    /// 2. `[]` The clock sysvar
+    CloseChannel {
+        pass: [u8; 32],
+    },
 }
\ No newline at end of file
@@ -1,3 +1,4 @@
+pub mod ed25519;
 pub mod instruction;
 pub mod processor;
 pub mod state;
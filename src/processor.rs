@@ -4,16 +4,28 @@ use solana_program::{
     entrypoint::ProgramResult,
     msg,
     program_error::ProgramError,
+    program_option::COption,
     program_pack::Pack,
     pubkey::Pubkey,
     system_instruction,
-    sysvar::{rent::Rent, Sysvar},
+    sysvar::{clock::Clock, rent::Rent, Sysvar},
 };
+use std::convert::TryFrom;
 
-use spl_token::{instruction::initialize_account, instruction::transfer, state::Account};
+use spl_token::{
+    instruction::initialize_account,
+    instruction::transfer,
+    state::{Account, Mint},
+};
 
+use crate::ed25519;
 use crate::instruction::EscrowInstruction;
-use crate::state::{EscrowData, EscrowState};
+use crate::state::{
+    AuctionData, CampaignData, ChannelBalance, ContributionReceipt, DeliveryData, DistributorData,
+    DutchAuctionData, DutchAuctionTerms, EscrowData, EscrowState, EscrowTerms, MilestoneEscrowData,
+    Offer, OptionData, OptionState, OptionTerms, Payout, PriceFeed, RingData, RingLeg, StreamData,
+    MAX_APPROVERS, MAX_MILESTONES, MAX_PAYOUTS, MAX_RING_LEGS,
+};
 
 pub struct Processor;
 impl Processor {
@@ -29,18 +41,287 @@ impl Processor {
                 amount_x,
                 amount_y,
                 pass,
+                terms,
             } => {
                 msg!("Instruction: InitEscrow");
-                Self::process_init_escrow(accounts, amount_x, amount_y, pass, program_id)
+                Self::process_init_escrow(accounts, amount_x, amount_y, pass, *terms, program_id)
             }
-            EscrowInstruction::Deposit { pass } => {
+            EscrowInstruction::Deposit { pass, amount } => {
                 msg!("Instruction: Deposit");
-                Self::process_deposit(accounts, pass, program_id)
+                Self::process_deposit(accounts, pass, amount, program_id)
             }
             EscrowInstruction::Withdrawal { pass } => {
                 msg!("Instruction: Withdrawal");
                 Self::process_withdrawal(accounts, pass, program_id)
             }
+            EscrowInstruction::Approve { pass } => {
+                msg!("Instruction: Approve");
+                Self::process_approve(accounts, pass, program_id)
+            }
+            EscrowInstruction::Claim { pass } => {
+                msg!("Instruction: Claim");
+                Self::process_claim(accounts, pass, program_id)
+            }
+            EscrowInstruction::Fund { pass } => {
+                msg!("Instruction: Fund");
+                Self::process_fund(accounts, pass, program_id)
+            }
+            EscrowInstruction::Repay { pass } => {
+                msg!("Instruction: Repay");
+                Self::process_repay(accounts, pass, program_id)
+            }
+            EscrowInstruction::Liquidate { pass } => {
+                msg!("Instruction: Liquidate");
+                Self::process_liquidate(accounts, pass, program_id)
+            }
+            EscrowInstruction::Rent { pass } => {
+                msg!("Instruction: Rent");
+                Self::process_rent(accounts, pass, program_id)
+            }
+            EscrowInstruction::ReturnRental { pass } => {
+                msg!("Instruction: ReturnRental");
+                Self::process_return_rental(accounts, pass, program_id)
+            }
+            EscrowInstruction::ClaimCollateral { pass } => {
+                msg!("Instruction: ClaimCollateral");
+                Self::process_claim_collateral(accounts, pass, program_id)
+            }
+            EscrowInstruction::SubmitChannelState { balance, pass } => {
+                msg!("Instruction: SubmitChannelState");
+                Self::process_submit_channel_state(accounts, balance, pass, program_id)
+            }
+            EscrowInstruction::SettleChannel { pass } => {
+                msg!("Instruction: SettleChannel");
+                Self::process_settle_channel(accounts, pass, program_id)
+            }
+            EscrowInstruction::CloseChannel { pass } => {
+                msg!("Instruction: CloseChannel");
+                Self::process_close_channel(accounts, pass, program_id)
+            }
+            EscrowInstruction::TakeOffer { offer } => {
+                msg!("Instruction: TakeOffer");
+                Self::process_take_offer(accounts, offer, program_id)
+            }
+            EscrowInstruction::InitDelegatedEscrow {
+                amount_x,
+                amount_y,
+                pass,
+            } => {
+                msg!("Instruction: InitDelegatedEscrow");
+                Self::process_init_delegated_escrow(accounts, amount_x, amount_y, pass, program_id)
+            }
+            EscrowInstruction::SettleDelegated { pass } => {
+                msg!("Instruction: SettleDelegated");
+                Self::process_settle_delegated(accounts, pass, program_id)
+            }
+            EscrowInstruction::MutualRefund { pass } => {
+                msg!("Instruction: MutualRefund");
+                Self::process_mutual_refund(accounts, pass, program_id)
+            }
+            EscrowInstruction::Propose {
+                amount_x,
+                amount_y,
+                pass,
+            } => {
+                msg!("Instruction: Propose");
+                Self::process_propose(accounts, amount_x, amount_y, pass, program_id)
+            }
+            EscrowInstruction::Accept {
+                amount_x,
+                amount_y,
+                pass,
+            } => {
+                msg!("Instruction: Accept");
+                Self::process_accept(accounts, amount_x, amount_y, pass, program_id)
+            }
+            EscrowInstruction::SetPayouts { recipients, pass } => {
+                msg!("Instruction: SetPayouts");
+                Self::process_set_payouts(accounts, recipients, pass, program_id)
+            }
+            EscrowInstruction::NetSettle { passes } => {
+                msg!("Instruction: NetSettle");
+                Self::process_net_settle(accounts, passes, program_id)
+            }
+            EscrowInstruction::ClaimBond { pass } => {
+                msg!("Instruction: ClaimBond");
+                Self::process_claim_bond(accounts, pass, program_id)
+            }
+            EscrowInstruction::InitAuction {
+                amount_x,
+                reserve_price,
+                min_increment,
+                end_ts,
+                pass,
+            } => {
+                msg!("Instruction: InitAuction");
+                Self::process_init_auction(
+                    accounts,
+                    amount_x,
+                    reserve_price,
+                    min_increment,
+                    end_ts,
+                    pass,
+                    program_id,
+                )
+            }
+            EscrowInstruction::Bid { amount, pass } => {
+                msg!("Instruction: Bid");
+                Self::process_bid(accounts, amount, pass, program_id)
+            }
+            EscrowInstruction::SettleAuction { pass } => {
+                msg!("Instruction: SettleAuction");
+                Self::process_settle_auction(accounts, pass, program_id)
+            }
+            EscrowInstruction::InitDutchAuction { terms, pass } => {
+                msg!("Instruction: InitDutchAuction");
+                Self::process_init_dutch_auction(accounts, terms, pass, program_id)
+            }
+            EscrowInstruction::BuyDutch { max_price, pass } => {
+                msg!("Instruction: BuyDutch");
+                Self::process_buy_dutch(accounts, max_price, pass, program_id)
+            }
+            EscrowInstruction::CancelDutchAuction { pass } => {
+                msg!("Instruction: CancelDutchAuction");
+                Self::process_cancel_dutch_auction(accounts, pass, program_id)
+            }
+            EscrowInstruction::InitDistributor {
+                merkle_root,
+                leaf_count,
+                total,
+                expiry,
+                pass,
+            } => {
+                msg!("Instruction: InitDistributor");
+                Self::process_init_distributor(
+                    accounts,
+                    merkle_root,
+                    leaf_count,
+                    total,
+                    expiry,
+                    pass,
+                    program_id,
+                )
+            }
+            EscrowInstruction::ClaimDistribution {
+                index,
+                amount,
+                proof,
+                pass,
+            } => {
+                msg!("Instruction: ClaimDistribution");
+                Self::process_claim_distribution(accounts, index, amount, proof, pass, program_id)
+            }
+            EscrowInstruction::ReclaimDistribution { pass } => {
+                msg!("Instruction: ReclaimDistribution");
+                Self::process_reclaim_distribution(accounts, pass, program_id)
+            }
+            EscrowInstruction::InitRing { legs, pass } => {
+                msg!("Instruction: InitRing");
+                Self::process_init_ring(accounts, legs, pass, program_id)
+            }
+            EscrowInstruction::RingDeposit { index, pass } => {
+                msg!("Instruction: RingDeposit");
+                Self::process_ring_deposit(accounts, index, pass, program_id)
+            }
+            EscrowInstruction::RingWithdraw { index, pass } => {
+                msg!("Instruction: RingWithdraw");
+                Self::process_ring_withdraw(accounts, index, pass, program_id)
+            }
+            EscrowInstruction::InitDelivery {
+                amount,
+                attestor,
+                deadline,
+                pass,
+            } => {
+                msg!("Instruction: InitDelivery");
+                Self::process_init_delivery(accounts, amount, attestor, deadline, pass, program_id)
+            }
+            EscrowInstruction::ConfirmDelivery { pass } => {
+                msg!("Instruction: ConfirmDelivery");
+                Self::process_confirm_delivery(accounts, pass, program_id)
+            }
+            EscrowInstruction::RefundDelivery { pass } => {
+                msg!("Instruction: RefundDelivery");
+                Self::process_refund_delivery(accounts, pass, program_id)
+            }
+            EscrowInstruction::InitMilestoneEscrow {
+                amounts,
+                refund_after,
+                pass,
+            } => {
+                msg!("Instruction: InitMilestoneEscrow");
+                Self::process_init_milestone_escrow(
+                    accounts,
+                    amounts,
+                    refund_after,
+                    pass,
+                    program_id,
+                )
+            }
+            EscrowInstruction::ReleaseMilestone { index, pass } => {
+                msg!("Instruction: ReleaseMilestone");
+                Self::process_release_milestone(accounts, index, pass, program_id)
+            }
+            EscrowInstruction::RefundMilestones { pass } => {
+                msg!("Instruction: RefundMilestones");
+                Self::process_refund_milestones(accounts, pass, program_id)
+            }
+            EscrowInstruction::InitStream {
+                rate_per_second,
+                amount,
+                pass,
+            } => {
+                msg!("Instruction: InitStream");
+                Self::process_init_stream(accounts, rate_per_second, amount, pass, program_id)
+            }
+            EscrowInstruction::WithdrawStream { pass } => {
+                msg!("Instruction: WithdrawStream");
+                Self::process_withdraw_stream(accounts, pass, program_id)
+            }
+            EscrowInstruction::TopUpStream { amount, pass } => {
+                msg!("Instruction: TopUpStream");
+                Self::process_top_up_stream(accounts, amount, pass, program_id)
+            }
+            EscrowInstruction::CancelStream { pass } => {
+                msg!("Instruction: CancelStream");
+                Self::process_cancel_stream(accounts, pass, program_id)
+            }
+            EscrowInstruction::InitCampaign {
+                goal,
+                deadline,
+                pass,
+            } => {
+                msg!("Instruction: InitCampaign");
+                Self::process_init_campaign(accounts, goal, deadline, pass, program_id)
+            }
+            EscrowInstruction::Contribute { amount, pass } => {
+                msg!("Instruction: Contribute");
+                Self::process_contribute(accounts, amount, pass, program_id)
+            }
+            EscrowInstruction::WithdrawCampaign { pass } => {
+                msg!("Instruction: WithdrawCampaign");
+                Self::process_withdraw_campaign(accounts, pass, program_id)
+            }
+            EscrowInstruction::Refund { pass } => {
+                msg!("Instruction: Refund");
+                Self::process_refund(accounts, pass, program_id)
+            }
+            EscrowInstruction::WriteOption { terms, pass } => {
+                msg!("Instruction: WriteOption");
+                Self::process_write_option(accounts, terms, pass, program_id)
+            }
+            EscrowInstruction::BuyOption { pass } => {
+                msg!("Instruction: BuyOption");
+                Self::process_buy_option(accounts, pass, program_id)
+            }
+            EscrowInstruction::ExerciseOption { pass } => {
+                msg!("Instruction: ExerciseOption");
+                Self::process_exercise_option(accounts, pass, program_id)
+            }
+            EscrowInstruction::ReclaimOption { pass } => {
+                msg!("Instruction: ReclaimOption");
+                Self::process_reclaim_option(accounts, pass, program_id)
+            }
         }
     }
 
@@ -49,8 +330,98 @@ impl Processor {
         size_x: u64,
         size_y: u64,
         pass: [u8; 32],
+        terms: EscrowTerms,
         program_id: &Pubkey,
     ) -> ProgramResult {
+        let EscrowTerms {
+            approvers,
+            threshold,
+            vesting,
+            loan,
+            rental,
+            channel,
+            settle_after,
+            prerequisite,
+            oracle,
+            bond,
+        } = terms;
+        if approvers.len() > MAX_APPROVERS
+            || threshold as usize > approvers.len()
+            || (threshold == 0 && !approvers.is_empty())
+        {
+            msg!("Invalid approvers");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        for (i, approver) in approvers.iter().enumerate() {
+            if approvers[..i].contains(approver) {
+                msg!("Duplicate approver");
+                return Err(ProgramError::InvalidInstructionData);
+            }
+        }
+        let modes = [
+            vesting.is_some(),
+            loan.is_some(),
+            rental.is_some(),
+            channel.is_some(),
+        ];
+        if modes.iter().filter(|mode| **mode).count() > 1 {
+            msg!("Only one escrow mode can be set");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        if let Some(schedule) = vesting {
+            if !schedule.is_valid() || size_y != 0 {
+                msg!("Invalid vesting schedule");
+                return Err(ProgramError::InvalidInstructionData);
+            }
+        }
+        if let Some(terms) = loan {
+            // repay and liquidate are bilateral and never consult approvers
+            if terms.due_ts == 0
+                || terms.repayment_amount(size_y).is_none()
+                || !approvers.is_empty()
+            {
+                msg!("Invalid loan terms");
+                return Err(ProgramError::InvalidInstructionData);
+            }
+        }
+        if let Some(terms) = rental {
+            // rent and collateral claims are not gated on approvals either
+            if terms.duration <= 0 || size_x != 1 || !approvers.is_empty() {
+                msg!("Invalid rental terms");
+                return Err(ProgramError::InvalidInstructionData);
+            }
+        }
+        if let Some(terms) = channel {
+            // channels settle from co-signed balances alone
+            if terms.challenge_period <= 0 || !approvers.is_empty() {
+                msg!("Invalid channel terms");
+                return Err(ProgramError::InvalidInstructionData);
+            }
+        }
+        if settle_after < 0 || (settle_after != 0 && modes.contains(&true)) {
+            msg!("Only plain swaps can be forwards");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        if prerequisite.is_some() && modes.contains(&true) {
+            msg!("Only plain swaps can be chained");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        if let Some(condition) = oracle {
+            if condition.pubkey_oracle == Pubkey::default()
+                || condition.max_staleness <= 0
+                || modes.contains(&true)
+            {
+                msg!("Invalid oracle condition");
+                return Err(ProgramError::InvalidInstructionData);
+            }
+        }
+        if let Some(terms) = bond {
+            if terms.amount == 0 || terms.deposit_deadline <= 0 || modes.contains(&true) {
+                msg!("Invalid bond terms");
+                return Err(ProgramError::InvalidInstructionData);
+            }
+        }
+
         let account_info_iter = &mut accounts.iter();
         let escrow_info = next_account_info(account_info_iter)?;
         let mint_x_info = next_account_info(account_info_iter)?;
@@ -64,6 +435,40 @@ impl Processor {
         let rent_info = next_account_info(account_info_iter)?;
         let system_program_info = next_account_info(account_info_iter)?;
 
+        if let Some(prerequisite) = prerequisite {
+            // the prerequisite has to be live already and can never change, so chains cannot loop
+            let prerequisite_info = next_account_info(account_info_iter)?;
+            if prerequisite.pubkey_escrow == *escrow_info.key
+                || prerequisite.pubkey_escrow != *prerequisite_info.key
+            {
+                msg!("Invalid prerequisite");
+                return Err(ProgramError::InvalidAccountData);
+            }
+            if read_escrow_state(prerequisite_info, prerequisite.pass, program_id)?
+                == EscrowState::Uninitialized
+            {
+                msg!("Prerequisite escrow is not initialized");
+                return Err(ProgramError::InvalidAccountData);
+            }
+        }
+
+        let initiator = if alice_info.is_signer {
+            *alice_info.key
+        } else if bob_info.is_signer {
+            *bob_info.key
+        } else {
+            msg!("Alice or bob must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        };
+
+        if rental.is_some() {
+            let mint_x = Mint::unpack(&mint_x_info.data.borrow())?;
+            if mint_x.decimals != 0 {
+                msg!("Rented token must have 0 decimals");
+                return Err(ProgramError::InvalidAccountData);
+            }
+        }
+
         let escrow_bump = if escrow_info.data_len() == 0 {
             msg!("Creating escrow metadata");
             let escrow_seeds = &[
@@ -121,31 +526,20 @@ impl Processor {
             msg!("Creating vault for mint x");
             let bump = create_vault(
                 program_id,
-                &vault_x_info,
-                &alice_info,
-                &bob_info,
-                &mint_x_info,
-                &mint_y_info,
-                &payer_info,
-                &token_program_info,
-                &rent_info,
-                &system_program_info,
-                pass,
-                b"vault_x",
-            )?;
-            solana_program::program::invoke(
-                &initialize_account(
-                    token_program_info.key,
-                    vault_x_info.key,
-                    mint_x_info.key,
-                    escrow_info.key,
-                )?,
+                vault_x_info,
+                mint_x_info,
+                escrow_info,
+                payer_info,
+                token_program_info,
+                rent_info,
+                system_program_info,
                 &[
-                    vault_x_info.clone(),
-                    mint_x_info.clone(),
-                    escrow_info.clone(),
-                    rent_info.clone(),
-                    token_program_info.clone(),
+                    b"vault_x",
+                    alice_info.key.as_ref(),
+                    bob_info.key.as_ref(),
+                    mint_x_info.key.as_ref(),
+                    mint_y_info.key.as_ref(),
+                    pass.as_ref(),
                 ],
             )?;
             bump
@@ -165,31 +559,20 @@ impl Processor {
             msg!("Creating vault for mint y");
             let bump = create_vault(
                 program_id,
-                &vault_y_info,
-                &alice_info,
-                &bob_info,
-                &mint_x_info,
-                &mint_y_info,
-                &payer_info,
-                &token_program_info,
-                &rent_info,
-                &system_program_info,
-                pass,
-                b"vault_y",
-            )?;
-            solana_program::program::invoke(
-                &initialize_account(
-                    token_program_info.key,
-                    vault_y_info.key,
-                    mint_y_info.key,
-                    escrow_info.key,
-                )?,
+                vault_y_info,
+                mint_y_info,
+                escrow_info,
+                payer_info,
+                token_program_info,
+                rent_info,
+                system_program_info,
                 &[
-                    vault_y_info.clone(),
-                    mint_y_info.clone(),
-                    escrow_info.clone(),
-                    rent_info.clone(),
-                    token_program_info.clone(),
+                    b"vault_y",
+                    alice_info.key.as_ref(),
+                    bob_info.key.as_ref(),
+                    mint_x_info.key.as_ref(),
+                    mint_y_info.key.as_ref(),
+                    pass.as_ref(),
                 ],
             )?;
             bump
@@ -212,6 +595,18 @@ impl Processor {
             return Err(ProgramError::InvalidAccountData.into());
         }
 
+        if let Some(terms) = bond {
+            msg!("Posting bonds");
+            for party_info in [alice_info, bob_info].iter() {
+                if party_info.is_signer {
+                    post_bond(party_info, escrow_info, system_program_info, terms.amount)?;
+                }
+            }
+        }
+
+        let mut approver_keys = [Pubkey::default(); MAX_APPROVERS];
+        approver_keys[..approvers.len()].copy_from_slice(&approvers);
+
         EscrowData {
             size_x,
             size_y,
@@ -219,18 +614,53 @@ impl Processor {
             pubkey_bob: *bob_info.key,
             pubkey_mint_x: *mint_x_info.key,
             pubkey_mint_y: *mint_y_info.key,
-            state: EscrowState::Initialized,
+            state: if alice_info.is_signer && bob_info.is_signer {
+                EscrowState::Initialized
+            } else {
+                EscrowState::Proposed
+            },
             escrow_bump,
             vault_x_bump,
             vault_y_bump,
+            approvers: approver_keys,
+            approver_count: approvers.len() as u8,
+            approval_threshold: threshold,
+            approvals: 0,
+            vesting: vesting.unwrap_or_default(),
+            claimed: 0,
+            loan: loan.unwrap_or_default(),
+            rental: rental.unwrap_or_default(),
+            rental_due_ts: 0,
+            channel: channel.unwrap_or_default(),
+            channel_balance: ChannelBalance::default(),
+            channel_closes_at: 0,
+            delegated: false,
+            settle_after,
+            proposal_x: 0,
+            proposal_y: 0,
+            pubkey_proposer: Pubkey::default(),
+            pubkey_initiator: initiator,
+            deposited_x: 0,
+            deposited_y: 0,
+            pubkey_prerequisite: prerequisite.unwrap_or_default().pubkey_escrow,
+            prerequisite_pass: prerequisite.unwrap_or_default().pass,
+            oracle: oracle.unwrap_or_default(),
+            bond: bond.unwrap_or_default(),
+            bond_posted_alice: bond.is_some() && alice_info.is_signer,
+            bond_posted_bob: bond.is_some() && bob_info.is_signer,
+            payouts_alice: [Payout::default(); MAX_PAYOUTS],
+            payout_count_alice: 0,
+            payouts_bob: [Payout::default(); MAX_PAYOUTS],
+            payout_count_bob: 0,
         }
-        .serialize(&mut *escrow_info.data.borrow_mut())?;
+        .serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
         Ok(())
     }
 
     pub fn process_deposit(
         accounts: &[AccountInfo],
         pass: [u8;32],
+        amount: u64,
         program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
@@ -240,13 +670,39 @@ impl Processor {
         let payer_info = next_account_info(account_info_iter)?; // payer_account, is it both public and private key? yeah
         let token_program_info = next_account_info(account_info_iter)?; // token_program_id
         let mut escrow_data = EscrowData::try_from_slice(&escrow_info.data.borrow())?;
+        if escrow_data.delegated {
+            msg!("Delegated escrow settles with SettleDelegated");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if escrow_data.is_vesting() && *payer_info.key == escrow_data.pubkey_bob {
+            msg!("Vesting escrow has no Y leg");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if escrow_data.is_loan() && *payer_info.key == escrow_data.pubkey_bob {
+            msg!("Loan principal is provided with Fund");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if escrow_data.is_rental() && *payer_info.key == escrow_data.pubkey_bob {
+            msg!("Rental collateral is provided with Rent");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if escrow_data.is_bonded() {
+            let clock_info = next_account_info(account_info_iter)?;
+            if Clock::from_account_info(clock_info)?.unix_timestamp
+                >= escrow_data.bond.deposit_deadline
+            {
+                msg!("Deposit deadline has passed");
+                return Err(ProgramError::InvalidAccountData);
+            }
+        }
         msg!("Validating and chaning state");
-        match escrow_data.state {
+        escrow_data.pubkey_proposer = Pubkey::default(); // a deposit settles the terms
+        let funded_state = match escrow_data.state {
             EscrowState::Initialized => {
                 if *payer_info.key == escrow_data.pubkey_alice {
-                    escrow_data.state = EscrowState::DepositAlice;
+                    EscrowState::DepositAlice
                 } else if *payer_info.key == escrow_data.pubkey_bob {
-                    escrow_data.state = EscrowState::DepositBob;
+                    EscrowState::DepositBob
                 } else {
                     msg!("Invalid State");
                     return Err(ProgramError::InvalidAccountData);
@@ -254,7 +710,7 @@ impl Processor {
             }
             EscrowState::DepositAlice => {
                 if *payer_info.key == escrow_data.pubkey_bob {
-                    escrow_data.state = EscrowState::Committed;
+                    EscrowState::Committed
                 } else {
                     msg!("Invalid State");
                     return Err(ProgramError::InvalidAccountData);
@@ -262,7 +718,7 @@ impl Processor {
             }
             EscrowState::DepositBob => {
                 if *payer_info.key == escrow_data.pubkey_alice {
-                    escrow_data.state = EscrowState::Committed;
+                    EscrowState::Committed
                 } else {
                     msg!("Invalid State");
                     return Err(ProgramError::InvalidAccountData);
@@ -272,7 +728,7 @@ impl Processor {
                 msg!("Invalid State");
                 return Err(ProgramError::InvalidAccountData);
             }
-        }
+        };
 
         msg!("Validating account ownership");
         if payer_token_info.owner != token_program_info.key {
@@ -301,6 +757,22 @@ impl Processor {
             msg!("Invalid Owner");
             return Err(ProgramError::InvalidAccountData);
         };
+        let deposited = if *payer_info.key == escrow_data.pubkey_alice {
+            &mut escrow_data.deposited_x
+        } else {
+            &mut escrow_data.deposited_y
+        };
+        let unfunded = size
+            .checked_sub(*deposited)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        if amount == 0 || amount > unfunded {
+            msg!("Deposit exceeds the unfunded part of the leg");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        *deposited += amount;
+        if *deposited == size {
+            escrow_data.state = funded_state;
+        }
         let seeds = &[
             vault_seed,
             escrow_data.pubkey_alice.as_ref(),
@@ -338,7 +810,7 @@ impl Processor {
                 &vault_info.key,
                 &payer_info.key,
                 &[],
-                size,
+                amount,
             )?,
             &[
                 payer_token_info.clone(),
@@ -365,14 +837,86 @@ impl Processor {
         msg!("process_withdrawal 1");
         let mut escrow_data = EscrowData::try_from_slice(&escrow_info.data.borrow())?;
 
-        let withdraw_mint = match escrow_data.state {
+        if escrow_data.delegated {
+            msg!("Delegated escrow settles with SettleDelegated");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        match escrow_data.state {
+            EscrowState::Committed | EscrowState::WithdrawAlice | EscrowState::WithdrawBob
+                if !escrow_data.is_approved() =>
+            {
+                msg!("Release not approved");
+                return Err(ProgramError::InvalidAccountData);
+            }
+            EscrowState::DepositAlice if escrow_data.is_vesting() => {
+                msg!("Vesting escrow can only be claimed");
+                return Err(ProgramError::InvalidAccountData);
+            }
+            EscrowState::Committed if escrow_data.is_channel() => {
+                msg!("Payment channel is paid out with SettleChannel");
+                return Err(ProgramError::InvalidAccountData);
+            }
+            _ => {}
+        }
+        let now = if escrow_data.is_forward() || escrow_data.is_oracle_gated() {
+            let clock_info = next_account_info(account_info_iter)?;
+            Clock::from_account_info(clock_info)?.unix_timestamp
+        } else {
+            0
+        };
+        if escrow_data.is_forward() && now < escrow_data.settle_after {
+            match escrow_data.state {
+                EscrowState::Committed => {
+                    msg!("Forward cannot settle before settle_after");
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                EscrowState::Initialized | EscrowState::DepositAlice | EscrowState::DepositBob => {
+                    msg!("Forward can only be refunded with MutualRefund before settle_after");
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                _ => {}
+            }
+        }
+
+        match escrow_data.state {
+            EscrowState::Committed | EscrowState::WithdrawAlice | EscrowState::WithdrawBob
+                if escrow_data.is_chained() =>
+            {
+                let prerequisite_info = next_account_info(account_info_iter)?;
+                if *prerequisite_info.key != escrow_data.pubkey_prerequisite {
+                    msg!("Prerequisite key mismatch");
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                if read_escrow_state(prerequisite_info, escrow_data.prerequisite_pass, program_id)?
+                    != EscrowState::Completed
+                {
+                    msg!("Prerequisite escrow is not completed");
+                    return Err(ProgramError::InvalidAccountData);
+                }
+            }
+            _ => {}
+        }
+        if escrow_data.state == EscrowState::Committed && escrow_data.is_oracle_gated() {
+            let oracle_info = next_account_info(account_info_iter)?;
+            if *oracle_info.key != escrow_data.oracle.pubkey_oracle {
+                msg!("Oracle key mismatch");
+                return Err(ProgramError::InvalidAccountData);
+            }
+            let feed = PriceFeed::deserialize(&mut &oracle_info.data.borrow()[..])?;
+            if !escrow_data.oracle.is_met(&feed, now) {
+                msg!("Price condition not met");
+                return Err(ProgramError::InvalidAccountData);
+            }
+        }
+
+        let (withdraw_mint, size, pays_out) = match escrow_data.state {
             EscrowState::Committed => {
                 if *taker_info.key == escrow_data.pubkey_alice {
                     escrow_data.state = EscrowState::WithdrawAlice;
-                    escrow_data.pubkey_mint_y
+                    (escrow_data.pubkey_mint_y, escrow_data.size_y, true)
                 } else if *taker_info.key == escrow_data.pubkey_bob {
                     escrow_data.state = EscrowState::WithdrawBob;
-                    escrow_data.pubkey_mint_x
+                    (escrow_data.pubkey_mint_x, escrow_data.size_x, true)
                 } else {
                     msg!("Invalid State");
                     return Err(ProgramError::InvalidAccountData);
@@ -380,8 +924,8 @@ impl Processor {
             }
             EscrowState::WithdrawAlice => {
                 if *taker_info.key == escrow_data.pubkey_bob {
-                    escrow_data.state = EscrowState::Uninitialized;
-                    escrow_data.pubkey_mint_x
+                    escrow_data.state = EscrowState::Completed;
+                    (escrow_data.pubkey_mint_x, escrow_data.size_x, true)
                 } else {
                     msg!("Invalid State");
                     return Err(ProgramError::InvalidAccountData);
@@ -389,28 +933,31 @@ impl Processor {
             }
             EscrowState::WithdrawBob => {
                 if *taker_info.key == escrow_data.pubkey_alice {
-                    escrow_data.state = EscrowState::Uninitialized;
-                    escrow_data.pubkey_mint_y
-                } else {
-                    msg!("Invalid State");
-                    return Err(ProgramError::InvalidAccountData);
-                }
-            }
-            EscrowState::DepositAlice => {
-                if *taker_info.key == escrow_data.pubkey_alice {
-                    escrow_data.state = EscrowState::Initialized;
-                    escrow_data.pubkey_mint_x
+                    escrow_data.state = EscrowState::Completed;
+                    (escrow_data.pubkey_mint_y, escrow_data.size_y, true)
                 } else {
                     msg!("Invalid State");
                     return Err(ProgramError::InvalidAccountData);
                 }
             }
-            EscrowState::DepositBob => {
-                if *taker_info.key == escrow_data.pubkey_alice {
-                    escrow_data.state = EscrowState::Initialized;
-                    escrow_data.pubkey_mint_y
+            EscrowState::Initialized | EscrowState::DepositAlice | EscrowState::DepositBob => {
+                // refunds whatever the taker has deposited so far, fully funded or not
+                if *taker_info.key == escrow_data.pubkey_alice && escrow_data.deposited_x > 0 {
+                    if escrow_data.state == EscrowState::DepositAlice {
+                        escrow_data.state = EscrowState::Initialized;
+                    }
+                    let refund = escrow_data.deposited_x;
+                    escrow_data.deposited_x = 0;
+                    (escrow_data.pubkey_mint_x, refund, false)
+                } else if *taker_info.key == escrow_data.pubkey_bob && escrow_data.deposited_y > 0 {
+                    if escrow_data.state == EscrowState::DepositBob {
+                        escrow_data.state = EscrowState::Initialized;
+                    }
+                    let refund = escrow_data.deposited_y;
+                    escrow_data.deposited_y = 0;
+                    (escrow_data.pubkey_mint_y, refund, false)
                 } else {
-                    msg!("Invalid State");
+                    msg!("Nothing to refund");
                     return Err(ProgramError::InvalidAccountData);
                 }
             }
@@ -425,8 +972,16 @@ impl Processor {
             msg!("Invalid Token Account (system account not owned by Token Program)");
             return Err(ProgramError::InvalidAccountData);
         }
+        let payouts = if pays_out {
+            escrow_data.payouts_of(taker_info.key)
+        } else {
+            &[]
+        };
+        let receiver = payouts
+            .first()
+            .map_or(*taker_info.key, |payout| payout.recipient);
         let token_account: Account = Account::unpack_unchecked(&taker_token_info.data.borrow())?;
-        if token_account.owner != *taker_info.key {
+        if token_account.owner != receiver {
             msg!("Invalid Token Account (\"User space\" owner mismatch)");
             return Err(ProgramError::InvalidAccountData);
         }
@@ -436,10 +991,10 @@ impl Processor {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        let (vault_seed, bump_seed, size) = if withdraw_mint == escrow_data.pubkey_mint_y {
-            (b"vault_y", escrow_data.vault_y_bump, escrow_data.size_y)
+        let (vault_seed, bump_seed) = if withdraw_mint == escrow_data.pubkey_mint_y {
+            (b"vault_y", escrow_data.vault_y_bump)
         } else if withdraw_mint == escrow_data.pubkey_mint_x {
-            (b"vault_x", escrow_data.vault_x_bump, escrow_data.size_x)
+            (b"vault_x", escrow_data.vault_x_bump)
         } else {
             msg!("Invalid Mint");
             return Err(ProgramError::InvalidAccountData);
@@ -478,87 +1033,4331 @@ impl Processor {
             return Err(ProgramError::InvalidAccountData);
         }
         msg!("Sending transfer");
-
-        solana_program::program::invoke_signed(
-            &transfer(
-                token_program_info.key,
-                &vault_info.key,
-                taker_token_info.key,
-                &escrow_info.key,
-                &[],
-                size,
-            )?,
-            &[
-                vault_info.clone(),
-                escrow_info.clone(),
-                taker_token_info.clone(),
-                token_program_info.clone(),
-            ],
-            &[escrow_seeds],
+        let mut first_share = size;
+        for payout in payouts.iter().skip(1) {
+            let recipient_token_info = next_account_info(account_info_iter)?;
+            validate_token_account(
+                recipient_token_info,
+                token_program_info,
+                &payout.recipient,
+                &withdraw_mint,
+            )?;
+            let share = payout.share_of(size);
+            first_share -= share;
+            transfer_from_vault(
+                token_program_info,
+                vault_info,
+                recipient_token_info,
+                escrow_info,
+                share,
+                escrow_seeds,
+            )?;
+        }
+        transfer_from_vault(
+            token_program_info,
+            vault_info,
+            taker_token_info,
+            escrow_info,
+            first_share,
+            escrow_seeds,
         )?;
+        if pays_out {
+            let bond = escrow_data.take_bond(taker_info.key);
+            if bond > 0 {
+                msg!("Returning bond");
+                release_bond(escrow_info, taker_info, bond)?;
+            }
+        }
 
-        escrow_data.serialize(&mut *escrow_info.data.borrow_mut())?;
+        escrow_data.serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
 
         Ok(())
     }
-}
 
-fn create_vault<'a>(
-    program_id: &Pubkey,
-    vault_info: &AccountInfo<'a>,
-    alice_info: &AccountInfo<'a>,
-    bob_info: &AccountInfo<'a>,
-    mint_x_info: &AccountInfo<'a>,
-    mint_y_info: &AccountInfo<'a>,
-    payer_info: &AccountInfo<'a>,
-    token_program_info: &AccountInfo<'a>,
-    rent_info: &AccountInfo<'a>,
-    system_program_info: &AccountInfo<'a>,
-    pass: [u8; 32],
-    vault_seed: &[u8],
-) -> Result<u8, ProgramError> {
-    let space = Account::LEN;
-    let rent = &Rent::from_account_info(rent_info)?;
-    let required_lamports = rent
-        .minimum_balance(space)
-        .max(1)
-        .saturating_sub(vault_info.lamports());
-    let seeds = &[
-        vault_seed,
-        alice_info.key.as_ref(),
-        bob_info.key.as_ref(),
-        mint_x_info.key.as_ref(),
-        mint_y_info.key.as_ref(),
-        pass.as_ref(),
-    ];
+    pub fn process_approve(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let approver_info = next_account_info(account_info_iter)?;
 
-    let (_, bump_seed) = Pubkey::find_program_address(seeds, program_id);
-    let seeds = &[
-        vault_seed,
-        alice_info.key.as_ref(),
-        bob_info.key.as_ref(),
-        mint_x_info.key.as_ref(),
-        mint_y_info.key.as_ref(),
-        pass.as_ref(),
-        &[bump_seed],
-    ];
-    msg!("creeat_vault 1");
-    msg!("seeds: {:?}", seeds);
-    solana_program::program::invoke_signed(
-        &system_instruction::create_account(
-            payer_info.key,    //from_pubkey
-            vault_info.key,    //to_pubkey
-            required_lamports, //lamports
-            space as u64,      //space
-            token_program_info.key,
-        ),
-        &[
-            payer_info.clone(),
-            vault_info.clone(),
-            system_program_info.clone(),
-        ],
-        &[seeds],
-    )?;
-    msg!("create_vault 2");
-    Ok(bump_seed)
+        if !approver_info.is_signer {
+            msg!("Approver must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let mut escrow_data = EscrowData::try_from_slice(&escrow_info.data.borrow())?;
+        validate_escrow_key(escrow_info, &escrow_data, pass, program_id)?;
+
+        match escrow_data.state {
+            EscrowState::Initialized
+            | EscrowState::DepositAlice
+            | EscrowState::DepositBob
+            | EscrowState::Committed => {}
+            _ => {
+                msg!("Invalid State");
+                return Err(ProgramError::InvalidAccountData);
+            }
+        }
+        let index = match escrow_data.approver_index(approver_info.key) {
+            Some(index) => index,
+            None => {
+                msg!("Not an approver");
+                return Err(ProgramError::InvalidAccountData);
+            }
+        };
+        escrow_data.approvals |= 1 << index;
+
+        escrow_data.serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_claim(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let bob_token_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let bob_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut escrow_data = EscrowData::try_from_slice(&escrow_info.data.borrow())?;
+        if !escrow_data.is_vesting() || escrow_data.state != EscrowState::DepositAlice {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !bob_info.is_signer || *bob_info.key != escrow_data.pubkey_bob {
+            msg!("Only bob can claim");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if !escrow_data.is_approved() {
+            msg!("Release not approved");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        validate_escrow_key(escrow_info, &escrow_data, pass, program_id)?;
+        validate_vault_key(
+            vault_info,
+            &escrow_data,
+            b"vault_x",
+            escrow_data.vault_x_bump,
+            pass,
+            program_id,
+        )?;
+        validate_token_account(
+            bob_token_info,
+            token_program_info,
+            &escrow_data.pubkey_bob,
+            &escrow_data.pubkey_mint_x,
+        )?;
+
+        let clock = Clock::from_account_info(clock_info)?;
+        let vested = escrow_data
+            .vesting
+            .vested_amount(escrow_data.size_x, clock.unix_timestamp);
+        let amount = vested.saturating_sub(escrow_data.claimed);
+        if amount == 0 {
+            msg!("Nothing vested to claim");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        msg!("Sending transfer");
+        transfer_from_vault(
+            token_program_info,
+            vault_info,
+            bob_token_info,
+            escrow_info,
+            amount,
+            &escrow_seeds(&escrow_data, &pass),
+        )?;
+
+        escrow_data.claimed += amount;
+        if escrow_data.claimed == escrow_data.size_x {
+            escrow_data.state = EscrowState::Completed;
+        }
+        escrow_data.serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_fund(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let bob_token_info = next_account_info(account_info_iter)?;
+        let alice_token_info = next_account_info(account_info_iter)?;
+        let bob_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut escrow_data = EscrowData::try_from_slice(&escrow_info.data.borrow())?;
+        if !escrow_data.is_loan() || escrow_data.state != EscrowState::DepositAlice {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !bob_info.is_signer || *bob_info.key != escrow_data.pubkey_bob {
+            msg!("Only bob can fund the loan");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let clock = Clock::from_account_info(clock_info)?;
+        if clock.unix_timestamp >= escrow_data.loan.due_ts {
+            msg!("Loan is past its due date");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        validate_escrow_key(escrow_info, &escrow_data, pass, program_id)?;
+        validate_token_account(
+            alice_token_info,
+            token_program_info,
+            &escrow_data.pubkey_alice,
+            &escrow_data.pubkey_mint_y,
+        )?;
+
+        msg!("Sending principal");
+        transfer_tokens(
+            token_program_info,
+            bob_token_info,
+            alice_token_info,
+            bob_info,
+            escrow_data.size_y,
+        )?;
+
+        escrow_data.state = EscrowState::Funded;
+        escrow_data.serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_repay(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let alice_y_token_info = next_account_info(account_info_iter)?;
+        let bob_token_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let alice_x_token_info = next_account_info(account_info_iter)?;
+        let alice_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut escrow_data = EscrowData::try_from_slice(&escrow_info.data.borrow())?;
+        if !escrow_data.is_loan() || escrow_data.state != EscrowState::Funded {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !alice_info.is_signer || *alice_info.key != escrow_data.pubkey_alice {
+            msg!("Only alice can repay the loan");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let clock = Clock::from_account_info(clock_info)?;
+        if clock.unix_timestamp > escrow_data.loan.due_ts {
+            msg!("Loan is past its due date");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        validate_escrow_key(escrow_info, &escrow_data, pass, program_id)?;
+        validate_vault_key(
+            vault_info,
+            &escrow_data,
+            b"vault_x",
+            escrow_data.vault_x_bump,
+            pass,
+            program_id,
+        )?;
+        validate_token_account(
+            bob_token_info,
+            token_program_info,
+            &escrow_data.pubkey_bob,
+            &escrow_data.pubkey_mint_y,
+        )?;
+        validate_token_account(
+            alice_x_token_info,
+            token_program_info,
+            &escrow_data.pubkey_alice,
+            &escrow_data.pubkey_mint_x,
+        )?;
+        let repayment = escrow_data
+            .loan
+            .repayment_amount(escrow_data.size_y)
+            .ok_or(ProgramError::InvalidAccountData)?;
+
+        msg!("Sending repayment");
+        transfer_tokens(
+            token_program_info,
+            alice_y_token_info,
+            bob_token_info,
+            alice_info,
+            repayment,
+        )?;
+        msg!("Returning collateral");
+        transfer_from_vault(
+            token_program_info,
+            vault_info,
+            alice_x_token_info,
+            escrow_info,
+            escrow_data.size_x,
+            &escrow_seeds(&escrow_data, &pass),
+        )?;
+
+        escrow_data.state = EscrowState::Completed;
+        escrow_data.serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_liquidate(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let bob_token_info = next_account_info(account_info_iter)?;
+        let bob_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut escrow_data = EscrowData::try_from_slice(&escrow_info.data.borrow())?;
+        if !escrow_data.is_loan() || escrow_data.state != EscrowState::Funded {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !bob_info.is_signer || *bob_info.key != escrow_data.pubkey_bob {
+            msg!("Only bob can liquidate the loan");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let clock = Clock::from_account_info(clock_info)?;
+        if clock.unix_timestamp <= escrow_data.loan.due_ts {
+            msg!("Loan is not due yet");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        validate_escrow_key(escrow_info, &escrow_data, pass, program_id)?;
+        validate_vault_key(
+            vault_info,
+            &escrow_data,
+            b"vault_x",
+            escrow_data.vault_x_bump,
+            pass,
+            program_id,
+        )?;
+        validate_token_account(
+            bob_token_info,
+            token_program_info,
+            &escrow_data.pubkey_bob,
+            &escrow_data.pubkey_mint_x,
+        )?;
+
+        msg!("Sending collateral");
+        transfer_from_vault(
+            token_program_info,
+            vault_info,
+            bob_token_info,
+            escrow_info,
+            escrow_data.size_x,
+            &escrow_seeds(&escrow_data, &pass),
+        )?;
+
+        escrow_data.state = EscrowState::Completed;
+        escrow_data.serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_rent(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let bob_y_token_info = next_account_info(account_info_iter)?;
+        let vault_y_info = next_account_info(account_info_iter)?;
+        let alice_y_token_info = next_account_info(account_info_iter)?;
+        let vault_x_info = next_account_info(account_info_iter)?;
+        let bob_x_token_info = next_account_info(account_info_iter)?;
+        let bob_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut escrow_data = EscrowData::try_from_slice(&escrow_info.data.borrow())?;
+        if !escrow_data.is_rental() || escrow_data.state != EscrowState::DepositAlice {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !bob_info.is_signer || *bob_info.key != escrow_data.pubkey_bob {
+            msg!("Only bob can rent");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        validate_escrow_key(escrow_info, &escrow_data, pass, program_id)?;
+        validate_vault_key(
+            vault_x_info,
+            &escrow_data,
+            b"vault_x",
+            escrow_data.vault_x_bump,
+            pass,
+            program_id,
+        )?;
+        validate_vault_key(
+            vault_y_info,
+            &escrow_data,
+            b"vault_y",
+            escrow_data.vault_y_bump,
+            pass,
+            program_id,
+        )?;
+        validate_token_account(
+            bob_y_token_info,
+            token_program_info,
+            &escrow_data.pubkey_bob,
+            &escrow_data.pubkey_mint_y,
+        )?;
+        validate_token_account(
+            alice_y_token_info,
+            token_program_info,
+            &escrow_data.pubkey_alice,
+            &escrow_data.pubkey_mint_y,
+        )?;
+        validate_token_account(
+            bob_x_token_info,
+            token_program_info,
+            &escrow_data.pubkey_bob,
+            &escrow_data.pubkey_mint_x,
+        )?;
+
+        msg!("Sending collateral");
+        transfer_tokens(
+            token_program_info,
+            bob_y_token_info,
+            vault_y_info,
+            bob_info,
+            escrow_data.size_y,
+        )?;
+        if escrow_data.rental.fee > 0 {
+            msg!("Sending rental fee");
+            transfer_tokens(
+                token_program_info,
+                bob_y_token_info,
+                alice_y_token_info,
+                bob_info,
+                escrow_data.rental.fee,
+            )?;
+        }
+        msg!("Sending rented token");
+        transfer_from_vault(
+            token_program_info,
+            vault_x_info,
+            bob_x_token_info,
+            escrow_info,
+            escrow_data.size_x,
+            &escrow_seeds(&escrow_data, &pass),
+        )?;
+
+        let clock = Clock::from_account_info(clock_info)?;
+        escrow_data.rental_due_ts = clock
+            .unix_timestamp
+            .checked_add(escrow_data.rental.duration)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        escrow_data.state = EscrowState::Rented;
+        escrow_data.serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_return_rental(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let bob_x_token_info = next_account_info(account_info_iter)?;
+        let alice_x_token_info = next_account_info(account_info_iter)?;
+        let vault_y_info = next_account_info(account_info_iter)?;
+        let bob_y_token_info = next_account_info(account_info_iter)?;
+        let bob_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut escrow_data = EscrowData::try_from_slice(&escrow_info.data.borrow())?;
+        if !escrow_data.is_rental() || escrow_data.state != EscrowState::Rented {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !bob_info.is_signer || *bob_info.key != escrow_data.pubkey_bob {
+            msg!("Only bob can return the rental");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let clock = Clock::from_account_info(clock_info)?;
+        if clock.unix_timestamp > escrow_data.rental_due_ts {
+            msg!("Rental period is over");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        validate_escrow_key(escrow_info, &escrow_data, pass, program_id)?;
+        validate_vault_key(
+            vault_y_info,
+            &escrow_data,
+            b"vault_y",
+            escrow_data.vault_y_bump,
+            pass,
+            program_id,
+        )?;
+        validate_token_account(
+            alice_x_token_info,
+            token_program_info,
+            &escrow_data.pubkey_alice,
+            &escrow_data.pubkey_mint_x,
+        )?;
+        validate_token_account(
+            bob_y_token_info,
+            token_program_info,
+            &escrow_data.pubkey_bob,
+            &escrow_data.pubkey_mint_y,
+        )?;
+
+        msg!("Returning rented token");
+        transfer_tokens(
+            token_program_info,
+            bob_x_token_info,
+            alice_x_token_info,
+            bob_info,
+            escrow_data.size_x,
+        )?;
+        msg!("Returning collateral");
+        transfer_from_vault(
+            token_program_info,
+            vault_y_info,
+            bob_y_token_info,
+            escrow_info,
+            escrow_data.size_y,
+            &escrow_seeds(&escrow_data, &pass),
+        )?;
+
+        escrow_data.state = EscrowState::Completed;
+        escrow_data.serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_claim_collateral(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let vault_y_info = next_account_info(account_info_iter)?;
+        let alice_y_token_info = next_account_info(account_info_iter)?;
+        let alice_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut escrow_data = EscrowData::try_from_slice(&escrow_info.data.borrow())?;
+        if !escrow_data.is_rental() || escrow_data.state != EscrowState::Rented {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !alice_info.is_signer || *alice_info.key != escrow_data.pubkey_alice {
+            msg!("Only alice can claim the collateral");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let clock = Clock::from_account_info(clock_info)?;
+        if clock.unix_timestamp <= escrow_data.rental_due_ts {
+            msg!("Rental period is not over");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        validate_escrow_key(escrow_info, &escrow_data, pass, program_id)?;
+        validate_vault_key(
+            vault_y_info,
+            &escrow_data,
+            b"vault_y",
+            escrow_data.vault_y_bump,
+            pass,
+            program_id,
+        )?;
+        validate_token_account(
+            alice_y_token_info,
+            token_program_info,
+            &escrow_data.pubkey_alice,
+            &escrow_data.pubkey_mint_y,
+        )?;
+
+        msg!("Sending collateral");
+        transfer_from_vault(
+            token_program_info,
+            vault_y_info,
+            alice_y_token_info,
+            escrow_info,
+            escrow_data.size_y,
+            &escrow_seeds(&escrow_data, &pass),
+        )?;
+
+        escrow_data.state = EscrowState::Completed;
+        escrow_data.serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_submit_channel_state(
+        accounts: &[AccountInfo],
+        balance: ChannelBalance,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let submitter_info = next_account_info(account_info_iter)?;
+        let instructions_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut escrow_data = EscrowData::try_from_slice(&escrow_info.data.borrow())?;
+        if !escrow_data.is_channel() {
+            msg!("Not a payment channel");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !submitter_info.is_signer
+            || (*submitter_info.key != escrow_data.pubkey_alice
+                && *submitter_info.key != escrow_data.pubkey_bob)
+        {
+            msg!("Only alice or bob can submit a channel state");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        validate_escrow_key(escrow_info, &escrow_data, pass, program_id)?;
+
+        let clock = Clock::from_account_info(clock_info)?;
+        match escrow_data.state {
+            EscrowState::Committed => {
+                escrow_data.channel_closes_at = clock
+                    .unix_timestamp
+                    .checked_add(escrow_data.channel.challenge_period)
+                    .ok_or(ProgramError::InvalidAccountData)?;
+                escrow_data.state = EscrowState::Settling;
+            }
+            EscrowState::Settling if clock.unix_timestamp < escrow_data.channel_closes_at => {}
+            _ => {
+                msg!("Invalid State");
+                return Err(ProgramError::InvalidAccountData);
+            }
+        }
+        if balance.nonce <= escrow_data.channel_balance.nonce {
+            msg!("Channel state is not newer than the submitted one");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        if balance.alice_x > escrow_data.size_x || balance.alice_y > escrow_data.size_y {
+            msg!("Channel balance exceeds the deposits");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let message = balance.message(escrow_info.key);
+        ed25519::verify_signatures(
+            instructions_info,
+            &[
+                (&escrow_data.pubkey_alice, &message),
+                (&escrow_data.pubkey_bob, &message),
+            ],
+        )?;
+
+        escrow_data.channel_balance = balance;
+        escrow_data.serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_close_channel(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let closer_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut escrow_data = EscrowData::try_from_slice(&escrow_info.data.borrow())?;
+        if !escrow_data.is_channel() || escrow_data.state != EscrowState::Committed {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !closer_info.is_signer
+            || (*closer_info.key != escrow_data.pubkey_alice
+                && *closer_info.key != escrow_data.pubkey_bob)
+        {
+            msg!("Only alice or bob can close a channel");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        validate_escrow_key(escrow_info, &escrow_data, pass, program_id)?;
+
+        let clock = Clock::from_account_info(clock_info)?;
+        escrow_data.channel_closes_at = clock
+            .unix_timestamp
+            .checked_add(escrow_data.channel.challenge_period)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        escrow_data.channel_balance = ChannelBalance {
+            nonce: 0,
+            alice_x: escrow_data.size_x,
+            alice_y: 0,
+        };
+        escrow_data.state = EscrowState::Settling;
+        escrow_data.serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_settle_channel(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let vault_x_info = next_account_info(account_info_iter)?;
+        let vault_y_info = next_account_info(account_info_iter)?;
+        let alice_x_token_info = next_account_info(account_info_iter)?;
+        let alice_y_token_info = next_account_info(account_info_iter)?;
+        let bob_x_token_info = next_account_info(account_info_iter)?;
+        let bob_y_token_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut escrow_data = EscrowData::try_from_slice(&escrow_info.data.borrow())?;
+        if !escrow_data.is_channel() || escrow_data.state != EscrowState::Settling {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let clock = Clock::from_account_info(clock_info)?;
+        if clock.unix_timestamp < escrow_data.channel_closes_at {
+            msg!("Challenge period is not over");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        validate_escrow_key(escrow_info, &escrow_data, pass, program_id)?;
+        validate_vault_key(
+            vault_x_info,
+            &escrow_data,
+            b"vault_x",
+            escrow_data.vault_x_bump,
+            pass,
+            program_id,
+        )?;
+        validate_vault_key(
+            vault_y_info,
+            &escrow_data,
+            b"vault_y",
+            escrow_data.vault_y_bump,
+            pass,
+            program_id,
+        )?;
+        validate_token_account(
+            alice_x_token_info,
+            token_program_info,
+            &escrow_data.pubkey_alice,
+            &escrow_data.pubkey_mint_x,
+        )?;
+        validate_token_account(
+            alice_y_token_info,
+            token_program_info,
+            &escrow_data.pubkey_alice,
+            &escrow_data.pubkey_mint_y,
+        )?;
+        validate_token_account(
+            bob_x_token_info,
+            token_program_info,
+            &escrow_data.pubkey_bob,
+            &escrow_data.pubkey_mint_x,
+        )?;
+        validate_token_account(
+            bob_y_token_info,
+            token_program_info,
+            &escrow_data.pubkey_bob,
+            &escrow_data.pubkey_mint_y,
+        )?;
+
+        let balance = escrow_data.channel_balance;
+        let seeds = escrow_seeds(&escrow_data, &pass);
+        let payouts = [
+            (vault_x_info, alice_x_token_info, balance.alice_x),
+            (
+                vault_x_info,
+                bob_x_token_info,
+                escrow_data.size_x - balance.alice_x,
+            ),
+            (vault_y_info, alice_y_token_info, balance.alice_y),
+            (
+                vault_y_info,
+                bob_y_token_info,
+                escrow_data.size_y - balance.alice_y,
+            ),
+        ];
+        msg!("Sending transfers");
+        for (vault_info, destination_info, amount) in payouts.iter() {
+            if *amount > 0 {
+                transfer_from_vault(
+                    token_program_info,
+                    vault_info,
+                    destination_info,
+                    escrow_info,
+                    *amount,
+                    &seeds,
+                )?;
+            }
+        }
+
+        escrow_data.state = EscrowState::Completed;
+        escrow_data.serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_take_offer(
+        accounts: &[AccountInfo],
+        offer: Offer,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let nonce_info = next_account_info(account_info_iter)?;
+        let maker_x_token_info = next_account_info(account_info_iter)?;
+        let maker_y_token_info = next_account_info(account_info_iter)?;
+        let taker_x_token_info = next_account_info(account_info_iter)?;
+        let taker_y_token_info = next_account_info(account_info_iter)?;
+        let delegate_info = next_account_info(account_info_iter)?;
+        let taker_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let instructions_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if !taker_info.is_signer {
+            msg!("Taker must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let clock = Clock::from_account_info(clock_info)?;
+        if clock.unix_timestamp >= offer.expiry {
+            msg!("Offer has expired");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        ed25519::verify_signatures(
+            instructions_info,
+            &[(&offer.pubkey_maker, &offer.message())],
+        )?;
+        validate_token_account(
+            maker_x_token_info,
+            token_program_info,
+            &offer.pubkey_maker,
+            &offer.pubkey_mint_x,
+        )?;
+        validate_token_account(
+            maker_y_token_info,
+            token_program_info,
+            &offer.pubkey_maker,
+            &offer.pubkey_mint_y,
+        )?;
+        let (delegate_key, delegate_bump) =
+            Pubkey::find_program_address(&[b"delegate", offer.pubkey_maker.as_ref()], program_id);
+        if delegate_key != *delegate_info.key {
+            msg!("Delegate key mismatch");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if nonce_info.data_len() != 0 {
+            msg!("Offer was already taken");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        msg!("Consuming offer nonce");
+        // a single byte of data marks the nonce as used
+        create_program_account(
+            program_id,
+            nonce_info,
+            taker_info,
+            rent_info,
+            system_program_info,
+            1,
+            &[
+                b"nonce",
+                offer.pubkey_maker.as_ref(),
+                &offer.nonce.to_le_bytes(),
+            ],
+        )?;
+
+        msg!("Sending transfers");
+        transfer_from_vault(
+            token_program_info,
+            maker_x_token_info,
+            taker_x_token_info,
+            delegate_info,
+            offer.amount_x,
+            &[b"delegate", offer.pubkey_maker.as_ref(), &[delegate_bump]],
+        )?;
+        transfer_tokens(
+            token_program_info,
+            taker_y_token_info,
+            maker_y_token_info,
+            taker_info,
+            offer.amount_y,
+        )?;
+        Ok(())
+    }
+
+    pub fn process_init_delegated_escrow(
+        accounts: &[AccountInfo],
+        size_x: u64,
+        size_y: u64,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let mint_x_info = next_account_info(account_info_iter)?;
+        let mint_y_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let alice_info = next_account_info(account_info_iter)?;
+        let bob_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let initiator = if alice_info.is_signer {
+            *alice_info.key
+        } else if bob_info.is_signer {
+            *bob_info.key
+        } else {
+            msg!("Alice or bob must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        };
+        if escrow_info.data_len() != 0 {
+            msg!("Trying reinitialize an existing escrow");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        msg!("Creating escrow metadata");
+        let escrow_bump = create_program_account(
+            program_id,
+            escrow_info,
+            payer_info,
+            rent_info,
+            system_program_info,
+            EscrowData::LEN,
+            &[
+                b"escrow",
+                alice_info.key.as_ref(),
+                bob_info.key.as_ref(),
+                mint_x_info.key.as_ref(),
+                mint_y_info.key.as_ref(),
+                pass.as_ref(),
+            ],
+        )?;
+
+        EscrowData {
+            size_x,
+            size_y,
+            pubkey_alice: *alice_info.key,
+            pubkey_bob: *bob_info.key,
+            pubkey_mint_x: *mint_x_info.key,
+            pubkey_mint_y: *mint_y_info.key,
+            state: EscrowState::Proposed,
+            escrow_bump,
+            delegated: true,
+            pubkey_initiator: initiator,
+            ..EscrowData::default()
+        }
+        .serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_settle_delegated(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let alice_x_token_info = next_account_info(account_info_iter)?;
+        let alice_y_token_info = next_account_info(account_info_iter)?;
+        let bob_x_token_info = next_account_info(account_info_iter)?;
+        let bob_y_token_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let mut escrow_data = EscrowData::try_from_slice(&escrow_info.data.borrow())?;
+        if !escrow_data.delegated || escrow_data.state != EscrowState::Initialized {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        validate_escrow_key(escrow_info, &escrow_data, pass, program_id)?;
+        validate_token_account(
+            alice_y_token_info,
+            token_program_info,
+            &escrow_data.pubkey_alice,
+            &escrow_data.pubkey_mint_y,
+        )?;
+        validate_token_account(
+            bob_x_token_info,
+            token_program_info,
+            &escrow_data.pubkey_bob,
+            &escrow_data.pubkey_mint_x,
+        )?;
+
+        msg!("Validating allowances");
+        validate_allowance(
+            alice_x_token_info,
+            token_program_info,
+            &escrow_data.pubkey_alice,
+            &escrow_data.pubkey_mint_x,
+            escrow_info.key,
+            escrow_data.size_x,
+        )?;
+        validate_allowance(
+            bob_y_token_info,
+            token_program_info,
+            &escrow_data.pubkey_bob,
+            &escrow_data.pubkey_mint_y,
+            escrow_info.key,
+            escrow_data.size_y,
+        )?;
+
+        msg!("Sending transfers");
+        let seeds = escrow_seeds(&escrow_data, &pass);
+        transfer_from_vault(
+            token_program_info,
+            alice_x_token_info,
+            bob_x_token_info,
+            escrow_info,
+            escrow_data.size_x,
+            &seeds,
+        )?;
+        transfer_from_vault(
+            token_program_info,
+            bob_y_token_info,
+            alice_y_token_info,
+            escrow_info,
+            escrow_data.size_y,
+            &seeds,
+        )?;
+
+        escrow_data.state = EscrowState::Completed;
+        escrow_data.serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_mutual_refund(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let vault_x_info = next_account_info(account_info_iter)?;
+        let vault_y_info = next_account_info(account_info_iter)?;
+        let alice_token_info = next_account_info(account_info_iter)?;
+        let bob_token_info = next_account_info(account_info_iter)?;
+        let alice_info = next_account_info(account_info_iter)?;
+        let bob_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let mut escrow_data = EscrowData::try_from_slice(&escrow_info.data.borrow())?;
+        if escrow_data.delegated {
+            msg!("Delegated escrow holds no deposits");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !alice_info.is_signer
+            || !bob_info.is_signer
+            || *alice_info.key != escrow_data.pubkey_alice
+            || *bob_info.key != escrow_data.pubkey_bob
+        {
+            msg!("Both parties must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        match escrow_data.state {
+            EscrowState::Initialized
+            | EscrowState::DepositAlice
+            | EscrowState::DepositBob
+            | EscrowState::Committed => {}
+            _ => {
+                msg!("Invalid State");
+                return Err(ProgramError::InvalidAccountData);
+            }
+        }
+        let refund_x = escrow_data.deposited_x - escrow_data.claimed;
+        let refund_y = escrow_data.deposited_y;
+        validate_escrow_key(escrow_info, &escrow_data, pass, program_id)?;
+        let seeds = escrow_seeds(&escrow_data, &pass);
+
+        msg!("Sending transfers");
+        if refund_x > 0 {
+            validate_vault_key(
+                vault_x_info,
+                &escrow_data,
+                b"vault_x",
+                escrow_data.vault_x_bump,
+                pass,
+                program_id,
+            )?;
+            validate_token_account(
+                alice_token_info,
+                token_program_info,
+                &escrow_data.pubkey_alice,
+                &escrow_data.pubkey_mint_x,
+            )?;
+            transfer_from_vault(
+                token_program_info,
+                vault_x_info,
+                alice_token_info,
+                escrow_info,
+                refund_x,
+                &seeds,
+            )?;
+        }
+        if refund_y > 0 {
+            validate_vault_key(
+                vault_y_info,
+                &escrow_data,
+                b"vault_y",
+                escrow_data.vault_y_bump,
+                pass,
+                program_id,
+            )?;
+            validate_token_account(
+                bob_token_info,
+                token_program_info,
+                &escrow_data.pubkey_bob,
+                &escrow_data.pubkey_mint_y,
+            )?;
+            transfer_from_vault(
+                token_program_info,
+                vault_y_info,
+                bob_token_info,
+                escrow_info,
+                refund_y,
+                &seeds,
+            )?;
+        }
+
+        for party_info in [alice_info, bob_info].iter() {
+            let bond = escrow_data.take_bond(party_info.key);
+            if bond > 0 {
+                msg!("Returning bond");
+                release_bond(escrow_info, party_info, bond)?;
+            }
+        }
+
+        escrow_data.state = EscrowState::Initialized;
+        escrow_data.claimed = 0;
+        escrow_data.deposited_x = 0;
+        escrow_data.deposited_y = 0;
+        escrow_data.serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_propose(
+        accounts: &[AccountInfo],
+        amount_x: u64,
+        amount_y: u64,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let proposer_info = next_account_info(account_info_iter)?;
+
+        let mut escrow_data = EscrowData::try_from_slice(&escrow_info.data.borrow())?;
+        if escrow_data.state != EscrowState::Initialized
+            || !escrow_data.is_negotiable()
+            || !escrow_data.is_unfunded()
+        {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !proposer_info.is_signer
+            || (*proposer_info.key != escrow_data.pubkey_alice
+                && *proposer_info.key != escrow_data.pubkey_bob)
+        {
+            msg!("Only alice or bob can propose");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        validate_escrow_key(escrow_info, &escrow_data, pass, program_id)?;
+
+        escrow_data.proposal_x = amount_x;
+        escrow_data.proposal_y = amount_y;
+        escrow_data.pubkey_proposer = *proposer_info.key;
+        escrow_data.serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_accept(
+        accounts: &[AccountInfo],
+        amount_x: u64,
+        amount_y: u64,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let acceptor_info = next_account_info(account_info_iter)?;
+
+        let mut escrow_data = EscrowData::try_from_slice(&escrow_info.data.borrow())?;
+        let offered_by = match escrow_data.state {
+            EscrowState::Proposed => escrow_data.pubkey_initiator,
+            EscrowState::Initialized
+                if escrow_data.pubkey_proposer != Pubkey::default()
+                    && escrow_data.is_unfunded() =>
+            {
+                escrow_data.pubkey_proposer
+            }
+            _ => {
+                msg!("Nothing to accept");
+                return Err(ProgramError::InvalidAccountData);
+            }
+        };
+        let counterparty = if offered_by == escrow_data.pubkey_alice {
+            escrow_data.pubkey_bob
+        } else {
+            escrow_data.pubkey_alice
+        };
+        if !acceptor_info.is_signer || *acceptor_info.key != counterparty {
+            msg!("Only the counterparty can accept");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        validate_escrow_key(escrow_info, &escrow_data, pass, program_id)?;
+
+        let (offered_x, offered_y) = if escrow_data.state == EscrowState::Proposed {
+            (escrow_data.size_x, escrow_data.size_y)
+        } else {
+            (escrow_data.proposal_x, escrow_data.proposal_y)
+        };
+        if amount_x != offered_x || amount_y != offered_y {
+            msg!("Amounts differ from the offer");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        if escrow_data.state == EscrowState::Proposed {
+            if escrow_data.is_bonded() {
+                let system_program_info = next_account_info(account_info_iter)?;
+                let clock_info = next_account_info(account_info_iter)?;
+                let clock = Clock::from_account_info(clock_info)?;
+                if clock.unix_timestamp >= escrow_data.bond.deposit_deadline {
+                    msg!("Deposit deadline has passed");
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                msg!("Posting bond");
+                post_bond(
+                    acceptor_info,
+                    escrow_info,
+                    system_program_info,
+                    escrow_data.bond.amount,
+                )?;
+                if counterparty == escrow_data.pubkey_alice {
+                    escrow_data.bond_posted_alice = true;
+                } else {
+                    escrow_data.bond_posted_bob = true;
+                }
+            }
+            escrow_data.state = EscrowState::Initialized;
+        } else {
+            escrow_data.size_x = escrow_data.proposal_x;
+            escrow_data.size_y = escrow_data.proposal_y;
+            escrow_data.approvals = 0;
+            escrow_data.proposal_x = 0;
+            escrow_data.proposal_y = 0;
+            escrow_data.pubkey_proposer = Pubkey::default();
+        }
+        escrow_data.serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_set_payouts(
+        accounts: &[AccountInfo],
+        recipients: Vec<Payout>,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if recipients.len() > MAX_PAYOUTS
+            || recipients.iter().any(|payout| payout.bps == 0)
+            || (!recipients.is_empty()
+                && recipients
+                    .iter()
+                    .map(|payout| payout.bps as u32)
+                    .sum::<u32>()
+                    != 10_000)
+        {
+            msg!("Invalid payouts");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let party_info = next_account_info(account_info_iter)?;
+
+        let mut escrow_data = EscrowData::try_from_slice(&escrow_info.data.borrow())?;
+        if !escrow_data.is_negotiable() || escrow_data.delegated {
+            msg!("Payouts only apply to vault swaps");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !party_info.is_signer {
+            msg!("Alice or bob must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        validate_escrow_key(escrow_info, &escrow_data, pass, program_id)?;
+
+        let mut payouts = [Payout::default(); MAX_PAYOUTS];
+        payouts[..recipients.len()].copy_from_slice(&recipients);
+        match escrow_data.state {
+            EscrowState::Proposed
+            | EscrowState::Initialized
+            | EscrowState::DepositAlice
+            | EscrowState::DepositBob
+            | EscrowState::Committed
+            | EscrowState::WithdrawBob
+                if *party_info.key == escrow_data.pubkey_alice =>
+            {
+                escrow_data.payouts_alice = payouts;
+                escrow_data.payout_count_alice = recipients.len() as u8;
+            }
+            EscrowState::Proposed
+            | EscrowState::Initialized
+            | EscrowState::DepositAlice
+            | EscrowState::DepositBob
+            | EscrowState::Committed
+            | EscrowState::WithdrawAlice
+                if *party_info.key == escrow_data.pubkey_bob =>
+            {
+                escrow_data.payouts_bob = payouts;
+                escrow_data.payout_count_bob = recipients.len() as u8;
+            }
+            _ => {
+                msg!("Invalid State");
+                return Err(ProgramError::InvalidAccountData);
+            }
+        }
+        escrow_data.serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_net_settle(
+        accounts: &[AccountInfo],
+        passes: Vec<[u8; 32]>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if passes.is_empty() {
+            msg!("Nothing to settle");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let account_info_iter = &mut accounts.iter();
+        let first_party_info = next_account_info(account_info_iter)?;
+        let second_party_info = next_account_info(account_info_iter)?;
+        let first_party_tokens = [
+            next_account_info(account_info_iter)?,
+            next_account_info(account_info_iter)?,
+        ];
+        let second_party_tokens = [
+            next_account_info(account_info_iter)?,
+            next_account_info(account_info_iter)?,
+        ];
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if !first_party_info.is_signer || !second_party_info.is_signer {
+            msg!("Both parties must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut escrows: Vec<(&AccountInfo, EscrowData, [u8; 32])> =
+            Vec::with_capacity(passes.len());
+        // committed legs sitting in a vault: mint, escrow, vault, amount and whether the first party is owed it
+        let mut vaults: Vec<(usize, usize, &AccountInfo, u64, bool)> = Vec::new();
+        let mut mints = None;
+        let mut net = [0i128; 2]; // owed by the first party to the second out of their own accounts, per mint
+        let mut held = [[0i128; 2]; 2]; // vault balances owed to the first and to the second party, per mint
+        for pass in passes {
+            let escrow_info = next_account_info(account_info_iter)?;
+            if escrows
+                .iter()
+                .any(|(info, _, _)| info.key == escrow_info.key)
+            {
+                msg!("Duplicate escrow");
+                return Err(ProgramError::InvalidAccountData);
+            }
+            let escrow_data = EscrowData::try_from_slice(&escrow_info.data.borrow())?;
+            validate_escrow_key(escrow_info, &escrow_data, pass, program_id)?;
+            let committed = match escrow_data.state {
+                EscrowState::Initialized if escrow_data.is_unfunded() => false,
+                EscrowState::Committed => true,
+                _ => {
+                    msg!("Only unfunded or committed escrows can be netted");
+                    return Err(ProgramError::InvalidAccountData);
+                }
+            };
+            if !escrow_data.is_negotiable()
+                || escrow_data.delegated
+                || escrow_data.is_forward()
+                || escrow_data.is_chained()
+                || escrow_data.is_oracle_gated()
+                || !escrow_data.is_approved()
+                || escrow_data.payout_count_alice != 0
+                || escrow_data.payout_count_bob != 0
+                || escrow_data.is_bonded()
+            {
+                msg!("Escrow cannot be netted");
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            let mints: &[Pubkey; 2] =
+                mints.get_or_insert([escrow_data.pubkey_mint_x, escrow_data.pubkey_mint_y]);
+            let (leg_x, leg_y) = match (
+                mints
+                    .iter()
+                    .position(|mint| *mint == escrow_data.pubkey_mint_x),
+                mints
+                    .iter()
+                    .position(|mint| *mint == escrow_data.pubkey_mint_y),
+            ) {
+                (Some(leg_x), Some(leg_y)) => (leg_x, leg_y),
+                _ => {
+                    msg!("Invalid Mint");
+                    return Err(ProgramError::InvalidAccountData);
+                }
+            };
+            // alice pays `size_x` of mint x to bob, who pays `size_y` of mint y back
+            let alice_is_first = if escrow_data.pubkey_alice == *first_party_info.key
+                && escrow_data.pubkey_bob == *second_party_info.key
+            {
+                true
+            } else if escrow_data.pubkey_alice == *second_party_info.key
+                && escrow_data.pubkey_bob == *first_party_info.key
+            {
+                false
+            } else {
+                msg!("Invalid Owner");
+                return Err(ProgramError::InvalidAccountData);
+            };
+            if committed {
+                let vault_x_info = next_account_info(account_info_iter)?;
+                let vault_y_info = next_account_info(account_info_iter)?;
+                validate_vault_key(
+                    vault_x_info,
+                    &escrow_data,
+                    b"vault_x",
+                    escrow_data.vault_x_bump,
+                    pass,
+                    program_id,
+                )?;
+                validate_vault_key(
+                    vault_y_info,
+                    &escrow_data,
+                    b"vault_y",
+                    escrow_data.vault_y_bump,
+                    pass,
+                    program_id,
+                )?;
+                // vault x holds alice's leg for bob, vault y bob's leg for alice
+                let legs = [
+                    (leg_x, vault_x_info, escrow_data.size_x, !alice_is_first),
+                    (leg_y, vault_y_info, escrow_data.size_y, alice_is_first),
+                ];
+                for (leg, vault_info, amount, to_first) in legs.iter().copied() {
+                    held[leg][if to_first { 0 } else { 1 }] += amount as i128;
+                    vaults.push((leg, escrows.len(), vault_info, amount, to_first));
+                }
+            } else {
+                let direction = if alice_is_first { 1 } else { -1 };
+                net[leg_x] += direction * escrow_data.size_x as i128;
+                net[leg_y] -= direction * escrow_data.size_y as i128;
+            }
+            escrows.push((escrow_info, escrow_data, pass));
+        }
+
+        msg!("Sending transfers");
+        let mints = mints.unwrap_or_default();
+        for (i, mint) in mints.iter().enumerate() {
+            validate_token_account(
+                first_party_tokens[i],
+                token_program_info,
+                first_party_info.key,
+                mint,
+            )?;
+            validate_token_account(
+                second_party_tokens[i],
+                token_program_info,
+                second_party_info.key,
+                mint,
+            )?;
+            // what each party ends up receiving, the vaults pay out first and only a shortfall
+            // moves between the parties' own accounts
+            let to_first = held[i][0] - net[i];
+            let to_second = held[i][1] + net[i];
+            if to_first < 0 {
+                let amount = u64::try_from(-to_first).map_err(|_| ProgramError::InvalidArgument)?;
+                transfer_tokens(
+                    token_program_info,
+                    first_party_tokens[i],
+                    second_party_tokens[i],
+                    first_party_info,
+                    amount,
+                )?;
+            } else if to_second < 0 {
+                let amount =
+                    u64::try_from(-to_second).map_err(|_| ProgramError::InvalidArgument)?;
+                transfer_tokens(
+                    token_program_info,
+                    second_party_tokens[i],
+                    first_party_tokens[i],
+                    second_party_info,
+                    amount,
+                )?;
+            }
+
+            let mut first_share = to_first.max(0).min(held[i][0] + held[i][1]);
+            for (_, k, vault_info, amount, _) in vaults.iter().filter(|vault| vault.0 == i) {
+                let (escrow_info, escrow_data, pass) = &escrows[*k];
+                let seeds = escrow_seeds(escrow_data, pass);
+                let take = first_share.min(*amount as i128);
+                first_share -= take;
+                let shares = [
+                    (first_party_tokens[i], take as u64),
+                    (second_party_tokens[i], *amount - take as u64),
+                ];
+                for (destination_info, share) in shares.iter() {
+                    if *share > 0 {
+                        transfer_from_vault(
+                            token_program_info,
+                            vault_info,
+                            destination_info,
+                            escrow_info,
+                            *share,
+                            &seeds,
+                        )?;
+                    }
+                }
+            }
+        }
+
+        for (escrow_info, mut escrow_data, _) in escrows {
+            escrow_data.state = EscrowState::Completed;
+            escrow_data.pubkey_proposer = Pubkey::default();
+            escrow_data.serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        }
+        Ok(())
+    }
+
+    pub fn process_claim_bond(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let claimant_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut escrow_data = EscrowData::try_from_slice(&escrow_info.data.borrow())?;
+        if !escrow_data.is_bonded() {
+            msg!("Escrow has no bonds");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !claimant_info.is_signer {
+            msg!("Claimant must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        validate_escrow_key(escrow_info, &escrow_data, pass, program_id)?;
+        let (alice_funded, bob_funded) = match escrow_data.state {
+            EscrowState::Proposed | EscrowState::Initialized => (false, false),
+            EscrowState::DepositAlice => (true, false),
+            EscrowState::DepositBob => (false, true),
+            // nothing can be slashed once both legs are in, even if the release still waits on approvals
+            EscrowState::Committed => (true, true),
+            _ => {
+                msg!("Invalid State");
+                return Err(ProgramError::InvalidAccountData);
+            }
+        };
+        let clock = Clock::from_account_info(clock_info)?;
+        if !(alice_funded && bob_funded) && clock.unix_timestamp < escrow_data.bond.deposit_deadline
+        {
+            msg!("Deposit deadline has not passed");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (claimant_funded, counterparty_funded, counterparty) =
+            if *claimant_info.key == escrow_data.pubkey_alice {
+                (alice_funded, bob_funded, escrow_data.pubkey_bob)
+            } else if *claimant_info.key == escrow_data.pubkey_bob {
+                (bob_funded, alice_funded, escrow_data.pubkey_alice)
+            } else {
+                msg!("Invalid Owner");
+                return Err(ProgramError::InvalidAccountData);
+            };
+        // a party that failed to fund its leg cannot take its bond back before it is slashed
+        let mut amount = 0;
+        if claimant_funded || !counterparty_funded {
+            amount += escrow_data.take_bond(claimant_info.key);
+        }
+        if claimant_funded && !counterparty_funded {
+            amount += escrow_data.take_bond(&counterparty);
+        }
+        if amount == 0 {
+            msg!("No bond to claim");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        msg!("Releasing bonds");
+        release_bond(escrow_info, claimant_info, amount)?;
+        escrow_data.serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_init_milestone_escrow(
+        accounts: &[AccountInfo],
+        amounts: Vec<u64>,
+        refund_after: i64,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if amounts.is_empty() || amounts.len() > MAX_MILESTONES || amounts.contains(&0) {
+            msg!("Invalid milestones");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let total = amounts
+            .iter()
+            .try_fold(0u64, |total, amount| total.checked_add(*amount))
+            .ok_or(ProgramError::InvalidInstructionData)?;
+
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let payee_info = next_account_info(account_info_iter)?;
+        let arbiter_info = next_account_info(account_info_iter)?;
+        let payer_token_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if escrow_info.data_len() != 0 {
+            msg!("Trying reinitialize an existing escrow");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        msg!("Creating milestone escrow metadata");
+        let escrow_bump = create_program_account(
+            program_id,
+            escrow_info,
+            payer_info,
+            rent_info,
+            system_program_info,
+            MilestoneEscrowData::LEN,
+            &[
+                b"milestone",
+                payer_info.key.as_ref(),
+                payee_info.key.as_ref(),
+                mint_info.key.as_ref(),
+                pass.as_ref(),
+            ],
+        )?;
+        msg!("Creating vault");
+        let vault_bump = create_vault(
+            program_id,
+            vault_info,
+            mint_info,
+            escrow_info,
+            payer_info,
+            token_program_info,
+            rent_info,
+            system_program_info,
+            &[b"vault", escrow_info.key.as_ref()],
+        )?;
+
+        msg!("Sending transfer");
+        transfer_tokens(
+            token_program_info,
+            payer_token_info,
+            vault_info,
+            payer_info,
+            total,
+        )?;
+
+        let mut milestone_amounts = [0u64; MAX_MILESTONES];
+        milestone_amounts[..amounts.len()].copy_from_slice(&amounts);
+        MilestoneEscrowData {
+            is_initialized: true,
+            pubkey_payer: *payer_info.key,
+            pubkey_payee: *payee_info.key,
+            pubkey_arbiter: *arbiter_info.key,
+            pubkey_mint: *mint_info.key,
+            amounts: milestone_amounts,
+            milestone_count: amounts.len() as u8,
+            settled: 0,
+            refund_after,
+            escrow_bump,
+            vault_bump,
+        }
+        .serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_release_milestone(
+        accounts: &[AccountInfo],
+        index: u8,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let payee_token_info = next_account_info(account_info_iter)?;
+        let signer_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let mut escrow_data = MilestoneEscrowData::try_from_slice(&escrow_info.data.borrow())?;
+        if !escrow_data.is_initialized {
+            msg!("Invalid State");
+            return Err(ProgramError::UninitializedAccount);
+        }
+        if !signer_info.is_signer
+            || (*signer_info.key != escrow_data.pubkey_payer
+                && *signer_info.key != escrow_data.pubkey_arbiter)
+        {
+            msg!("Only the payer or the arbiter can release a milestone");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if index as usize >= escrow_data.milestone_count as usize {
+            msg!("Invalid milestone index");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        if escrow_data.settled & (1 << index) != 0 {
+            msg!("Milestone already settled");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        validate_token_account(
+            payee_token_info,
+            token_program_info,
+            &escrow_data.pubkey_payee,
+            &escrow_data.pubkey_mint,
+        )?;
+
+        let escrow_seeds: &[&[u8]] = &[
+            b"milestone",
+            escrow_data.pubkey_payer.as_ref(),
+            escrow_data.pubkey_payee.as_ref(),
+            escrow_data.pubkey_mint.as_ref(),
+            pass.as_ref(),
+            &[escrow_data.escrow_bump],
+        ];
+        validate_escrow_and_vault(
+            escrow_info,
+            vault_info,
+            escrow_seeds,
+            escrow_data.vault_bump,
+            program_id,
+        )?;
+
+        msg!("Sending transfer");
+        transfer_from_vault(
+            token_program_info,
+            vault_info,
+            payee_token_info,
+            escrow_info,
+            escrow_data.amounts[index as usize],
+            escrow_seeds,
+        )?;
+
+        escrow_data.settled |= 1 << index;
+        escrow_data.serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_refund_milestones(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let payer_token_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut escrow_data = MilestoneEscrowData::try_from_slice(&escrow_info.data.borrow())?;
+        if !escrow_data.is_initialized {
+            msg!("Invalid State");
+            return Err(ProgramError::UninitializedAccount);
+        }
+        if !payer_info.is_signer || *payer_info.key != escrow_data.pubkey_payer {
+            msg!("Only the payer can claim a refund");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let clock = Clock::from_account_info(clock_info)?;
+        if clock.unix_timestamp < escrow_data.refund_after {
+            msg!("Refund deadline not reached");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let amount = escrow_data.unsettled_amount();
+        if amount == 0 {
+            msg!("Nothing to refund");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        validate_token_account(
+            payer_token_info,
+            token_program_info,
+            &escrow_data.pubkey_payer,
+            &escrow_data.pubkey_mint,
+        )?;
+
+        let escrow_seeds: &[&[u8]] = &[
+            b"milestone",
+            escrow_data.pubkey_payer.as_ref(),
+            escrow_data.pubkey_payee.as_ref(),
+            escrow_data.pubkey_mint.as_ref(),
+            pass.as_ref(),
+            &[escrow_data.escrow_bump],
+        ];
+        validate_escrow_and_vault(
+            escrow_info,
+            vault_info,
+            escrow_seeds,
+            escrow_data.vault_bump,
+            program_id,
+        )?;
+
+        msg!("Sending transfer");
+        transfer_from_vault(
+            token_program_info,
+            vault_info,
+            payer_token_info,
+            escrow_info,
+            amount,
+            escrow_seeds,
+        )?;
+
+        escrow_data.settled = u8::MAX;
+        escrow_data.serialize(&mut &mut escrow_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_init_stream(
+        accounts: &[AccountInfo],
+        rate_per_second: u64,
+        amount: u64,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if rate_per_second == 0 || amount == 0 {
+            msg!("Invalid stream terms");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let account_info_iter = &mut accounts.iter();
+        let stream_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let recipient_info = next_account_info(account_info_iter)?;
+        let payer_token_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        if stream_info.data_len() != 0 {
+            msg!("Trying reinitialize an existing stream");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        msg!("Creating stream metadata");
+        let escrow_bump = create_program_account(
+            program_id,
+            stream_info,
+            payer_info,
+            rent_info,
+            system_program_info,
+            StreamData::LEN,
+            &[
+                b"stream",
+                payer_info.key.as_ref(),
+                recipient_info.key.as_ref(),
+                mint_info.key.as_ref(),
+                pass.as_ref(),
+            ],
+        )?;
+        msg!("Creating vault");
+        let vault_bump = create_vault(
+            program_id,
+            vault_info,
+            mint_info,
+            stream_info,
+            payer_info,
+            token_program_info,
+            rent_info,
+            system_program_info,
+            &[b"vault", stream_info.key.as_ref()],
+        )?;
+
+        msg!("Sending transfer");
+        transfer_tokens(
+            token_program_info,
+            payer_token_info,
+            vault_info,
+            payer_info,
+            amount,
+        )?;
+
+        let clock = Clock::from_account_info(clock_info)?;
+        StreamData {
+            is_initialized: true,
+            is_cancelled: false,
+            pubkey_payer: *payer_info.key,
+            pubkey_recipient: *recipient_info.key,
+            pubkey_mint: *mint_info.key,
+            rate_per_second,
+            deposited: amount,
+            withdrawn: 0,
+            checkpoint_ts: clock.unix_timestamp,
+            checkpoint_streamed: 0,
+            escrow_bump,
+            vault_bump,
+        }
+        .serialize(&mut &mut stream_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_withdraw_stream(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stream_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let recipient_token_info = next_account_info(account_info_iter)?;
+        let recipient_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut stream_data = StreamData::try_from_slice(&stream_info.data.borrow())?;
+        if !stream_data.is_initialized {
+            msg!("Invalid State");
+            return Err(ProgramError::UninitializedAccount);
+        }
+        if !recipient_info.is_signer || *recipient_info.key != stream_data.pubkey_recipient {
+            msg!("Only the recipient can withdraw");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        validate_token_account(
+            recipient_token_info,
+            token_program_info,
+            &stream_data.pubkey_recipient,
+            &stream_data.pubkey_mint,
+        )?;
+        let stream_seeds: &[&[u8]] = &[
+            b"stream",
+            stream_data.pubkey_payer.as_ref(),
+            stream_data.pubkey_recipient.as_ref(),
+            stream_data.pubkey_mint.as_ref(),
+            pass.as_ref(),
+            &[stream_data.escrow_bump],
+        ];
+        validate_escrow_and_vault(
+            stream_info,
+            vault_info,
+            stream_seeds,
+            stream_data.vault_bump,
+            program_id,
+        )?;
+
+        let clock = Clock::from_account_info(clock_info)?;
+        let amount = stream_data
+            .streamed_amount(clock.unix_timestamp)
+            .saturating_sub(stream_data.withdrawn);
+        if amount == 0 {
+            msg!("Nothing streamed to withdraw");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        msg!("Sending transfer");
+        transfer_from_vault(
+            token_program_info,
+            vault_info,
+            recipient_token_info,
+            stream_info,
+            amount,
+            stream_seeds,
+        )?;
+
+        stream_data.withdrawn += amount;
+        stream_data.serialize(&mut &mut stream_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_top_up_stream(
+        accounts: &[AccountInfo],
+        amount: u64,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stream_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let payer_token_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut stream_data = StreamData::try_from_slice(&stream_info.data.borrow())?;
+        if !stream_data.is_initialized || stream_data.is_cancelled {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !payer_info.is_signer || *payer_info.key != stream_data.pubkey_payer {
+            msg!("Only the payer can top up");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let stream_seeds: &[&[u8]] = &[
+            b"stream",
+            stream_data.pubkey_payer.as_ref(),
+            stream_data.pubkey_recipient.as_ref(),
+            stream_data.pubkey_mint.as_ref(),
+            pass.as_ref(),
+            &[stream_data.escrow_bump],
+        ];
+        validate_escrow_and_vault(
+            stream_info,
+            vault_info,
+            stream_seeds,
+            stream_data.vault_bump,
+            program_id,
+        )?;
+
+        msg!("Sending transfer");
+        transfer_tokens(
+            token_program_info,
+            payer_token_info,
+            vault_info,
+            payer_info,
+            amount,
+        )?;
+
+        // Checkpoint first so a stream that already ran dry does not accrue for the time it was empty
+        let clock = Clock::from_account_info(clock_info)?;
+        stream_data.checkpoint_streamed = stream_data.streamed_amount(clock.unix_timestamp);
+        stream_data.checkpoint_ts = clock.unix_timestamp;
+        stream_data.deposited = stream_data
+            .deposited
+            .checked_add(amount)
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        stream_data.serialize(&mut &mut stream_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_cancel_stream(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stream_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let payer_token_info = next_account_info(account_info_iter)?;
+        let recipient_token_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut stream_data = StreamData::try_from_slice(&stream_info.data.borrow())?;
+        if !stream_data.is_initialized || stream_data.is_cancelled {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !payer_info.is_signer || *payer_info.key != stream_data.pubkey_payer {
+            msg!("Only the payer can cancel");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        validate_token_account(
+            payer_token_info,
+            token_program_info,
+            &stream_data.pubkey_payer,
+            &stream_data.pubkey_mint,
+        )?;
+        validate_token_account(
+            recipient_token_info,
+            token_program_info,
+            &stream_data.pubkey_recipient,
+            &stream_data.pubkey_mint,
+        )?;
+        let stream_seeds: &[&[u8]] = &[
+            b"stream",
+            stream_data.pubkey_payer.as_ref(),
+            stream_data.pubkey_recipient.as_ref(),
+            stream_data.pubkey_mint.as_ref(),
+            pass.as_ref(),
+            &[stream_data.escrow_bump],
+        ];
+        validate_escrow_and_vault(
+            stream_info,
+            vault_info,
+            stream_seeds,
+            stream_data.vault_bump,
+            program_id,
+        )?;
+
+        let clock = Clock::from_account_info(clock_info)?;
+        let streamed = stream_data.streamed_amount(clock.unix_timestamp);
+        let owed = streamed.saturating_sub(stream_data.withdrawn);
+        let remainder = stream_data.deposited - streamed;
+
+        msg!("Sending transfers");
+        if owed > 0 {
+            transfer_from_vault(
+                token_program_info,
+                vault_info,
+                recipient_token_info,
+                stream_info,
+                owed,
+                stream_seeds,
+            )?;
+        }
+        if remainder > 0 {
+            transfer_from_vault(
+                token_program_info,
+                vault_info,
+                payer_token_info,
+                stream_info,
+                remainder,
+                stream_seeds,
+            )?;
+        }
+
+        stream_data.is_cancelled = true;
+        stream_data.checkpoint_streamed = streamed;
+        stream_data.checkpoint_ts = clock.unix_timestamp;
+        stream_data.withdrawn = streamed;
+        stream_data.deposited = streamed;
+        stream_data.serialize(&mut &mut stream_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_init_campaign(
+        accounts: &[AccountInfo],
+        goal: u64,
+        deadline: i64,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if goal == 0 {
+            msg!("Invalid campaign goal");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let account_info_iter = &mut accounts.iter();
+        let campaign_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let creator_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if campaign_info.data_len() != 0 {
+            msg!("Trying reinitialize an existing campaign");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        msg!("Creating campaign metadata");
+        let escrow_bump = create_program_account(
+            program_id,
+            campaign_info,
+            creator_info,
+            rent_info,
+            system_program_info,
+            CampaignData::LEN,
+            &[
+                b"campaign",
+                creator_info.key.as_ref(),
+                mint_info.key.as_ref(),
+                pass.as_ref(),
+            ],
+        )?;
+        msg!("Creating vault");
+        let vault_bump = create_vault(
+            program_id,
+            vault_info,
+            mint_info,
+            campaign_info,
+            creator_info,
+            token_program_info,
+            rent_info,
+            system_program_info,
+            &[b"vault", campaign_info.key.as_ref()],
+        )?;
+
+        CampaignData {
+            is_initialized: true,
+            is_withdrawn: false,
+            pubkey_creator: *creator_info.key,
+            pubkey_mint: *mint_info.key,
+            goal,
+            deadline,
+            raised: 0,
+            escrow_bump,
+            vault_bump,
+        }
+        .serialize(&mut &mut campaign_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_contribute(
+        accounts: &[AccountInfo],
+        amount: u64,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let campaign_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let receipt_info = next_account_info(account_info_iter)?;
+        let contributor_token_info = next_account_info(account_info_iter)?;
+        let contributor_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut campaign_data = CampaignData::try_from_slice(&campaign_info.data.borrow())?;
+        if !campaign_data.is_initialized {
+            msg!("Invalid State");
+            return Err(ProgramError::UninitializedAccount);
+        }
+        if amount == 0 {
+            msg!("Invalid contribution");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let clock = Clock::from_account_info(clock_info)?;
+        if clock.unix_timestamp >= campaign_data.deadline {
+            msg!("Campaign has ended");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        validate_escrow_and_vault(
+            campaign_info,
+            vault_info,
+            &campaign_seeds(&campaign_data, &pass),
+            campaign_data.vault_bump,
+            program_id,
+        )?;
+
+        let mut receipt = if receipt_info.data_len() == 0 {
+            msg!("Creating contribution receipt");
+            let bump = create_program_account(
+                program_id,
+                receipt_info,
+                contributor_info,
+                rent_info,
+                system_program_info,
+                ContributionReceipt::LEN,
+                &[
+                    b"receipt",
+                    campaign_info.key.as_ref(),
+                    contributor_info.key.as_ref(),
+                ],
+            )?;
+            ContributionReceipt {
+                is_initialized: true,
+                pubkey_campaign: *campaign_info.key,
+                pubkey_contributor: *contributor_info.key,
+                amount: 0,
+                bump,
+            }
+        } else {
+            let receipt = ContributionReceipt::try_from_slice(&receipt_info.data.borrow())?;
+            validate_receipt(
+                receipt_info,
+                &receipt,
+                campaign_info,
+                contributor_info,
+                program_id,
+            )?;
+            receipt
+        };
+
+        msg!("Sending transfer");
+        transfer_tokens(
+            token_program_info,
+            contributor_token_info,
+            vault_info,
+            contributor_info,
+            amount,
+        )?;
+
+        receipt.amount = receipt
+            .amount
+            .checked_add(amount)
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        campaign_data.raised = campaign_data
+            .raised
+            .checked_add(amount)
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        receipt.serialize(&mut &mut receipt_info.data.borrow_mut()[..])?;
+        campaign_data.serialize(&mut &mut campaign_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_withdraw_campaign(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let campaign_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let creator_token_info = next_account_info(account_info_iter)?;
+        let creator_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut campaign_data = CampaignData::try_from_slice(&campaign_info.data.borrow())?;
+        if !campaign_data.is_initialized || campaign_data.is_withdrawn {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !creator_info.is_signer || *creator_info.key != campaign_data.pubkey_creator {
+            msg!("Only the creator can withdraw");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let clock = Clock::from_account_info(clock_info)?;
+        if clock.unix_timestamp < campaign_data.deadline {
+            msg!("Campaign has not ended");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if campaign_data.raised < campaign_data.goal {
+            msg!("Campaign goal not met");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        validate_token_account(
+            creator_token_info,
+            token_program_info,
+            &campaign_data.pubkey_creator,
+            &campaign_data.pubkey_mint,
+        )?;
+        let seeds = campaign_seeds(&campaign_data, &pass);
+        validate_escrow_and_vault(
+            campaign_info,
+            vault_info,
+            &seeds,
+            campaign_data.vault_bump,
+            program_id,
+        )?;
+
+        msg!("Sending transfer");
+        transfer_from_vault(
+            token_program_info,
+            vault_info,
+            creator_token_info,
+            campaign_info,
+            campaign_data.raised,
+            &seeds,
+        )?;
+
+        campaign_data.is_withdrawn = true;
+        campaign_data.serialize(&mut &mut campaign_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_refund(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let campaign_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let receipt_info = next_account_info(account_info_iter)?;
+        let contributor_token_info = next_account_info(account_info_iter)?;
+        let contributor_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let campaign_data = CampaignData::try_from_slice(&campaign_info.data.borrow())?;
+        if !campaign_data.is_initialized {
+            msg!("Invalid State");
+            return Err(ProgramError::UninitializedAccount);
+        }
+        if !contributor_info.is_signer {
+            msg!("Contributor must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let clock = Clock::from_account_info(clock_info)?;
+        if clock.unix_timestamp < campaign_data.deadline {
+            msg!("Campaign has not ended");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if campaign_data.raised >= campaign_data.goal {
+            msg!("Campaign goal was met");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut receipt = ContributionReceipt::try_from_slice(&receipt_info.data.borrow())?;
+        validate_receipt(
+            receipt_info,
+            &receipt,
+            campaign_info,
+            contributor_info,
+            program_id,
+        )?;
+        if receipt.amount == 0 {
+            msg!("Nothing to refund");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        validate_token_account(
+            contributor_token_info,
+            token_program_info,
+            contributor_info.key,
+            &campaign_data.pubkey_mint,
+        )?;
+        let seeds = campaign_seeds(&campaign_data, &pass);
+        validate_escrow_and_vault(
+            campaign_info,
+            vault_info,
+            &seeds,
+            campaign_data.vault_bump,
+            program_id,
+        )?;
+
+        msg!("Sending transfer");
+        transfer_from_vault(
+            token_program_info,
+            vault_info,
+            contributor_token_info,
+            campaign_info,
+            receipt.amount,
+            &seeds,
+        )?;
+
+        receipt.amount = 0;
+        receipt.serialize(&mut &mut receipt_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_write_option(
+        accounts: &[AccountInfo],
+        terms: OptionTerms,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let OptionTerms {
+            amount_x,
+            premium,
+            strike,
+            exercise_start,
+            expiry,
+        } = terms;
+        if amount_x == 0 || strike == 0 || exercise_start >= expiry {
+            msg!("Invalid option terms");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let account_info_iter = &mut accounts.iter();
+        let option_info = next_account_info(account_info_iter)?;
+        let mint_x_info = next_account_info(account_info_iter)?;
+        let mint_y_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let writer_info = next_account_info(account_info_iter)?;
+        let writer_token_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if option_info.data_len() != 0 {
+            msg!("Trying reinitialize an existing option");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        msg!("Creating option metadata");
+        let escrow_bump = create_program_account(
+            program_id,
+            option_info,
+            writer_info,
+            rent_info,
+            system_program_info,
+            OptionData::LEN,
+            &[
+                b"option",
+                writer_info.key.as_ref(),
+                mint_x_info.key.as_ref(),
+                mint_y_info.key.as_ref(),
+                pass.as_ref(),
+            ],
+        )?;
+        msg!("Creating vault for mint x");
+        let vault_bump = create_vault(
+            program_id,
+            vault_info,
+            mint_x_info,
+            option_info,
+            writer_info,
+            token_program_info,
+            rent_info,
+            system_program_info,
+            &[b"vault", option_info.key.as_ref()],
+        )?;
+
+        msg!("Sending transfer");
+        transfer_tokens(
+            token_program_info,
+            writer_token_info,
+            vault_info,
+            writer_info,
+            amount_x,
+        )?;
+
+        OptionData {
+            state: OptionState::Written,
+            pubkey_writer: *writer_info.key,
+            pubkey_holder: Pubkey::default(),
+            pubkey_mint_x: *mint_x_info.key,
+            pubkey_mint_y: *mint_y_info.key,
+            amount_x,
+            premium,
+            strike,
+            exercise_start,
+            expiry,
+            escrow_bump,
+            vault_bump,
+        }
+        .serialize(&mut &mut option_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_buy_option(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let option_info = next_account_info(account_info_iter)?;
+        let buyer_token_info = next_account_info(account_info_iter)?;
+        let writer_token_info = next_account_info(account_info_iter)?;
+        let buyer_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut option_data = OptionData::try_from_slice(&option_info.data.borrow())?;
+        if option_data.state != OptionState::Written {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !buyer_info.is_signer {
+            msg!("Buyer must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let clock = Clock::from_account_info(clock_info)?;
+        if clock.unix_timestamp >= option_data.expiry {
+            msg!("Option has expired");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        validate_option_key(option_info, &option_data, pass, program_id)?;
+        validate_token_account(
+            writer_token_info,
+            token_program_info,
+            &option_data.pubkey_writer,
+            &option_data.pubkey_mint_y,
+        )?;
+
+        if option_data.premium > 0 {
+            msg!("Sending premium");
+            transfer_tokens(
+                token_program_info,
+                buyer_token_info,
+                writer_token_info,
+                buyer_info,
+                option_data.premium,
+            )?;
+        }
+
+        option_data.state = OptionState::Held;
+        option_data.pubkey_holder = *buyer_info.key;
+        option_data.serialize(&mut &mut option_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_exercise_option(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let option_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let holder_x_token_info = next_account_info(account_info_iter)?;
+        let holder_y_token_info = next_account_info(account_info_iter)?;
+        let writer_token_info = next_account_info(account_info_iter)?;
+        let holder_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut option_data = OptionData::try_from_slice(&option_info.data.borrow())?;
+        if option_data.state != OptionState::Held {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !holder_info.is_signer || *holder_info.key != option_data.pubkey_holder {
+            msg!("Only the holder can exercise");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let clock = Clock::from_account_info(clock_info)?;
+        if clock.unix_timestamp < option_data.exercise_start
+            || clock.unix_timestamp >= option_data.expiry
+        {
+            msg!("Outside of the exercise window");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        validate_token_account(
+            holder_x_token_info,
+            token_program_info,
+            &option_data.pubkey_holder,
+            &option_data.pubkey_mint_x,
+        )?;
+        validate_token_account(
+            writer_token_info,
+            token_program_info,
+            &option_data.pubkey_writer,
+            &option_data.pubkey_mint_y,
+        )?;
+        let seeds = option_seeds(&option_data, &pass);
+        validate_escrow_and_vault(
+            option_info,
+            vault_info,
+            &seeds,
+            option_data.vault_bump,
+            program_id,
+        )?;
+
+        msg!("Sending strike");
+        transfer_tokens(
+            token_program_info,
+            holder_y_token_info,
+            writer_token_info,
+            holder_info,
+            option_data.strike,
+        )?;
+        msg!("Sending underlying");
+        transfer_from_vault(
+            token_program_info,
+            vault_info,
+            holder_x_token_info,
+            option_info,
+            option_data.amount_x,
+            &seeds,
+        )?;
+
+        option_data.state = OptionState::Exercised;
+        option_data.serialize(&mut &mut option_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_reclaim_option(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let option_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let writer_token_info = next_account_info(account_info_iter)?;
+        let writer_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut option_data = OptionData::try_from_slice(&option_info.data.borrow())?;
+        match option_data.state {
+            OptionState::Written | OptionState::Held => {}
+            _ => {
+                msg!("Invalid State");
+                return Err(ProgramError::InvalidAccountData);
+            }
+        }
+        if !writer_info.is_signer || *writer_info.key != option_data.pubkey_writer {
+            msg!("Only the writer can reclaim");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let clock = Clock::from_account_info(clock_info)?;
+        if clock.unix_timestamp < option_data.expiry {
+            msg!("Option has not expired");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        validate_token_account(
+            writer_token_info,
+            token_program_info,
+            &option_data.pubkey_writer,
+            &option_data.pubkey_mint_x,
+        )?;
+        let seeds = option_seeds(&option_data, &pass);
+        validate_escrow_and_vault(
+            option_info,
+            vault_info,
+            &seeds,
+            option_data.vault_bump,
+            program_id,
+        )?;
+
+        msg!("Sending transfer");
+        transfer_from_vault(
+            token_program_info,
+            vault_info,
+            writer_token_info,
+            option_info,
+            option_data.amount_x,
+            &seeds,
+        )?;
+
+        option_data.state = OptionState::Reclaimed;
+        option_data.serialize(&mut &mut option_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_init_auction(
+        accounts: &[AccountInfo],
+        amount_x: u64,
+        reserve_price: u64,
+        min_increment: u64,
+        end_ts: i64,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if amount_x == 0 {
+            msg!("Invalid auction terms");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let account_info_iter = &mut accounts.iter();
+        let auction_info = next_account_info(account_info_iter)?;
+        let mint_x_info = next_account_info(account_info_iter)?;
+        let mint_y_info = next_account_info(account_info_iter)?;
+        let vault_x_info = next_account_info(account_info_iter)?;
+        let vault_y_info = next_account_info(account_info_iter)?;
+        let seller_info = next_account_info(account_info_iter)?;
+        let seller_token_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if auction_info.data_len() != 0 {
+            msg!("Trying reinitialize an existing auction");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        msg!("Creating auction metadata");
+        let escrow_bump = create_program_account(
+            program_id,
+            auction_info,
+            seller_info,
+            rent_info,
+            system_program_info,
+            AuctionData::LEN,
+            &[
+                b"auction",
+                seller_info.key.as_ref(),
+                mint_x_info.key.as_ref(),
+                mint_y_info.key.as_ref(),
+                pass.as_ref(),
+            ],
+        )?;
+        msg!("Creating vault for mint x");
+        let vault_x_bump = create_vault(
+            program_id,
+            vault_x_info,
+            mint_x_info,
+            auction_info,
+            seller_info,
+            token_program_info,
+            rent_info,
+            system_program_info,
+            &[b"vault_x", auction_info.key.as_ref()],
+        )?;
+        msg!("Creating vault for mint y");
+        let vault_y_bump = create_vault(
+            program_id,
+            vault_y_info,
+            mint_y_info,
+            auction_info,
+            seller_info,
+            token_program_info,
+            rent_info,
+            system_program_info,
+            &[b"vault_y", auction_info.key.as_ref()],
+        )?;
+
+        msg!("Sending transfer");
+        transfer_tokens(
+            token_program_info,
+            seller_token_info,
+            vault_x_info,
+            seller_info,
+            amount_x,
+        )?;
+
+        AuctionData {
+            is_initialized: true,
+            is_settled: false,
+            pubkey_seller: *seller_info.key,
+            pubkey_mint_x: *mint_x_info.key,
+            pubkey_mint_y: *mint_y_info.key,
+            amount_x,
+            reserve_price,
+            min_increment,
+            end_ts,
+            pubkey_top_bidder: Pubkey::default(),
+            top_bid: 0,
+            escrow_bump,
+            vault_x_bump,
+            vault_y_bump,
+        }
+        .serialize(&mut &mut auction_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_bid(
+        accounts: &[AccountInfo],
+        amount: u64,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let auction_info = next_account_info(account_info_iter)?;
+        let vault_y_info = next_account_info(account_info_iter)?;
+        let bidder_token_info = next_account_info(account_info_iter)?;
+        let previous_bidder_token_info = next_account_info(account_info_iter)?;
+        let bidder_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut auction_data = AuctionData::try_from_slice(&auction_info.data.borrow())?;
+        if !auction_data.is_initialized || auction_data.is_settled {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !bidder_info.is_signer {
+            msg!("Bidder must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let clock = Clock::from_account_info(clock_info)?;
+        if clock.unix_timestamp >= auction_data.end_ts {
+            msg!("Auction has ended");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if amount < auction_data.min_bid() {
+            msg!("Bid too low");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let seeds = auction_seeds(&auction_data, &pass);
+        validate_auction_key(auction_info, &seeds, program_id)?;
+        validate_escrow_vault(
+            vault_y_info,
+            b"vault_y",
+            auction_info,
+            auction_data.vault_y_bump,
+            program_id,
+        )?;
+
+        msg!("Sending bid");
+        transfer_tokens(
+            token_program_info,
+            bidder_token_info,
+            vault_y_info,
+            bidder_info,
+            amount,
+        )?;
+        if auction_data.top_bid > 0 {
+            validate_token_account(
+                previous_bidder_token_info,
+                token_program_info,
+                &auction_data.pubkey_top_bidder,
+                &auction_data.pubkey_mint_y,
+            )?;
+            msg!("Refunding previous bid");
+            transfer_from_vault(
+                token_program_info,
+                vault_y_info,
+                previous_bidder_token_info,
+                auction_info,
+                auction_data.top_bid,
+                &seeds,
+            )?;
+        }
+
+        auction_data.pubkey_top_bidder = *bidder_info.key;
+        auction_data.top_bid = amount;
+        auction_data.serialize(&mut &mut auction_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_settle_auction(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let auction_info = next_account_info(account_info_iter)?;
+        let vault_x_info = next_account_info(account_info_iter)?;
+        let vault_y_info = next_account_info(account_info_iter)?;
+        let seller_x_token_info = next_account_info(account_info_iter)?;
+        let seller_y_token_info = next_account_info(account_info_iter)?;
+        let winner_token_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut auction_data = AuctionData::try_from_slice(&auction_info.data.borrow())?;
+        if !auction_data.is_initialized || auction_data.is_settled {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let clock = Clock::from_account_info(clock_info)?;
+        if clock.unix_timestamp < auction_data.end_ts {
+            msg!("Auction has not ended");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let seeds = auction_seeds(&auction_data, &pass);
+        validate_auction_key(auction_info, &seeds, program_id)?;
+        validate_escrow_vault(
+            vault_x_info,
+            b"vault_x",
+            auction_info,
+            auction_data.vault_x_bump,
+            program_id,
+        )?;
+        validate_escrow_vault(
+            vault_y_info,
+            b"vault_y",
+            auction_info,
+            auction_data.vault_y_bump,
+            program_id,
+        )?;
+
+        msg!("Sending transfers");
+        if auction_data.top_bid > 0 {
+            validate_token_account(
+                seller_y_token_info,
+                token_program_info,
+                &auction_data.pubkey_seller,
+                &auction_data.pubkey_mint_y,
+            )?;
+            validate_token_account(
+                winner_token_info,
+                token_program_info,
+                &auction_data.pubkey_top_bidder,
+                &auction_data.pubkey_mint_x,
+            )?;
+            transfer_from_vault(
+                token_program_info,
+                vault_y_info,
+                seller_y_token_info,
+                auction_info,
+                auction_data.top_bid,
+                &seeds,
+            )?;
+            transfer_from_vault(
+                token_program_info,
+                vault_x_info,
+                winner_token_info,
+                auction_info,
+                auction_data.amount_x,
+                &seeds,
+            )?;
+        } else {
+            validate_token_account(
+                seller_x_token_info,
+                token_program_info,
+                &auction_data.pubkey_seller,
+                &auction_data.pubkey_mint_x,
+            )?;
+            transfer_from_vault(
+                token_program_info,
+                vault_x_info,
+                seller_x_token_info,
+                auction_info,
+                auction_data.amount_x,
+                &seeds,
+            )?;
+        }
+
+        auction_data.is_settled = true;
+        auction_data.serialize(&mut &mut auction_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_init_dutch_auction(
+        accounts: &[AccountInfo],
+        terms: DutchAuctionTerms,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let DutchAuctionTerms {
+            amount_x,
+            start_price,
+            end_price,
+            start_slot,
+            end_slot,
+        } = terms;
+        if amount_x == 0 || start_price < end_price || start_slot >= end_slot {
+            msg!("Invalid auction terms");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let account_info_iter = &mut accounts.iter();
+        let auction_info = next_account_info(account_info_iter)?;
+        let mint_x_info = next_account_info(account_info_iter)?;
+        let mint_y_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let seller_info = next_account_info(account_info_iter)?;
+        let seller_token_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if auction_info.data_len() != 0 {
+            msg!("Trying reinitialize an existing auction");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        msg!("Creating auction metadata");
+        let escrow_bump = create_program_account(
+            program_id,
+            auction_info,
+            seller_info,
+            rent_info,
+            system_program_info,
+            DutchAuctionData::LEN,
+            &[
+                b"dutch_auction",
+                seller_info.key.as_ref(),
+                mint_x_info.key.as_ref(),
+                mint_y_info.key.as_ref(),
+                pass.as_ref(),
+            ],
+        )?;
+        msg!("Creating vault");
+        let vault_bump = create_vault(
+            program_id,
+            vault_info,
+            mint_x_info,
+            auction_info,
+            seller_info,
+            token_program_info,
+            rent_info,
+            system_program_info,
+            &[b"vault", auction_info.key.as_ref()],
+        )?;
+
+        msg!("Sending transfer");
+        transfer_tokens(
+            token_program_info,
+            seller_token_info,
+            vault_info,
+            seller_info,
+            amount_x,
+        )?;
+
+        DutchAuctionData {
+            is_initialized: true,
+            is_closed: false,
+            pubkey_seller: *seller_info.key,
+            pubkey_mint_x: *mint_x_info.key,
+            pubkey_mint_y: *mint_y_info.key,
+            amount_x,
+            start_price,
+            end_price,
+            start_slot,
+            end_slot,
+            escrow_bump,
+            vault_bump,
+        }
+        .serialize(&mut &mut auction_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_buy_dutch(
+        accounts: &[AccountInfo],
+        max_price: u64,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let auction_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let buyer_y_token_info = next_account_info(account_info_iter)?;
+        let seller_y_token_info = next_account_info(account_info_iter)?;
+        let buyer_x_token_info = next_account_info(account_info_iter)?;
+        let buyer_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut auction_data = DutchAuctionData::try_from_slice(&auction_info.data.borrow())?;
+        if !auction_data.is_initialized || auction_data.is_closed {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !buyer_info.is_signer {
+            msg!("Buyer must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let clock = Clock::from_account_info(clock_info)?;
+        if clock.slot < auction_data.start_slot {
+            msg!("Auction has not started");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let price = auction_data.price_at(clock.slot);
+        if price > max_price {
+            msg!("Price above max_price");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let seeds = dutch_auction_seeds(&auction_data, &pass);
+        validate_escrow_and_vault(
+            auction_info,
+            vault_info,
+            &seeds,
+            auction_data.vault_bump,
+            program_id,
+        )?;
+        validate_token_account(
+            seller_y_token_info,
+            token_program_info,
+            &auction_data.pubkey_seller,
+            &auction_data.pubkey_mint_y,
+        )?;
+
+        msg!("Sending transfers");
+        transfer_tokens(
+            token_program_info,
+            buyer_y_token_info,
+            seller_y_token_info,
+            buyer_info,
+            price,
+        )?;
+        transfer_from_vault(
+            token_program_info,
+            vault_info,
+            buyer_x_token_info,
+            auction_info,
+            auction_data.amount_x,
+            &seeds,
+        )?;
+
+        auction_data.is_closed = true;
+        auction_data.serialize(&mut &mut auction_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_cancel_dutch_auction(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let auction_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let seller_token_info = next_account_info(account_info_iter)?;
+        let seller_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let mut auction_data = DutchAuctionData::try_from_slice(&auction_info.data.borrow())?;
+        if !auction_data.is_initialized || auction_data.is_closed {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !seller_info.is_signer || *seller_info.key != auction_data.pubkey_seller {
+            msg!("Only the seller can cancel");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let seeds = dutch_auction_seeds(&auction_data, &pass);
+        validate_escrow_and_vault(
+            auction_info,
+            vault_info,
+            &seeds,
+            auction_data.vault_bump,
+            program_id,
+        )?;
+
+        msg!("Sending transfer");
+        transfer_from_vault(
+            token_program_info,
+            vault_info,
+            seller_token_info,
+            auction_info,
+            auction_data.amount_x,
+            &seeds,
+        )?;
+
+        auction_data.is_closed = true;
+        auction_data.serialize(&mut &mut auction_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_init_distributor(
+        accounts: &[AccountInfo],
+        merkle_root: [u8; 32],
+        leaf_count: u32,
+        total: u64,
+        expiry: i64,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if leaf_count == 0 || total == 0 {
+            msg!("Invalid distribution");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let account_info_iter = &mut accounts.iter();
+        let distributor_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let bitmap_info = next_account_info(account_info_iter)?;
+        let depositor_info = next_account_info(account_info_iter)?;
+        let depositor_token_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if distributor_info.data_len() != 0 {
+            msg!("Trying reinitialize an existing distributor");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        msg!("Creating distributor metadata");
+        let escrow_bump = create_program_account(
+            program_id,
+            distributor_info,
+            depositor_info,
+            rent_info,
+            system_program_info,
+            DistributorData::LEN,
+            &[
+                b"distributor",
+                depositor_info.key.as_ref(),
+                mint_info.key.as_ref(),
+                pass.as_ref(),
+            ],
+        )?;
+        msg!("Creating vault");
+        let vault_bump = create_vault(
+            program_id,
+            vault_info,
+            mint_info,
+            distributor_info,
+            depositor_info,
+            token_program_info,
+            rent_info,
+            system_program_info,
+            &[b"vault", distributor_info.key.as_ref()],
+        )?;
+
+        let mut distributor_data = DistributorData {
+            is_initialized: true,
+            is_reclaimed: false,
+            pubkey_depositor: *depositor_info.key,
+            pubkey_mint: *mint_info.key,
+            merkle_root,
+            leaf_count,
+            total,
+            claimed: 0,
+            expiry,
+            escrow_bump,
+            vault_bump,
+            bitmap_bump: 0,
+        };
+        msg!("Creating claim bitmap");
+        distributor_data.bitmap_bump = create_program_account(
+            program_id,
+            bitmap_info,
+            depositor_info,
+            rent_info,
+            system_program_info,
+            distributor_data.bitmap_len(),
+            &[b"bitmap", distributor_info.key.as_ref()],
+        )?;
+
+        msg!("Sending transfer");
+        transfer_tokens(
+            token_program_info,
+            depositor_token_info,
+            vault_info,
+            depositor_info,
+            total,
+        )?;
+
+        distributor_data.serialize(&mut &mut distributor_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_claim_distribution(
+        accounts: &[AccountInfo],
+        index: u32,
+        amount: u64,
+        proof: Vec<[u8; 32]>,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let distributor_info = next_account_info(account_info_iter)?;
+        let bitmap_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let recipient_token_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut distributor_data =
+            DistributorData::try_from_slice(&distributor_info.data.borrow())?;
+        if !distributor_data.is_initialized || distributor_data.is_reclaimed {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let clock = Clock::from_account_info(clock_info)?;
+        if clock.unix_timestamp >= distributor_data.expiry {
+            msg!("Distribution has expired");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if index >= distributor_data.leaf_count {
+            msg!("Invalid leaf index");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let seeds = distributor_seeds(&distributor_data, &pass);
+        validate_escrow_and_vault(
+            distributor_info,
+            vault_info,
+            &seeds,
+            distributor_data.vault_bump,
+            program_id,
+        )?;
+        let bitmap_key = Pubkey::create_program_address(
+            &[
+                b"bitmap",
+                distributor_info.key.as_ref(),
+                &[distributor_data.bitmap_bump],
+            ],
+            program_id,
+        )?;
+        if bitmap_key != *bitmap_info.key {
+            msg!("Bitmap key mismatch");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if recipient_token_info.owner != token_program_info.key {
+            msg!("Invalid Token Account (system account not owned by Token Program)");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let recipient = Account::unpack(&recipient_token_info.data.borrow())?.owner;
+        let leaf = DistributorData::leaf(index, &recipient, amount);
+        if !distributor_data.verify_proof(&proof, leaf) {
+            msg!("Invalid proof");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        validate_token_account(
+            recipient_token_info,
+            token_program_info,
+            &recipient,
+            &distributor_data.pubkey_mint,
+        )?;
+
+        {
+            let mut bitmap = bitmap_info.data.borrow_mut();
+            let byte = &mut bitmap[index as usize / 8];
+            let bit = 1 << (index % 8);
+            if *byte & bit != 0 {
+                msg!("Leaf already claimed");
+                return Err(ProgramError::InvalidAccountData);
+            }
+            *byte |= bit;
+        }
+        let claimed = distributor_data.claimed.saturating_add(amount);
+        if claimed > distributor_data.total {
+            msg!("Claims exceed the distribution total");
+            return Err(ProgramError::InsufficientFunds);
+        }
+
+        msg!("Sending transfer");
+        transfer_from_vault(
+            token_program_info,
+            vault_info,
+            recipient_token_info,
+            distributor_info,
+            amount,
+            &seeds,
+        )?;
+
+        distributor_data.claimed = claimed;
+        distributor_data.serialize(&mut &mut distributor_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_reclaim_distribution(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let distributor_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let depositor_token_info = next_account_info(account_info_iter)?;
+        let depositor_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut distributor_data =
+            DistributorData::try_from_slice(&distributor_info.data.borrow())?;
+        if !distributor_data.is_initialized || distributor_data.is_reclaimed {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !depositor_info.is_signer || *depositor_info.key != distributor_data.pubkey_depositor {
+            msg!("Only the depositor can reclaim");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let clock = Clock::from_account_info(clock_info)?;
+        if clock.unix_timestamp < distributor_data.expiry {
+            msg!("Distribution has not expired");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let seeds = distributor_seeds(&distributor_data, &pass);
+        validate_escrow_and_vault(
+            distributor_info,
+            vault_info,
+            &seeds,
+            distributor_data.vault_bump,
+            program_id,
+        )?;
+
+        msg!("Sending transfer");
+        transfer_from_vault(
+            token_program_info,
+            vault_info,
+            depositor_token_info,
+            distributor_info,
+            distributor_data.total - distributor_data.claimed,
+            &seeds,
+        )?;
+
+        distributor_data.is_reclaimed = true;
+        distributor_data.serialize(&mut &mut distributor_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_init_ring(
+        accounts: &[AccountInfo],
+        legs: Vec<RingLeg>,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if legs.len() < 2 || legs.len() > MAX_RING_LEGS {
+            msg!("Invalid number of legs");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        for (i, leg) in legs.iter().enumerate() {
+            let owed_from = leg.owed_from as usize;
+            if leg.amount == 0
+                || owed_from >= legs.len()
+                || owed_from == i
+                || legs[..i]
+                    .iter()
+                    .any(|other| other.owed_from == leg.owed_from)
+            {
+                msg!("Invalid ring legs");
+                return Err(ProgramError::InvalidInstructionData);
+            }
+        }
+
+        let account_info_iter = &mut accounts.iter();
+        let ring_info = next_account_info(account_info_iter)?;
+        let creator_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if ring_info.data_len() != 0 {
+            msg!("Trying reinitialize an existing ring");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        msg!("Creating ring metadata");
+        let escrow_bump = create_program_account(
+            program_id,
+            ring_info,
+            creator_info,
+            rent_info,
+            system_program_info,
+            RingData::LEN,
+            &[b"ring", creator_info.key.as_ref(), pass.as_ref()],
+        )?;
+
+        let mut ring_legs = [RingLeg::default(); MAX_RING_LEGS];
+        for (ring_leg, leg) in ring_legs.iter_mut().zip(legs.iter()) {
+            *ring_leg = RingLeg {
+                deposited: false,
+                withdrawn: false,
+                vault_bump: 0,
+                ..*leg
+            };
+        }
+        RingData {
+            is_initialized: true,
+            pubkey_creator: *creator_info.key,
+            legs: ring_legs,
+            leg_count: legs.len() as u8,
+            escrow_bump,
+        }
+        .serialize(&mut &mut ring_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_ring_deposit(
+        accounts: &[AccountInfo],
+        index: u8,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let ring_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let participant_token_info = next_account_info(account_info_iter)?;
+        let participant_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let mut ring_data = RingData::try_from_slice(&ring_info.data.borrow())?;
+        if !ring_data.is_initialized || index >= ring_data.leg_count {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        validate_ring_key(ring_info, &ring_data, &pass, program_id)?;
+        let leg = ring_data.legs[index as usize];
+        if !participant_info.is_signer || *participant_info.key != leg.participant {
+            msg!("Only the participant of the leg can deposit");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if leg.deposited {
+            msg!("Leg already deposited");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if *mint_info.key != leg.mint {
+            msg!("Invalid Mint");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let vault_bump = if vault_info.data_len() == 0 {
+            msg!("Creating vault");
+            create_vault(
+                program_id,
+                vault_info,
+                mint_info,
+                ring_info,
+                participant_info,
+                token_program_info,
+                rent_info,
+                system_program_info,
+                &[b"ring_vault", ring_info.key.as_ref(), &[index]],
+            )?
+        } else {
+            leg.vault_bump
+        };
+        // the leg only counts as deposited if its tokens sit in the vault withdrawals are paid from
+        validate_ring_vault(vault_info, ring_info, index, vault_bump, program_id)?;
+
+        msg!("Sending transfer");
+        transfer_tokens(
+            token_program_info,
+            participant_token_info,
+            vault_info,
+            participant_info,
+            leg.amount,
+        )?;
+
+        ring_data.legs[index as usize].deposited = true;
+        ring_data.legs[index as usize].vault_bump = vault_bump;
+        ring_data.serialize(&mut &mut ring_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_ring_withdraw(
+        accounts: &[AccountInfo],
+        index: u8,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let ring_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let participant_token_info = next_account_info(account_info_iter)?;
+        let participant_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let mut ring_data = RingData::try_from_slice(&ring_info.data.borrow())?;
+        if !ring_data.is_initialized || index >= ring_data.leg_count {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        validate_ring_key(ring_info, &ring_data, &pass, program_id)?;
+        let leg = ring_data.legs[index as usize];
+        if !participant_info.is_signer || *participant_info.key != leg.participant {
+            msg!("Only the participant of the leg can withdraw");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let source_index = if ring_data.is_complete() {
+            if leg.withdrawn {
+                msg!("Leg already withdrawn");
+                return Err(ProgramError::InvalidAccountData);
+            }
+            ring_data.legs[index as usize].withdrawn = true;
+            leg.owed_from
+        } else {
+            if !leg.deposited {
+                msg!("Nothing to refund");
+                return Err(ProgramError::InvalidAccountData);
+            }
+            ring_data.legs[index as usize].deposited = false;
+            index
+        };
+        let source = ring_data.legs[source_index as usize];
+        validate_ring_vault(
+            vault_info,
+            ring_info,
+            source_index,
+            source.vault_bump,
+            program_id,
+        )?;
+        validate_token_account(
+            participant_token_info,
+            token_program_info,
+            &leg.participant,
+            &source.mint,
+        )?;
+
+        msg!("Sending transfer");
+        let seeds = ring_seeds(&ring_data, &pass);
+        transfer_from_vault(
+            token_program_info,
+            vault_info,
+            participant_token_info,
+            ring_info,
+            source.amount,
+            &seeds,
+        )?;
+
+        ring_data.serialize(&mut &mut ring_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_init_delivery(
+        accounts: &[AccountInfo],
+        amount: u64,
+        attestor: Pubkey,
+        deadline: i64,
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if amount == 0 || attestor == Pubkey::default() {
+            msg!("Invalid delivery terms");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let account_info_iter = &mut accounts.iter();
+        let delivery_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let buyer_info = next_account_info(account_info_iter)?;
+        let buyer_token_info = next_account_info(account_info_iter)?;
+        let seller_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if delivery_info.data_len() != 0 {
+            msg!("Trying reinitialize an existing delivery escrow");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        msg!("Creating delivery metadata");
+        let escrow_bump = create_program_account(
+            program_id,
+            delivery_info,
+            buyer_info,
+            rent_info,
+            system_program_info,
+            DeliveryData::LEN,
+            &[
+                b"delivery",
+                buyer_info.key.as_ref(),
+                seller_info.key.as_ref(),
+                mint_info.key.as_ref(),
+                pass.as_ref(),
+            ],
+        )?;
+        msg!("Creating vault");
+        let vault_bump = create_vault(
+            program_id,
+            vault_info,
+            mint_info,
+            delivery_info,
+            buyer_info,
+            token_program_info,
+            rent_info,
+            system_program_info,
+            &[b"vault", delivery_info.key.as_ref()],
+        )?;
+
+        msg!("Sending transfer");
+        transfer_tokens(
+            token_program_info,
+            buyer_token_info,
+            vault_info,
+            buyer_info,
+            amount,
+        )?;
+
+        DeliveryData {
+            is_initialized: true,
+            is_settled: false,
+            pubkey_buyer: *buyer_info.key,
+            pubkey_seller: *seller_info.key,
+            pubkey_attestor: attestor,
+            pubkey_mint: *mint_info.key,
+            amount,
+            deadline,
+            escrow_bump,
+            vault_bump,
+        }
+        .serialize(&mut &mut delivery_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_confirm_delivery(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let delivery_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let seller_token_info = next_account_info(account_info_iter)?;
+        let instructions_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut delivery_data = DeliveryData::try_from_slice(&delivery_info.data.borrow())?;
+        if !delivery_data.is_initialized || delivery_data.is_settled {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let clock = Clock::from_account_info(clock_info)?;
+        if clock.unix_timestamp >= delivery_data.deadline {
+            msg!("Delivery deadline has passed");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let seeds = delivery_seeds(&delivery_data, &pass);
+        validate_escrow_and_vault(
+            delivery_info,
+            vault_info,
+            &seeds,
+            delivery_data.vault_bump,
+            program_id,
+        )?;
+        ed25519::verify_signatures(
+            instructions_info,
+            &[(
+                &delivery_data.pubkey_attestor,
+                &DeliveryData::attestation_message(delivery_info.key),
+            )],
+        )?;
+        validate_token_account(
+            seller_token_info,
+            token_program_info,
+            &delivery_data.pubkey_seller,
+            &delivery_data.pubkey_mint,
+        )?;
+
+        msg!("Sending transfer");
+        transfer_from_vault(
+            token_program_info,
+            vault_info,
+            seller_token_info,
+            delivery_info,
+            delivery_data.amount,
+            &seeds,
+        )?;
+
+        delivery_data.is_settled = true;
+        delivery_data.serialize(&mut &mut delivery_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_refund_delivery(
+        accounts: &[AccountInfo],
+        pass: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let delivery_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let buyer_token_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let mut delivery_data = DeliveryData::try_from_slice(&delivery_info.data.borrow())?;
+        if !delivery_data.is_initialized || delivery_data.is_settled {
+            msg!("Invalid State");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let clock = Clock::from_account_info(clock_info)?;
+        if clock.unix_timestamp < delivery_data.deadline {
+            msg!("Delivery deadline has not passed");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let seeds = delivery_seeds(&delivery_data, &pass);
+        validate_escrow_and_vault(
+            delivery_info,
+            vault_info,
+            &seeds,
+            delivery_data.vault_bump,
+            program_id,
+        )?;
+        validate_token_account(
+            buyer_token_info,
+            token_program_info,
+            &delivery_data.pubkey_buyer,
+            &delivery_data.pubkey_mint,
+        )?;
+
+        msg!("Sending transfer");
+        transfer_from_vault(
+            token_program_info,
+            vault_info,
+            buyer_token_info,
+            delivery_info,
+            delivery_data.amount,
+            &seeds,
+        )?;
+
+        delivery_data.is_settled = true;
+        delivery_data.serialize(&mut &mut delivery_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+}
+
+fn escrow_seeds<'a>(escrow_data: &'a EscrowData, pass: &'a [u8; 32]) -> [&'a [u8]; 7] {
+    [
+        b"escrow",
+        escrow_data.pubkey_alice.as_ref(),
+        escrow_data.pubkey_bob.as_ref(),
+        escrow_data.pubkey_mint_x.as_ref(),
+        escrow_data.pubkey_mint_y.as_ref(),
+        pass.as_ref(),
+        std::slice::from_ref(&escrow_data.escrow_bump),
+    ]
+}
+
+fn campaign_seeds<'a>(campaign_data: &'a CampaignData, pass: &'a [u8; 32]) -> [&'a [u8]; 5] {
+    [
+        b"campaign",
+        campaign_data.pubkey_creator.as_ref(),
+        campaign_data.pubkey_mint.as_ref(),
+        pass.as_ref(),
+        std::slice::from_ref(&campaign_data.escrow_bump),
+    ]
+}
+
+fn option_seeds<'a>(option_data: &'a OptionData, pass: &'a [u8; 32]) -> [&'a [u8]; 6] {
+    [
+        b"option",
+        option_data.pubkey_writer.as_ref(),
+        option_data.pubkey_mint_x.as_ref(),
+        option_data.pubkey_mint_y.as_ref(),
+        pass.as_ref(),
+        std::slice::from_ref(&option_data.escrow_bump),
+    ]
+}
+
+fn validate_option_key(
+    option_info: &AccountInfo,
+    option_data: &OptionData,
+    pass: [u8; 32],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let option_key = Pubkey::create_program_address(&option_seeds(option_data, &pass), program_id)?;
+    if option_key != *option_info.key {
+        msg!("Option key mismatch");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+fn auction_seeds<'a>(auction_data: &'a AuctionData, pass: &'a [u8; 32]) -> [&'a [u8]; 6] {
+    [
+        b"auction",
+        auction_data.pubkey_seller.as_ref(),
+        auction_data.pubkey_mint_x.as_ref(),
+        auction_data.pubkey_mint_y.as_ref(),
+        pass.as_ref(),
+        std::slice::from_ref(&auction_data.escrow_bump),
+    ]
+}
+
+fn dutch_auction_seeds<'a>(
+    auction_data: &'a DutchAuctionData,
+    pass: &'a [u8; 32],
+) -> [&'a [u8]; 6] {
+    [
+        b"dutch_auction",
+        auction_data.pubkey_seller.as_ref(),
+        auction_data.pubkey_mint_x.as_ref(),
+        auction_data.pubkey_mint_y.as_ref(),
+        pass.as_ref(),
+        std::slice::from_ref(&auction_data.escrow_bump),
+    ]
+}
+
+fn distributor_seeds<'a>(
+    distributor_data: &'a DistributorData,
+    pass: &'a [u8; 32],
+) -> [&'a [u8]; 5] {
+    [
+        b"distributor",
+        distributor_data.pubkey_depositor.as_ref(),
+        distributor_data.pubkey_mint.as_ref(),
+        pass.as_ref(),
+        std::slice::from_ref(&distributor_data.escrow_bump),
+    ]
+}
+
+fn ring_seeds<'a>(ring_data: &'a RingData, pass: &'a [u8; 32]) -> [&'a [u8]; 4] {
+    [
+        b"ring",
+        ring_data.pubkey_creator.as_ref(),
+        pass.as_ref(),
+        std::slice::from_ref(&ring_data.escrow_bump),
+    ]
+}
+
+fn validate_ring_key(
+    ring_info: &AccountInfo,
+    ring_data: &RingData,
+    pass: &[u8; 32],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let ring_key = Pubkey::create_program_address(&ring_seeds(ring_data, pass), program_id)?;
+    if ring_key != *ring_info.key {
+        msg!("Ring key mismatch");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+fn validate_ring_vault(
+    vault_info: &AccountInfo,
+    ring_info: &AccountInfo,
+    index: u8,
+    vault_bump: u8,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let vault_key = Pubkey::create_program_address(
+        &[
+            b"ring_vault",
+            ring_info.key.as_ref(),
+            &[index],
+            &[vault_bump],
+        ],
+        program_id,
+    )?;
+    if vault_key != *vault_info.key {
+        msg!("Vault key mismatch");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+fn delivery_seeds<'a>(delivery_data: &'a DeliveryData, pass: &'a [u8; 32]) -> [&'a [u8]; 6] {
+    [
+        b"delivery",
+        delivery_data.pubkey_buyer.as_ref(),
+        delivery_data.pubkey_seller.as_ref(),
+        delivery_data.pubkey_mint.as_ref(),
+        pass.as_ref(),
+        std::slice::from_ref(&delivery_data.escrow_bump),
+    ]
+}
+
+fn validate_auction_key(
+    auction_info: &AccountInfo,
+    auction_seeds: &[&[u8]],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let auction_key = Pubkey::create_program_address(auction_seeds, program_id)?;
+    if auction_key != *auction_info.key {
+        msg!("Auction key mismatch");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+fn validate_receipt(
+    receipt_info: &AccountInfo,
+    receipt: &ContributionReceipt,
+    campaign_info: &AccountInfo,
+    contributor_info: &AccountInfo,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    if !receipt.is_initialized
+        || receipt.pubkey_campaign != *campaign_info.key
+        || receipt.pubkey_contributor != *contributor_info.key
+    {
+        msg!("Receipt mismatch");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let receipt_key = Pubkey::create_program_address(
+        &[
+            b"receipt",
+            campaign_info.key.as_ref(),
+            contributor_info.key.as_ref(),
+            &[receipt.bump],
+        ],
+        program_id,
+    )?;
+    if receipt_key != *receipt_info.key {
+        msg!("Receipt key mismatch");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+fn validate_escrow_key(
+    escrow_info: &AccountInfo,
+    escrow_data: &EscrowData,
+    pass: [u8; 32],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let escrow_key = Pubkey::create_program_address(&escrow_seeds(escrow_data, &pass), program_id)?;
+    if escrow_key != *escrow_info.key {
+        msg!("Escrow key mismatch");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+fn validate_vault_key(
+    vault_info: &AccountInfo,
+    escrow_data: &EscrowData,
+    vault_seed: &[u8],
+    bump_seed: u8,
+    pass: [u8; 32],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let vault_seeds = &[
+        vault_seed,
+        escrow_data.pubkey_alice.as_ref(),
+        escrow_data.pubkey_bob.as_ref(),
+        escrow_data.pubkey_mint_x.as_ref(),
+        escrow_data.pubkey_mint_y.as_ref(),
+        pass.as_ref(),
+        &[bump_seed],
+    ];
+    let vault_pubkey = Pubkey::create_program_address(vault_seeds, program_id)?;
+    if vault_pubkey != *vault_info.key {
+        msg!("Vault key mismatch");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+/// Moves a SOL bond from `party_info` into the escrow account
+fn post_bond<'a>(
+    party_info: &AccountInfo<'a>,
+    escrow_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    solana_program::program::invoke(
+        &system_instruction::transfer(party_info.key, escrow_info.key, amount),
+        &[
+            party_info.clone(),
+            escrow_info.clone(),
+            system_program_info.clone(),
+        ],
+    )
+}
+
+/// Pays bond lamports held by the program-owned escrow account out to `recipient_info`
+fn release_bond(
+    escrow_info: &AccountInfo,
+    recipient_info: &AccountInfo,
+    amount: u64,
+) -> ProgramResult {
+    **escrow_info.try_borrow_mut_lamports()? -= amount;
+    **recipient_info.try_borrow_mut_lamports()? += amount;
+    Ok(())
+}
+
+/// State of another swap escrow owned by this program, checked against its PDA for `pass`
+fn read_escrow_state(
+    escrow_info: &AccountInfo,
+    pass: [u8; 32],
+    program_id: &Pubkey,
+) -> Result<EscrowState, ProgramError> {
+    if escrow_info.owner != program_id || escrow_info.data_len() != EscrowData::LEN {
+        msg!("Not an escrow account");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let escrow_data = EscrowData::try_from_slice(&escrow_info.data.borrow())?;
+    validate_escrow_key(escrow_info, &escrow_data, pass, program_id)?;
+    Ok(escrow_data.state)
+}
+
+/// Checks the escrow PDA of a one-sided escrow kind and its `["vault", escrow]` vault
+fn validate_escrow_and_vault(
+    escrow_info: &AccountInfo,
+    vault_info: &AccountInfo,
+    escrow_seeds: &[&[u8]],
+    vault_bump: u8,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let escrow_key = Pubkey::create_program_address(escrow_seeds, program_id)?;
+    if escrow_key != *escrow_info.key {
+        msg!("Escrow key mismatch");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    validate_escrow_vault(vault_info, b"vault", escrow_info, vault_bump, program_id)
+}
+
+/// Checks a vault derived from the key of the escrow account owning it
+fn validate_escrow_vault(
+    vault_info: &AccountInfo,
+    vault_seed: &[u8],
+    escrow_info: &AccountInfo,
+    vault_bump: u8,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let vault_key = Pubkey::create_program_address(
+        &[vault_seed, escrow_info.key.as_ref(), &[vault_bump]],
+        program_id,
+    )?;
+    if vault_key != *vault_info.key {
+        msg!("Vault key mismatch");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+fn create_vault<'a>(
+    program_id: &Pubkey,
+    vault_info: &AccountInfo<'a>,
+    mint_info: &AccountInfo<'a>,
+    owner_info: &AccountInfo<'a>,
+    payer_info: &AccountInfo<'a>,
+    token_program_info: &AccountInfo<'a>,
+    rent_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    seeds: &[&[u8]],
+) -> Result<u8, ProgramError> {
+    let (vault_key, bump_seed) = Pubkey::find_program_address(seeds, program_id);
+    if vault_key != *vault_info.key {
+        msg!("Vault key mismatch");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let bump = [bump_seed];
+    let mut signer_seeds = seeds.to_vec();
+    signer_seeds.push(&bump);
+    create_pda_account(
+        vault_info,
+        payer_info,
+        rent_info,
+        system_program_info,
+        Account::LEN,
+        token_program_info.key,
+        &signer_seeds,
+    )?;
+    solana_program::program::invoke(
+        &initialize_account(
+            token_program_info.key,
+            vault_info.key,
+            mint_info.key,
+            owner_info.key,
+        )?,
+        &[
+            vault_info.clone(),
+            mint_info.clone(),
+            owner_info.clone(),
+            rent_info.clone(),
+            token_program_info.clone(),
+        ],
+    )?;
+    Ok(bump_seed)
+}
+
+/// Creates the program owned PDA derived from `seeds` (without bump), which `account_info` must be, and returns its bump seed
+fn create_program_account<'a>(
+    program_id: &Pubkey,
+    account_info: &AccountInfo<'a>,
+    payer_info: &AccountInfo<'a>,
+    rent_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> Result<u8, ProgramError> {
+    let (key, bump_seed) = Pubkey::find_program_address(seeds, program_id);
+    if key != *account_info.key {
+        msg!("Account key mismatch");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let bump = [bump_seed];
+    let mut signer_seeds = seeds.to_vec();
+    signer_seeds.push(&bump);
+    create_pda_account(
+        account_info,
+        payer_info,
+        rent_info,
+        system_program_info,
+        space,
+        program_id,
+        &signer_seeds,
+    )?;
+    Ok(bump_seed)
+}
+
+/// Creates the PDA signed for by `signer_seeds` with `owner`, even if it already holds lamports
+fn create_pda_account<'a>(
+    account_info: &AccountInfo<'a>,
+    payer_info: &AccountInfo<'a>,
+    rent_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    space: usize,
+    owner: &Pubkey,
+    signer_seeds: &[&[u8]],
+) -> ProgramResult {
+    let rent = &Rent::from_account_info(rent_info)?;
+    let required_lamports = rent
+        .minimum_balance(space)
+        .max(1)
+        .saturating_sub(account_info.lamports());
+
+    if account_info.lamports() == 0 {
+        solana_program::program::invoke_signed(
+            &system_instruction::create_account(
+                payer_info.key,    //from_pubkey
+                account_info.key,  //to_pubkey
+                required_lamports, //lamports
+                space as u64,      //space
+                owner,
+            ),
+            &[
+                payer_info.clone(),
+                account_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[signer_seeds],
+        )?;
+        return Ok(());
+    }
+
+    // anyone can send lamports to the address beforehand, which makes create_account fail
+    if required_lamports > 0 {
+        solana_program::program::invoke(
+            &system_instruction::transfer(payer_info.key, account_info.key, required_lamports),
+            &[
+                payer_info.clone(),
+                account_info.clone(),
+                system_program_info.clone(),
+            ],
+        )?;
+    }
+    solana_program::program::invoke_signed(
+        &system_instruction::allocate(account_info.key, space as u64),
+        &[account_info.clone(), system_program_info.clone()],
+        &[signer_seeds],
+    )?;
+    solana_program::program::invoke_signed(
+        &system_instruction::assign(account_info.key, owner),
+        &[account_info.clone(), system_program_info.clone()],
+        &[signer_seeds],
+    )?;
+    Ok(())
+}
+
+/// Checks that `token_info` is a token account held by `owner` for `mint`
+fn validate_token_account(
+    token_info: &AccountInfo,
+    token_program_info: &AccountInfo,
+    owner: &Pubkey,
+    mint: &Pubkey,
+) -> ProgramResult {
+    if token_info.owner != token_program_info.key {
+        msg!("Invalid Token Account (system account not owned by Token Program)");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let token_account: Account = Account::unpack_unchecked(&token_info.data.borrow())?;
+    if token_account.owner != *owner {
+        msg!("Invalid Token Account (\"User space\" owner mismatch)");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if token_account.mint != *mint {
+        msg!("Invalid Mint");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+/// Checks that `token_info` belongs to `owner` and lets `delegate` move at least `amount` out of it
+fn validate_allowance(
+    token_info: &AccountInfo,
+    token_program_info: &AccountInfo,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    delegate: &Pubkey,
+    amount: u64,
+) -> ProgramResult {
+    validate_token_account(token_info, token_program_info, owner, mint)?;
+    let token_account: Account = Account::unpack(&token_info.data.borrow())?;
+    if token_account.delegate != COption::Some(*delegate) {
+        msg!("Escrow is not the delegate (allowance revoked?)");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if token_account.delegated_amount < amount {
+        msg!("Insufficient allowance");
+        return Err(ProgramError::InsufficientFunds);
+    }
+    if token_account.amount < amount {
+        msg!("Insufficient balance");
+        return Err(ProgramError::InsufficientFunds);
+    }
+    Ok(())
+}
+
+/// Moves `amount` out of a vault owned by (or delegated to) the PDA `authority_info`, signing with `authority_seeds`
+fn transfer_from_vault<'a>(
+    token_program_info: &AccountInfo<'a>,
+    vault_info: &AccountInfo<'a>,
+    destination_info: &AccountInfo<'a>,
+    authority_info: &AccountInfo<'a>,
+    amount: u64,
+    authority_seeds: &[&[u8]],
+) -> ProgramResult {
+    solana_program::program::invoke_signed(
+        &transfer(
+            token_program_info.key,
+            vault_info.key,
+            destination_info.key,
+            authority_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            vault_info.clone(),
+            authority_info.clone(),
+            destination_info.clone(),
+            token_program_info.clone(),
+        ],
+        &[authority_seeds],
+    )
+}
+
+/// Moves `amount` out of a token account whose owner signed the transaction
+fn transfer_tokens<'a>(
+    token_program_info: &AccountInfo<'a>,
+    source_info: &AccountInfo<'a>,
+    destination_info: &AccountInfo<'a>,
+    owner_info: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    solana_program::program::invoke(
+        &transfer(
+            token_program_info.key,
+            source_info.key,
+            destination_info.key,
+            owner_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            source_info.clone(),
+            owner_info.clone(),
+            destination_info.clone(),
+            token_program_info.clone(),
+        ],
+    )
 }
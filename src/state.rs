@@ -1,5 +1,15 @@
 use borsh::{BorshDeserialize, BorshSerialize};
-use solana_program::pubkey::Pubkey;
+use solana_program::{keccak, pubkey::Pubkey};
+use std::convert::TryFrom;
+
+/// Maximum number of approvers that can be attached to a single escrow
+pub const MAX_APPROVERS: usize = 8;
+/// Maximum number of milestones a one-sided payment escrow can be split into
+pub const MAX_MILESTONES: usize = 8;
+/// Maximum number of recipients a withdrawal can be split between
+pub const MAX_PAYOUTS: usize = 4;
+/// Maximum number of participants in a ring swap
+pub const MAX_RING_LEGS: usize = 8;
 
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug)]
 pub enum EscrowState {
@@ -10,9 +20,224 @@ pub enum EscrowState {
     Committed,
     WithdrawAlice,
     WithdrawBob,
+    Funded,
+    Rented,
+    Settling,
+    Proposed,
+    Completed,
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
+impl Default for EscrowState {
+    fn default() -> Self {
+        EscrowState::Uninitialized
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug)]
+pub enum OptionState {
+    Uninitialized,
+    Written,
+    Held,
+    Exercised,
+    Reclaimed,
+}
+
+/// Share of a withdrawal paid to `recipient`, in basis points of the withdrawn amount
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct Payout {
+    pub recipient: Pubkey,
+    pub bps: u16,
+}
+
+impl Payout {
+    pub const LEN: usize = 32 // recipient
+    + 2 // bps
+    ;
+
+    /// Rounded down share of `amount`, the dust is left to the first recipient
+    pub fn share_of(&self, amount: u64) -> u64 {
+        (amount as u128 * self.bps as u128 / 10_000) as u64
+    }
+}
+
+/// Account layout of the price feeds oracle-gated escrows read
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct PriceFeed {
+    pub price: i64,
+    pub confidence: u64, // width of the confidence interval around `price`
+    pub publish_time: i64,
+}
+
+impl PriceFeed {
+    pub const LEN: usize = 8 // price
+    + 8 // confidence
+    + 8 // publish_time
+    ;
+}
+
+/// Price condition an oracle-gated escrow has to meet before it settles
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct OracleCondition {
+    pub pubkey_oracle: Pubkey,
+    pub price: i64,
+    pub above: bool, // settle only at or above `price`, at or below otherwise
+    pub max_staleness: i64, // seconds
+    pub max_confidence: u64,
+}
+
+impl OracleCondition {
+    pub const LEN: usize = 32 // pubkey_oracle
+    + 8 // price
+    + 1 // above
+    + 8 // max_staleness
+    + 8 // max_confidence
+    ;
+
+    /// True if `feed` is fresh and precise enough at `now` and its price is on the right side of the bound
+    pub fn is_met(&self, feed: &PriceFeed, now: i64) -> bool {
+        let in_range = if self.above {
+            feed.price >= self.price
+        } else {
+            feed.price <= self.price
+        };
+        in_range
+            && now.saturating_sub(feed.publish_time) <= self.max_staleness
+            && feed.confidence <= self.max_confidence
+    }
+}
+
+/// Linear release schedule of a vesting escrow, nothing vests before `cliff_ts`
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct VestingSchedule {
+    pub start_ts: i64,
+    pub cliff_ts: i64,
+    pub end_ts: i64,
+}
+
+impl VestingSchedule {
+    pub const LEN: usize = 8 + 8 + 8;
+
+    /// An `end_ts` of 0 is rejected as it marks escrows without vesting
+    pub fn is_valid(&self) -> bool {
+        self.start_ts <= self.cliff_ts
+            && self.cliff_ts <= self.end_ts
+            && self.start_ts < self.end_ts
+            && self.end_ts != 0
+    }
+
+    /// Portion of `total` that has vested at `now`
+    pub fn vested_amount(&self, total: u64, now: i64) -> u64 {
+        if now < self.cliff_ts {
+            0
+        } else if now >= self.end_ts {
+            total
+        } else {
+            let elapsed = (now - self.start_ts) as u128;
+            let duration = (self.end_ts - self.start_ts) as u128;
+            (total as u128 * elapsed / duration) as u64
+        }
+    }
+}
+
+/// Terms of a collateralized loan, alice borrows `size_y` of Y against `size_x` of X
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct LoanTerms {
+    pub interest_bps: u16,
+    pub due_ts: i64,
+}
+
+impl LoanTerms {
+    pub const LEN: usize = 2 + 8;
+
+    /// Principal plus interest owed to the lender
+    pub fn repayment_amount(&self, principal: u64) -> Option<u64> {
+        let interest = principal as u128 * self.interest_bps as u128 / 10_000;
+        principal.checked_add(u64::try_from(interest).ok()?)
+    }
+}
+
+/// Terms of an NFT rental, bob posts `size_y` of Y as collateral and pays `fee` of Y to borrow the NFT for `duration` seconds
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct RentalTerms {
+    pub fee: u64,
+    pub duration: i64,
+}
+
+impl RentalTerms {
+    pub const LEN: usize = 8 + 8;
+}
+
+/// Terms of a payment channel, a submitted balance can be replaced by a newer one for `challenge_period` seconds
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct ChannelTerms {
+    pub challenge_period: i64,
+}
+
+impl ChannelTerms {
+    pub const LEN: usize = 8;
+}
+
+/// SOL bond each party posts into the escrow account, forfeited to the counterparty
+/// by a party whose leg is not funded by `deposit_deadline`
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct BondTerms {
+    pub amount: u64, // lamports
+    pub deposit_deadline: i64,
+}
+
+impl BondTerms {
+    pub const LEN: usize = 8 + 8;
+}
+
+/// Escrow a chained escrow waits on, with the pass its PDA is derived from
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct Prerequisite {
+    pub pubkey_escrow: Pubkey,
+    pub pass: [u8; 32],
+}
+
+impl Prerequisite {
+    pub const LEN: usize = 32 + 32;
+}
+
+/// Optional terms of a new escrow, all unset for a plain swap
+#[derive(BorshSerialize, BorshDeserialize, Clone, Default, PartialEq, Debug)]
+pub struct EscrowTerms {
+    pub approvers: Vec<Pubkey>, // up to `MAX_APPROVERS`, empty when no approval is required and for loans, rentals and channels
+    pub threshold: u8,
+    pub vesting: Option<VestingSchedule>, // turns the escrow into a grant of `amount_x` vesting to bob, `amount_y` must be 0
+    pub loan: Option<LoanTerms>, // turns the escrow into a loan of `amount_y` from bob against `amount_x` of collateral from alice
+    pub rental: Option<RentalTerms>, // turns the escrow into a rental of alice's NFT, `amount_x` must be 1 of a 0 decimals mint
+    pub channel: Option<ChannelTerms>, // turns the escrow into a payment channel settled from signed balances
+    pub settle_after: i64, // turns the escrow into a forward that settles after this timestamp, 0 to settle at once
+    pub prerequisite: Option<Prerequisite>, // initialized escrow that must be completed before this one pays out, refunds are never gated on it
+    pub oracle: Option<OracleCondition>, // price condition read from a `PriceFeed` account before settling
+    pub bond: Option<BondTerms>, // SOL bond posted by each party, the signers post theirs here
+}
+
+/// Off-chain balance of a payment channel, signed by both parties. Whatever alice is not owed goes to bob
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct ChannelBalance {
+    pub nonce: u64,
+    pub alice_x: u64,
+    pub alice_y: u64,
+}
+
+impl ChannelBalance {
+    pub const LEN: usize = 8 + 8 + 8;
+
+    /// The message both parties sign with ed25519 for the channel held by `escrow`
+    pub fn message(&self, escrow: &Pubkey) -> Vec<u8> {
+        let mut message = b"escrow_channel".to_vec();
+        message.extend_from_slice(escrow.as_ref());
+        message.extend_from_slice(&self.nonce.to_le_bytes());
+        message.extend_from_slice(&self.alice_x.to_le_bytes());
+        message.extend_from_slice(&self.alice_y.to_le_bytes());
+        message
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Default, Debug)]
 pub struct EscrowData {
     pub size_x: u64,
     pub size_y: u64,
@@ -24,6 +249,36 @@ pub struct EscrowData {
     pub escrow_bump: u8,
     pub vault_x_bump: u8,
     pub vault_y_bump: u8,
+    pub approvers: [Pubkey; MAX_APPROVERS],
+    pub approver_count: u8,
+    pub approval_threshold: u8,
+    pub approvals: u8, // bitmask over `approvers`
+    pub vesting: VestingSchedule, // all zero unless this is a vesting escrow
+    pub claimed: u64,
+    pub loan: LoanTerms, // all zero unless this is a loan escrow
+    pub rental: RentalTerms, // all zero unless this is a rental escrow
+    pub rental_due_ts: i64,
+    pub channel: ChannelTerms, // all zero unless this is a payment channel
+    pub channel_balance: ChannelBalance,
+    pub channel_closes_at: i64,
+    pub delegated: bool, // funds stay in the parties' own token accounts, delegated to the escrow
+    pub settle_after: i64, // 0 unless this is a forward
+    pub proposal_x: u64,
+    pub proposal_y: u64,
+    pub pubkey_proposer: Pubkey, // default unless a counter-offer is pending
+    pub pubkey_initiator: Pubkey, // whichever of alice and bob opened the escrow
+    pub deposited_x: u64, // funded so far by alice, the state only advances once it reaches `size_x`
+    pub deposited_y: u64, // funded so far by bob, the state only advances once it reaches `size_y`
+    pub pubkey_prerequisite: Pubkey, // escrow that must be completed before this one pays out, default if none
+    pub prerequisite_pass: [u8; 32], // pass of the prerequisite, its PDA is re-derived whenever it is read
+    pub oracle: OracleCondition, // all zero unless settlement depends on a price feed
+    pub bond: BondTerms, // all zero unless the parties post bonds
+    pub bond_posted_alice: bool,
+    pub bond_posted_bob: bool,
+    pub payouts_alice: [Payout; MAX_PAYOUTS], // split of what alice withdraws, all to alice when empty
+    pub payout_count_alice: u8,
+    pub payouts_bob: [Payout; MAX_PAYOUTS], // split of what bob withdraws, all to bob when empty
+    pub payout_count_bob: u8,
 }
 
 impl EscrowData {
@@ -37,5 +292,603 @@ impl EscrowData {
     + 1 // escrow_bump
     + 1 // vault_x_bump
     + 1 // vault_y_bump
+    + 32 * MAX_APPROVERS // approvers
+    + 1 // approver_count
+    + 1 // approval_threshold
+    + 1 // approvals
+    + VestingSchedule::LEN // vesting
+    + 8 // claimed
+    + LoanTerms::LEN // loan
+    + RentalTerms::LEN // rental
+    + 8 // rental_due_ts
+    + ChannelTerms::LEN // channel
+    + ChannelBalance::LEN // channel_balance
+    + 8 // channel_closes_at
+    + 1 // delegated
+    + 8 // settle_after
+    + 8 // proposal_x
+    + 8 // proposal_y
+    + 32 // pubkey_proposer
+    + 32 // pubkey_initiator
+    + 8 // deposited_x
+    + 8 // deposited_y
+    + 32 // pubkey_prerequisite
+    + 32 // prerequisite_pass
+    + OracleCondition::LEN // oracle
+    + BondTerms::LEN // bond
+    + 1 // bond_posted_alice
+    + 1 // bond_posted_bob
+    + Payout::LEN * MAX_PAYOUTS // payouts_alice
+    + 1 // payout_count_alice
+    + Payout::LEN * MAX_PAYOUTS // payouts_bob
+    + 1 // payout_count_bob
+    ;
+
+    /// Vesting escrows only have an X leg, which bob claims as it vests
+    pub fn is_vesting(&self) -> bool {
+        self.vesting.end_ts != 0
+    }
+
+    /// Loan escrows lock alice's collateral in vault x while bob's principal goes straight to alice
+    pub fn is_loan(&self) -> bool {
+        self.loan.due_ts != 0
+    }
+
+    /// Rental escrows lend alice's NFT to bob against collateral held in vault y
+    pub fn is_rental(&self) -> bool {
+        self.rental.duration != 0
+    }
+
+    /// Payment channels split both vaults according to the latest balance signed by alice and bob
+    pub fn is_channel(&self) -> bool {
+        self.channel.challenge_period != 0
+    }
+
+    /// Forwards can only settle, or be refunded one-sidedly, once `settle_after` has passed
+    pub fn is_forward(&self) -> bool {
+        self.settle_after != 0
+    }
+
+    /// Amounts of vesting, loan, rental and channel escrows are bound to their terms
+    pub fn is_negotiable(&self) -> bool {
+        !(self.is_vesting() || self.is_loan() || self.is_rental() || self.is_channel())
+    }
+
+    /// Neither leg has received a deposit yet, so the amounts can still change
+    pub fn is_unfunded(&self) -> bool {
+        self.deposited_x == 0 && self.deposited_y == 0
+    }
+
+    /// Chained escrows only pay out once their prerequisite escrow is completed
+    pub fn is_chained(&self) -> bool {
+        self.pubkey_prerequisite != Pubkey::default()
+    }
+
+    /// Oracle-gated escrows only settle while their price condition holds
+    pub fn is_oracle_gated(&self) -> bool {
+        self.oracle.pubkey_oracle != Pubkey::default()
+    }
+
+    /// Bonded escrows hold a SOL bond of each party on top of the rent of the escrow account
+    pub fn is_bonded(&self) -> bool {
+        self.bond.amount != 0
+    }
+
+    /// Clears the bond `key` has posted, returning the lamports to release
+    pub fn take_bond(&mut self, key: &Pubkey) -> u64 {
+        let posted = if *key == self.pubkey_alice {
+            &mut self.bond_posted_alice
+        } else if *key == self.pubkey_bob {
+            &mut self.bond_posted_bob
+        } else {
+            return 0;
+        };
+        if !*posted {
+            return 0;
+        }
+        *posted = false;
+        self.bond.amount
+    }
+
+    /// Payout split configured by `key`, empty if it is neither alice nor bob or kept the default
+    pub fn payouts_of(&self, key: &Pubkey) -> &[Payout] {
+        if *key == self.pubkey_alice {
+            &self.payouts_alice[..self.payout_count_alice as usize]
+        } else if *key == self.pubkey_bob {
+            &self.payouts_bob[..self.payout_count_bob as usize]
+        } else {
+            &[]
+        }
+    }
+
+    /// Index of `key` in the approver list, if it is one of the approvers
+    pub fn approver_index(&self, key: &Pubkey) -> Option<usize> {
+        self.approvers[..self.approver_count as usize]
+            .iter()
+            .position(|approver| approver == key)
+    }
+
+    /// True once at least `approval_threshold` approvers have signed off
+    pub fn is_approved(&self) -> bool {
+        self.approvals.count_ones() >= self.approval_threshold as u32
+    }
+}
+
+/// One-sided payment escrow: the payer funds the vault up front and releases it to the payee milestone by milestone
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct MilestoneEscrowData {
+    pub is_initialized: bool,
+    pub pubkey_payer: Pubkey,
+    pub pubkey_payee: Pubkey,
+    pub pubkey_arbiter: Pubkey,
+    pub pubkey_mint: Pubkey,
+    pub amounts: [u64; MAX_MILESTONES],
+    pub milestone_count: u8,
+    pub settled: u8, // bitmask over `amounts`, set once a milestone is released or refunded
+    pub refund_after: i64,
+    pub escrow_bump: u8,
+    pub vault_bump: u8,
+}
+
+impl MilestoneEscrowData {
+    pub const LEN: usize = 1 // is_initialized
+    + 32 // pubkey_payer
+    + 32 // pubkey_payee
+    + 32 // pubkey_arbiter
+    + 32 // pubkey_mint
+    + 8 * MAX_MILESTONES // amounts
+    + 1 // milestone_count
+    + 1 // settled
+    + 8 // refund_after
+    + 1 // escrow_bump
+    + 1 // vault_bump
+    ;
+
+    /// Sum of the milestones that have been neither released nor refunded
+    pub fn unsettled_amount(&self) -> u64 {
+        self.amounts[..self.milestone_count as usize]
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| self.settled & (1 << i) == 0)
+            .map(|(_, amount)| amount)
+            .sum()
+    }
+}
+
+/// Per-second payment stream, the recipient can withdraw whatever has streamed so far
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct StreamData {
+    pub is_initialized: bool,
+    pub is_cancelled: bool,
+    pub pubkey_payer: Pubkey,
+    pub pubkey_recipient: Pubkey,
+    pub pubkey_mint: Pubkey,
+    pub rate_per_second: u64,
+    pub deposited: u64,
+    pub withdrawn: u64,
+    pub checkpoint_ts: i64,
+    pub checkpoint_streamed: u64, // amount streamed up to `checkpoint_ts`
+    pub escrow_bump: u8,
+    pub vault_bump: u8,
+}
+
+impl StreamData {
+    pub const LEN: usize = 1 // is_initialized
+    + 1 // is_cancelled
+    + 32 // pubkey_payer
+    + 32 // pubkey_recipient
+    + 32 // pubkey_mint
+    + 8 // rate_per_second
+    + 8 // deposited
+    + 8 // withdrawn
+    + 8 // checkpoint_ts
+    + 8 // checkpoint_streamed
+    + 1 // escrow_bump
+    + 1 // vault_bump
+    ;
+
+    /// Total amount streamed to the recipient at `now`, never more than what was deposited
+    pub fn streamed_amount(&self, now: i64) -> u64 {
+        if self.is_cancelled {
+            return self.checkpoint_streamed;
+        }
+        let elapsed = now.saturating_sub(self.checkpoint_ts).max(0) as u64;
+        self.rate_per_second
+            .saturating_mul(elapsed)
+            .saturating_add(self.checkpoint_streamed)
+            .min(self.deposited)
+    }
+}
+
+/// All-or-nothing crowdfunding campaign, contributions sit in the vault until the deadline
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct CampaignData {
+    pub is_initialized: bool,
+    pub is_withdrawn: bool,
+    pub pubkey_creator: Pubkey,
+    pub pubkey_mint: Pubkey,
+    pub goal: u64,
+    pub deadline: i64,
+    pub raised: u64,
+    pub escrow_bump: u8,
+    pub vault_bump: u8,
+}
+
+impl CampaignData {
+    pub const LEN: usize = 1 // is_initialized
+    + 1 // is_withdrawn
+    + 32 // pubkey_creator
+    + 32 // pubkey_mint
+    + 8 // goal
+    + 8 // deadline
+    + 8 // raised
+    + 1 // escrow_bump
+    + 1 // vault_bump
+    ;
+}
+
+/// Tracks how much a single contributor put into a campaign, PDA of `["receipt", campaign, contributor]`
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct ContributionReceipt {
+    pub is_initialized: bool,
+    pub pubkey_campaign: Pubkey,
+    pub pubkey_contributor: Pubkey,
+    pub amount: u64,
+    pub bump: u8,
+}
+
+impl ContributionReceipt {
+    pub const LEN: usize = 1 // is_initialized
+    + 32 // pubkey_campaign
+    + 32 // pubkey_contributor
+    + 8 // amount
+    + 1 // bump
+    ;
+}
+
+/// Terms of a covered call, exercisable from `exercise_start` until `expiry`
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct OptionTerms {
+    pub amount_x: u64,
+    pub premium: u64,
+    pub strike: u64,
+    pub exercise_start: i64,
+    pub expiry: i64,
+}
+
+impl OptionTerms {
+    pub const LEN: usize = 8 + 8 + 8 + 8 + 8;
+}
+
+/// Covered call: the writer locks `amount_x` of X, the holder may buy it for `strike` of Y during the exercise window
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct OptionData {
+    pub state: OptionState,
+    pub pubkey_writer: Pubkey,
+    pub pubkey_holder: Pubkey,
+    pub pubkey_mint_x: Pubkey,
+    pub pubkey_mint_y: Pubkey,
+    pub amount_x: u64,
+    pub premium: u64,
+    pub strike: u64,
+    pub exercise_start: i64,
+    pub expiry: i64,
+    pub escrow_bump: u8,
+    pub vault_bump: u8,
+}
+
+impl OptionData {
+    pub const LEN: usize = 1 // state
+    + 32 // pubkey_writer
+    + 32 // pubkey_holder
+    + 32 // pubkey_mint_x
+    + 32 // pubkey_mint_y
+    + 8 // amount_x
+    + 8 // premium
+    + 8 // strike
+    + 8 // exercise_start
+    + 8 // expiry
+    + 1 // escrow_bump
+    + 1 // vault_bump
+    ;
+}
+
+/// Swap terms signed off-chain by the maker, taken on-chain in a single `TakeOffer`
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Offer {
+    pub pubkey_maker: Pubkey,
+    pub pubkey_mint_x: Pubkey,
+    pub pubkey_mint_y: Pubkey,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub expiry: i64,
+    pub nonce: u64,
+}
+
+impl Offer {
+    /// The message the maker signs with ed25519
+    pub fn message(&self) -> Vec<u8> {
+        let mut message = b"escrow_offer".to_vec();
+        message.extend_from_slice(self.pubkey_maker.as_ref());
+        message.extend_from_slice(self.pubkey_mint_x.as_ref());
+        message.extend_from_slice(self.pubkey_mint_y.as_ref());
+        message.extend_from_slice(&self.amount_x.to_le_bytes());
+        message.extend_from_slice(&self.amount_y.to_le_bytes());
+        message.extend_from_slice(&self.expiry.to_le_bytes());
+        message.extend_from_slice(&self.nonce.to_le_bytes());
+        message
+    }
+}
+
+/// English auction of `amount_x` of X, the top bid in Y is held in vault y until settlement
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct AuctionData {
+    pub is_initialized: bool,
+    pub is_settled: bool,
+    pub pubkey_seller: Pubkey,
+    pub pubkey_mint_x: Pubkey,
+    pub pubkey_mint_y: Pubkey,
+    pub amount_x: u64,
+    pub reserve_price: u64,
+    pub min_increment: u64,
+    pub end_ts: i64,
+    pub pubkey_top_bidder: Pubkey,
+    pub top_bid: u64, // 0 until the first bid
+    pub escrow_bump: u8,
+    pub vault_x_bump: u8,
+    pub vault_y_bump: u8,
+}
+
+impl AuctionData {
+    pub const LEN: usize = 1 // is_initialized
+    + 1 // is_settled
+    + 32 // pubkey_seller
+    + 32 // pubkey_mint_x
+    + 32 // pubkey_mint_y
+    + 8 // amount_x
+    + 8 // reserve_price
+    + 8 // min_increment
+    + 8 // end_ts
+    + 32 // pubkey_top_bidder
+    + 8 // top_bid
+    + 1 // escrow_bump
+    + 1 // vault_x_bump
+    + 1 // vault_y_bump
     ;
+
+    /// Smallest bid that can replace the current top bid
+    pub fn min_bid(&self) -> u64 {
+        if self.top_bid == 0 {
+            self.reserve_price.max(1)
+        } else {
+            self.top_bid.saturating_add(self.min_increment.max(1))
+        }
+    }
+}
+
+/// Terms of a Dutch auction, the price falls from `start_price` at `start_slot` to `end_price` at `end_slot`
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct DutchAuctionTerms {
+    pub amount_x: u64,
+    pub start_price: u64,
+    pub end_price: u64,
+    pub start_slot: u64,
+    pub end_slot: u64,
+}
+
+impl DutchAuctionTerms {
+    pub const LEN: usize = 8 + 8 + 8 + 8 + 8;
+}
+
+/// Dutch auction of `amount_x` of X whose price in Y falls linearly from `start_slot` to `end_slot`
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct DutchAuctionData {
+    pub is_initialized: bool,
+    pub is_closed: bool, // sold or cancelled
+    pub pubkey_seller: Pubkey,
+    pub pubkey_mint_x: Pubkey,
+    pub pubkey_mint_y: Pubkey,
+    pub amount_x: u64,
+    pub start_price: u64,
+    pub end_price: u64,
+    pub start_slot: u64,
+    pub end_slot: u64,
+    pub escrow_bump: u8,
+    pub vault_bump: u8,
+}
+
+impl DutchAuctionData {
+    pub const LEN: usize = 1 // is_initialized
+    + 1 // is_closed
+    + 32 // pubkey_seller
+    + 32 // pubkey_mint_x
+    + 32 // pubkey_mint_y
+    + 8 // amount_x
+    + 8 // start_price
+    + 8 // end_price
+    + 8 // start_slot
+    + 8 // end_slot
+    + 1 // escrow_bump
+    + 1 // vault_bump
+    ;
+
+    /// Amount of Y required to buy at `slot`
+    pub fn price_at(&self, slot: u64) -> u64 {
+        dutch_price(
+            self.start_price,
+            self.end_price,
+            self.start_slot,
+            self.end_slot,
+            slot,
+        )
+    }
+}
+
+/// Linear price decay from `start_price` at `start_slot` to `end_price` at `end_slot`,
+/// flat outside that range. Defined for any input: a higher `end_price` makes the price rise
+/// linearly instead, and `start_slot >= end_slot` jumps straight to `end_price` after `start_slot`
+pub fn dutch_price(start_price: u64, end_price: u64, start_slot: u64, end_slot: u64, slot: u64) -> u64 {
+    if slot <= start_slot {
+        return start_price;
+    }
+    if slot >= end_slot {
+        return end_price;
+    }
+    let elapsed = (slot - start_slot) as u128;
+    let duration = (end_slot - start_slot) as u128;
+    // the change is bounded by the gap between both prices, so the result stays between them
+    if start_price >= end_price {
+        start_price - ((start_price - end_price) as u128 * elapsed / duration) as u64
+    } else {
+        start_price + ((end_price - start_price) as u128 * elapsed / duration) as u64
+    }
+}
+
+/// Merkle distribution: the depositor funds the vault and recipients claim their leaf of `merkle_root`
+/// until `expiry`, after which the depositor reclaims the rest. Claimed leaves are tracked in a bitmap account
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct DistributorData {
+    pub is_initialized: bool,
+    pub is_reclaimed: bool,
+    pub pubkey_depositor: Pubkey,
+    pub pubkey_mint: Pubkey,
+    pub merkle_root: [u8; 32],
+    pub leaf_count: u32,
+    pub total: u64,
+    pub claimed: u64,
+    pub expiry: i64,
+    pub escrow_bump: u8,
+    pub vault_bump: u8,
+    pub bitmap_bump: u8,
+}
+
+impl DistributorData {
+    pub const LEN: usize = 1 // is_initialized
+    + 1 // is_reclaimed
+    + 32 // pubkey_depositor
+    + 32 // pubkey_mint
+    + 32 // merkle_root
+    + 4 // leaf_count
+    + 8 // total
+    + 8 // claimed
+    + 8 // expiry
+    + 1 // escrow_bump
+    + 1 // vault_bump
+    + 1 // bitmap_bump
+    ;
+
+    /// Size of the claim bitmap, one bit per leaf
+    pub fn bitmap_len(&self) -> usize {
+        (self.leaf_count as usize + 7) / 8
+    }
+
+    /// Leaf hash of `amount` owed to `recipient` at position `index`
+    pub fn leaf(index: u32, recipient: &Pubkey, amount: u64) -> [u8; 32] {
+        keccak::hashv(&[
+            &index.to_le_bytes(),
+            recipient.as_ref(),
+            &amount.to_le_bytes(),
+        ])
+        .0
+    }
+
+    /// True if `proof` links `leaf` to `merkle_root`, each pair of nodes being hashed in sorted order
+    pub fn verify_proof(&self, proof: &[[u8; 32]], leaf: [u8; 32]) -> bool {
+        let node = proof.iter().fold(leaf, |node, sibling| {
+            if node <= *sibling {
+                keccak::hashv(&[&node, sibling]).0
+            } else {
+                keccak::hashv(&[sibling, &node]).0
+            }
+        });
+        node == self.merkle_root
+    }
+}
+
+/// One participant of a ring swap: deposits `amount` of `mint` and is owed the deposit of leg `owed_from`
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct RingLeg {
+    pub participant: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub owed_from: u8,
+    pub deposited: bool,
+    pub withdrawn: bool, // set once the participant took the deposit of `owed_from`
+    pub vault_bump: u8, // vault created with the first deposit, PDA of `["ring_vault", ring, index]`
+}
+
+impl RingLeg {
+    pub const LEN: usize = 32 // participant
+    + 32 // mint
+    + 8 // amount
+    + 1 // owed_from
+    + 1 // deposited
+    + 1 // withdrawn
+    + 1 // vault_bump
+    ;
+}
+
+/// N-party barter ring, nothing is released until every participant has deposited
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct RingData {
+    pub is_initialized: bool,
+    pub pubkey_creator: Pubkey,
+    pub legs: [RingLeg; MAX_RING_LEGS],
+    pub leg_count: u8,
+    pub escrow_bump: u8,
+}
+
+impl RingData {
+    pub const LEN: usize = 1 // is_initialized
+    + 32 // pubkey_creator
+    + RingLeg::LEN * MAX_RING_LEGS // legs
+    + 1 // leg_count
+    + 1 // escrow_bump
+    ;
+
+    pub fn legs(&self) -> &[RingLeg] {
+        &self.legs[..self.leg_count as usize]
+    }
+
+    /// True once every participant has deposited, deposits can no longer be refunded from then on
+    pub fn is_complete(&self) -> bool {
+        self.legs().iter().all(|leg| leg.deposited)
+    }
+}
+
+/// Proof-of-delivery escrow: the buyer's payment goes to the seller once the attestor signs off on the delivery,
+/// or back to the buyer after the deadline
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct DeliveryData {
+    pub is_initialized: bool,
+    pub is_settled: bool,
+    pub pubkey_buyer: Pubkey,
+    pub pubkey_seller: Pubkey,
+    pub pubkey_attestor: Pubkey,
+    pub pubkey_mint: Pubkey,
+    pub amount: u64,
+    pub deadline: i64,
+    pub escrow_bump: u8,
+    pub vault_bump: u8,
+}
+
+impl DeliveryData {
+    pub const LEN: usize = 1 // is_initialized
+    + 1 // is_settled
+    + 32 // pubkey_buyer
+    + 32 // pubkey_seller
+    + 32 // pubkey_attestor
+    + 32 // pubkey_mint
+    + 8 // amount
+    + 8 // deadline
+    + 1 // escrow_bump
+    + 1 // vault_bump
+    ;
+
+    /// The message the attestor signs with ed25519 once the goods of `delivery` have arrived
+    pub fn attestation_message(delivery: &Pubkey) -> Vec<u8> {
+        let mut message = b"escrow_delivery".to_vec();
+        message.extend_from_slice(delivery.as_ref());
+        message
+    }
 }
\ No newline at end of file
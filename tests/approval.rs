@@ -0,0 +1,117 @@
+mod common;
+
+use common::*;
+use escrow::{
+    instruction::EscrowInstruction,
+    state::{EscrowData, EscrowState, EscrowTerms},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const SIZE_X: u64 = 1_000;
+const SIZE_Y: u64 = 2_000;
+
+fn approve(swap: &Swap, approver: &Keypair) -> Instruction {
+    instruction(
+        swap.program_id,
+        EscrowInstruction::Approve { pass: PASS },
+        vec![
+            AccountMeta::new(swap.escrow, false),
+            AccountMeta::new_readonly(approver.pubkey(), true),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_release_waits_for_threshold() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let approvers = [Keypair::new(), Keypair::new(), Keypair::new()];
+    let outsider = Keypair::new();
+    let mut context = Context::start(program_test).await;
+    let parties = [&swap.alice, &swap.bob];
+
+    let terms = EscrowTerms {
+        approvers: approvers.iter().map(|approver| approver.pubkey()).collect(),
+        threshold: 2,
+        ..EscrowTerms::default()
+    };
+    let init = swap.init(&context.payer(), SIZE_X, SIZE_Y, terms);
+    assert!(context.send(&[init], &parties).await);
+    let alice_deposit = swap.deposit(&swap.alice, SIZE_X);
+    let bob_deposit = swap.deposit(&swap.bob, SIZE_Y);
+    assert!(context.send(&[alice_deposit, bob_deposit], &parties).await);
+
+    assert!(!context.send(&[swap.withdraw_alice()], &[&swap.alice]).await);
+
+    // approving twice does not count twice
+    let first = approve(&swap, &approvers[0]);
+    assert!(
+        context
+            .send(&[first.clone(), first], &[&approvers[0]])
+            .await
+    );
+    assert!(!context.send(&[swap.withdraw_alice()], &[&swap.alice]).await);
+
+    let unknown = approve(&swap, &outsider);
+    assert!(!context.send(&[unknown], &[&outsider]).await);
+
+    let second = approve(&swap, &approvers[2]);
+    assert!(context.send(&[second], &[&approvers[2]]).await);
+    assert!(context.send(&[swap.withdraw_alice()], &[&swap.alice]).await);
+    assert!(context.send(&[swap.withdraw_bob()], &[&swap.bob]).await);
+
+    assert_eq!(context.balance(swap.alice_y).await, FUNDS + SIZE_Y);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS + SIZE_X);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Completed);
+    assert_eq!(escrow_data.approvals, 0b101);
+}
+
+#[tokio::test]
+async fn test_refund_is_not_gated_on_approvals() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let mut escrow_data = swap.escrow_data(SIZE_X, SIZE_Y);
+    escrow_data.state = EscrowState::DepositAlice;
+    escrow_data.deposited_x = SIZE_X;
+    escrow_data.approvers[0] = Pubkey::new_unique();
+    escrow_data.approver_count = 1;
+    escrow_data.approval_threshold = 1;
+    swap.add_escrow(&mut program_test, escrow_data);
+    let mut context = Context::start(program_test).await;
+
+    let refund = swap.withdrawal(&swap.alice, swap.alice_x, swap.vault_x);
+    assert!(context.send(&[refund], &[&swap.alice]).await);
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS + SIZE_X);
+}
+
+#[tokio::test]
+async fn test_init_rejects_invalid_thresholds() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let mut context = Context::start(program_test).await;
+
+    let approver = Pubkey::new_unique();
+    let invalid = vec![
+        (vec![approver], 0),
+        (vec![approver], 2),
+        (vec![approver, approver], 1),
+    ];
+    for (approvers, threshold) in invalid {
+        let terms = EscrowTerms {
+            approvers,
+            threshold,
+            ..EscrowTerms::default()
+        };
+        let init = swap.init(&context.payer(), SIZE_X, SIZE_Y, terms);
+        assert!(!context.send(&[init], &[&swap.alice, &swap.bob]).await);
+    }
+}
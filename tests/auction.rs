@@ -0,0 +1,268 @@
+mod common;
+
+use common::*;
+use escrow::{instruction::EscrowInstruction, state::AuctionData};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program, sysvar,
+};
+
+const AMOUNT_X: u64 = 1_000;
+const RESERVE: u64 = 500;
+const INCREMENT: u64 = 100;
+
+/// Alice auctions X, bob and carol bid in Y
+struct Setup {
+    swap: Swap,
+    carol: Keypair,
+    carol_x: Pubkey,
+    carol_y: Pubkey,
+    auction: Pubkey,
+    vault_x: Pubkey,
+    vault_y: Pubkey,
+}
+
+fn setup() -> (ProgramTest, Setup) {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let carol = Keypair::new();
+    let carol_x = Pubkey::new_unique();
+    let carol_y = Pubkey::new_unique();
+    program_test.add_account(carol_x, token_account(swap.mint_x, carol.pubkey(), 0));
+    program_test.add_account(carol_y, token_account(swap.mint_y, carol.pubkey(), FUNDS));
+    let auction = pda(
+        &program_id,
+        &[
+            b"auction",
+            swap.alice.pubkey().as_ref(),
+            swap.mint_x.as_ref(),
+            swap.mint_y.as_ref(),
+            PASS.as_ref(),
+        ],
+    );
+    let vault_x = pda(&program_id, &[b"vault_x", auction.as_ref()]);
+    let vault_y = pda(&program_id, &[b"vault_y", auction.as_ref()]);
+    (
+        program_test,
+        Setup {
+            swap,
+            carol,
+            carol_x,
+            carol_y,
+            auction,
+            vault_x,
+            vault_y,
+        },
+    )
+}
+
+/// An auction that ended with `top_bid` from bob, or without bids when 0
+fn add_ended_auction(program_test: &mut ProgramTest, setup: &Setup, top_bid: u64) {
+    let swap = &setup.swap;
+    let bump = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &swap.program_id).1;
+    let auction_data = AuctionData {
+        is_initialized: true,
+        is_settled: false,
+        pubkey_seller: swap.alice.pubkey(),
+        pubkey_mint_x: swap.mint_x,
+        pubkey_mint_y: swap.mint_y,
+        amount_x: AMOUNT_X,
+        reserve_price: RESERVE,
+        min_increment: INCREMENT,
+        end_ts: now() - 60,
+        pubkey_top_bidder: if top_bid > 0 {
+            swap.bob.pubkey()
+        } else {
+            Pubkey::default()
+        },
+        top_bid,
+        escrow_bump: bump(&[
+            b"auction",
+            swap.alice.pubkey().as_ref(),
+            swap.mint_x.as_ref(),
+            swap.mint_y.as_ref(),
+            PASS.as_ref(),
+        ]),
+        vault_x_bump: bump(&[b"vault_x", setup.auction.as_ref()]),
+        vault_y_bump: bump(&[b"vault_y", setup.auction.as_ref()]),
+    };
+    program_test.add_account(
+        setup.auction,
+        program_account(&auction_data, swap.program_id),
+    );
+    program_test.add_account(
+        setup.vault_x,
+        token_account(swap.mint_x, setup.auction, AMOUNT_X),
+    );
+    program_test.add_account(
+        setup.vault_y,
+        token_account(swap.mint_y, setup.auction, top_bid),
+    );
+}
+
+fn bid(
+    setup: &Setup,
+    bidder: &Keypair,
+    token: Pubkey,
+    previous: Pubkey,
+    amount: u64,
+) -> Instruction {
+    instruction(
+        setup.swap.program_id,
+        EscrowInstruction::Bid { amount, pass: PASS },
+        vec![
+            AccountMeta::new(setup.auction, false),
+            AccountMeta::new(setup.vault_y, false),
+            AccountMeta::new(token, false),
+            AccountMeta::new(previous, false),
+            AccountMeta::new_readonly(bidder.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+fn settle(setup: &Setup, winner_token: Pubkey) -> Instruction {
+    let swap = &setup.swap;
+    instruction(
+        swap.program_id,
+        EscrowInstruction::SettleAuction { pass: PASS },
+        vec![
+            AccountMeta::new(setup.auction, false),
+            AccountMeta::new(setup.vault_x, false),
+            AccountMeta::new(setup.vault_y, false),
+            AccountMeta::new(swap.alice_x, false),
+            AccountMeta::new(swap.alice_y, false),
+            AccountMeta::new(winner_token, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+#[test]
+fn test_min_bid() {
+    let mut auction_data = AuctionData {
+        is_initialized: true,
+        is_settled: false,
+        pubkey_seller: Pubkey::default(),
+        pubkey_mint_x: Pubkey::default(),
+        pubkey_mint_y: Pubkey::default(),
+        amount_x: AMOUNT_X,
+        reserve_price: 0,
+        min_increment: 0,
+        end_ts: 0,
+        pubkey_top_bidder: Pubkey::default(),
+        top_bid: 0,
+        escrow_bump: 0,
+        vault_x_bump: 0,
+        vault_y_bump: 0,
+    };
+    assert_eq!(auction_data.min_bid(), 1);
+    auction_data.top_bid = 10;
+    assert_eq!(auction_data.min_bid(), 11);
+
+    auction_data.reserve_price = RESERVE;
+    auction_data.min_increment = INCREMENT;
+    auction_data.top_bid = 0;
+    assert_eq!(auction_data.min_bid(), RESERVE);
+    auction_data.top_bid = RESERVE;
+    assert_eq!(auction_data.min_bid(), RESERVE + INCREMENT);
+    auction_data.top_bid = u64::MAX;
+    assert_eq!(auction_data.min_bid(), u64::MAX);
+}
+
+#[tokio::test]
+async fn test_bid_refunds_previous_bidder() {
+    let (program_test, setup) = setup();
+    let swap = &setup.swap;
+    let mut context = Context::start(program_test).await;
+
+    let init = instruction(
+        swap.program_id,
+        EscrowInstruction::InitAuction {
+            amount_x: AMOUNT_X,
+            reserve_price: RESERVE,
+            min_increment: INCREMENT,
+            end_ts: now() + 3_600,
+            pass: PASS,
+        },
+        vec![
+            AccountMeta::new(setup.auction, false),
+            AccountMeta::new_readonly(swap.mint_x, false),
+            AccountMeta::new_readonly(swap.mint_y, false),
+            AccountMeta::new(setup.vault_x, false),
+            AccountMeta::new(setup.vault_y, false),
+            AccountMeta::new(swap.alice.pubkey(), true),
+            AccountMeta::new(swap.alice_x, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    );
+    assert!(context.send(&[init], &[&swap.alice]).await);
+    assert_eq!(context.balance(setup.vault_x).await, AMOUNT_X);
+
+    let below_reserve = bid(&setup, &swap.bob, swap.bob_y, swap.bob_y, RESERVE - 1);
+    assert!(!context.send(&[below_reserve], &[&swap.bob]).await);
+    let first = bid(&setup, &swap.bob, swap.bob_y, swap.bob_y, RESERVE);
+    assert!(context.send(&[first], &[&swap.bob]).await);
+    assert_eq!(context.balance(setup.vault_y).await, RESERVE);
+
+    let (carol, carol_y) = (&setup.carol, setup.carol_y);
+    let outbid = RESERVE + INCREMENT;
+    let below_increment = bid(&setup, carol, carol_y, swap.bob_y, outbid - 1);
+    assert!(!context.send(&[below_increment], &[carol]).await);
+    let refund_to_carol = bid(&setup, carol, carol_y, carol_y, outbid);
+    assert!(!context.send(&[refund_to_carol], &[carol]).await);
+
+    let second = bid(&setup, carol, carol_y, swap.bob_y, outbid);
+    assert!(context.send(&[second], &[carol]).await);
+    assert_eq!(context.balance(swap.bob_y).await, FUNDS);
+    assert_eq!(context.balance(carol_y).await, FUNDS - outbid);
+    assert_eq!(context.balance(setup.vault_y).await, outbid);
+    let auction_data: AuctionData = context.read(setup.auction).await;
+    assert_eq!(auction_data.pubkey_top_bidder, carol.pubkey());
+
+    assert!(!context.send(&[settle(&setup, setup.carol_x)], &[]).await);
+}
+
+#[tokio::test]
+async fn test_settle_auction() {
+    let (mut program_test, setup) = setup();
+    let top_bid = 700;
+    add_ended_auction(&mut program_test, &setup, top_bid);
+    let swap = &setup.swap;
+    let mut context = Context::start(program_test).await;
+
+    let late = bid(&setup, &setup.carol, setup.carol_y, swap.bob_y, 2 * top_bid);
+    assert!(!context.send(&[late], &[&setup.carol]).await);
+    let to_carol = settle(&setup, setup.carol_x);
+    assert!(!context.send(&[to_carol], &[]).await);
+
+    assert!(context.send(&[settle(&setup, swap.bob_x)], &[]).await);
+    assert_eq!(context.balance(swap.alice_y).await, FUNDS + top_bid);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS + AMOUNT_X);
+    assert_eq!(context.balance(setup.vault_x).await, 0);
+    assert_eq!(context.balance(setup.vault_y).await, 0);
+
+    assert!(!context.send(&[settle(&setup, swap.bob_x)], &[]).await);
+}
+
+#[tokio::test]
+async fn test_settle_without_bids() {
+    let (mut program_test, setup) = setup();
+    add_ended_auction(&mut program_test, &setup, 0);
+    let swap = &setup.swap;
+    let mut context = Context::start(program_test).await;
+
+    assert!(context.send(&[settle(&setup, swap.bob_x)], &[]).await);
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS + AMOUNT_X);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS);
+    let auction_data: AuctionData = context.read(setup.auction).await;
+    assert!(auction_data.is_settled);
+}
@@ -0,0 +1,196 @@
+mod common;
+
+use common::*;
+use escrow::{
+    instruction::EscrowInstruction,
+    state::{BondTerms, EscrowData, EscrowState, EscrowTerms},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program, sysvar,
+};
+
+const SIZE_X: u64 = 1_000;
+const SIZE_Y: u64 = 2_000;
+const BOND: u64 = 10_000_000;
+
+fn bonded(deposit_deadline: i64) -> EscrowTerms {
+    EscrowTerms {
+        bond: Some(BondTerms {
+            amount: BOND,
+            deposit_deadline,
+        }),
+        ..EscrowTerms::default()
+    }
+}
+
+/// Bonded escrows read the clock on every deposit
+fn with_clock(mut instruction: Instruction) -> Instruction {
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(sysvar::clock::id(), false));
+    instruction
+}
+
+fn claim_bond(swap: &Swap, claimant: &Keypair) -> Instruction {
+    instruction(
+        swap.program_id,
+        EscrowInstruction::ClaimBond { pass: PASS },
+        vec![
+            AccountMeta::new(swap.escrow, false),
+            AccountMeta::new(claimant.pubkey(), true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+/// Started with a bonded escrow in `state`, both bonds posted and the deposits `state` implies
+async fn start_in(state: EscrowState, deposit_deadline: i64) -> (Context, Swap) {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let alice_funded = state == EscrowState::DepositAlice || state == EscrowState::Committed;
+    let bob_funded = state == EscrowState::DepositBob || state == EscrowState::Committed;
+    let escrow_data = EscrowData {
+        state,
+        deposited_x: if alice_funded { SIZE_X } else { 0 },
+        deposited_y: if bob_funded { SIZE_Y } else { 0 },
+        bond: BondTerms {
+            amount: BOND,
+            deposit_deadline,
+        },
+        bond_posted_alice: true,
+        bond_posted_bob: true,
+        ..swap.escrow_data(SIZE_X, SIZE_Y)
+    };
+    swap.add_escrow(&mut program_test, escrow_data);
+    (Context::start(program_test).await, swap)
+}
+
+#[tokio::test]
+async fn test_bonds_posted_and_returned() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let mut context = Context::start(program_test).await;
+    let terms = bonded(now() + 3_600);
+
+    let open = swap.open(&context.payer(), &swap.alice, SIZE_X, SIZE_Y, terms);
+    assert!(context.send(&[open], &[&swap.alice]).await);
+    assert_eq!(context.lamports(swap.alice.pubkey()).await, LAMPORTS - BOND);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert!(escrow_data.bond_posted_alice && !escrow_data.bond_posted_bob);
+    let escrow_lamports = context.lamports(swap.escrow).await;
+
+    let without_bond_accounts = swap.accept(&swap.bob, SIZE_X, SIZE_Y);
+    assert!(!context.send(&[without_bond_accounts], &[&swap.bob]).await);
+    let mut accept = swap.accept(&swap.bob, SIZE_X, SIZE_Y);
+    accept
+        .accounts
+        .push(AccountMeta::new_readonly(system_program::id(), false));
+    assert!(context.send(&[with_clock(accept)], &[&swap.bob]).await);
+    assert_eq!(context.lamports(swap.bob.pubkey()).await, LAMPORTS - BOND);
+    assert_eq!(context.lamports(swap.escrow).await, escrow_lamports + BOND);
+
+    let deposit = swap.deposit(&swap.alice, SIZE_X);
+    assert!(!context.send(&[deposit], &[&swap.alice]).await);
+    let alice_deposit = with_clock(swap.deposit(&swap.alice, SIZE_X));
+    let bob_deposit = with_clock(swap.deposit(&swap.bob, SIZE_Y));
+    let parties = [&swap.alice, &swap.bob];
+    assert!(context.send(&[alice_deposit, bob_deposit], &parties).await);
+
+    // committed bonds come back with a claim or with the payout
+    let alice_claim = claim_bond(&swap, &swap.alice);
+    assert!(context.send(&[alice_claim], &[&swap.alice]).await);
+    assert_eq!(context.lamports(swap.alice.pubkey()).await, LAMPORTS);
+    let claimed_twice = claim_bond(&swap, &swap.alice);
+    assert!(!context.send(&[claimed_twice], &[&swap.alice]).await);
+    assert!(context.send(&[swap.withdraw_bob()], &[&swap.bob]).await);
+    assert_eq!(context.lamports(swap.bob.pubkey()).await, LAMPORTS);
+    assert_eq!(context.lamports(swap.escrow).await, escrow_lamports - BOND);
+}
+
+#[tokio::test]
+async fn test_claim_bond_slashes_the_unfunded_party() {
+    let (mut context, swap) = start_in(EscrowState::DepositAlice, now() - 60).await;
+
+    let deposit = with_clock(swap.deposit(&swap.bob, SIZE_Y));
+    assert!(!context.send(&[deposit], &[&swap.bob]).await);
+    let by_bob = claim_bond(&swap, &swap.bob);
+    assert!(!context.send(&[by_bob], &[&swap.bob]).await);
+
+    let by_alice = claim_bond(&swap, &swap.alice);
+    assert!(context.send(&[by_alice], &[&swap.alice]).await);
+    let slashed = LAMPORTS + 2 * BOND;
+    assert_eq!(context.lamports(swap.alice.pubkey()).await, slashed);
+    assert_eq!(context.lamports(swap.escrow).await, LAMPORTS - 2 * BOND);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert!(!escrow_data.bond_posted_alice && !escrow_data.bond_posted_bob);
+
+    // alice still gets her deposit back
+    let refund = swap.withdrawal(&swap.alice, swap.alice_x, swap.vault_x);
+    assert!(context.send(&[refund], &[&swap.alice]).await);
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS + SIZE_X);
+}
+
+#[tokio::test]
+async fn test_claim_bond_waits_for_the_deadline() {
+    let (mut context, swap) = start_in(EscrowState::DepositBob, now() + 3_600).await;
+
+    let by_bob = claim_bond(&swap, &swap.bob);
+    assert!(!context.send(&[by_bob], &[&swap.bob]).await);
+    assert_eq!(context.lamports(swap.bob.pubkey()).await, LAMPORTS);
+}
+
+#[tokio::test]
+async fn test_claim_bond_without_deposits() {
+    let (mut context, swap) = start_in(EscrowState::Initialized, now() - 60).await;
+
+    for party in [&swap.alice, &swap.bob].iter() {
+        assert!(context.send(&[claim_bond(&swap, party)], &[party]).await);
+        assert_eq!(context.lamports(party.pubkey()).await, LAMPORTS + BOND);
+    }
+    let outsider = Keypair::new();
+    let by_outsider = claim_bond(&swap, &outsider);
+    assert!(!context.send(&[by_outsider], &[&outsider]).await);
+}
+
+#[tokio::test]
+async fn test_mutual_refund_returns_bonds() {
+    let (mut context, swap) = start_in(EscrowState::Committed, now() - 60).await;
+    let parties = [&swap.alice, &swap.bob];
+
+    assert!(context.send(&[swap.mutual_refund()], &parties).await);
+    assert_eq!(context.lamports(swap.alice.pubkey()).await, LAMPORTS + BOND);
+    assert_eq!(context.lamports(swap.bob.pubkey()).await, LAMPORTS + BOND);
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS + SIZE_X);
+    assert_eq!(context.balance(swap.bob_y).await, FUNDS + SIZE_Y);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Initialized);
+
+    let claim = claim_bond(&swap, &swap.alice);
+    assert!(!context.send(&[claim], &[&swap.alice]).await);
+}
+
+#[tokio::test]
+async fn test_init_rejects_invalid_bonds() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let mut context = Context::start(program_test).await;
+
+    let zero = EscrowTerms {
+        bond: Some(BondTerms {
+            amount: 0,
+            deposit_deadline: now() + 3_600,
+        }),
+        ..EscrowTerms::default()
+    };
+    for terms in [zero, bonded(0)] {
+        let init = swap.init(&context.payer(), SIZE_X, SIZE_Y, terms);
+        assert!(!context.send(&[init], &[&swap.alice, &swap.bob]).await);
+    }
+}
@@ -0,0 +1,252 @@
+mod common;
+
+use common::*;
+use escrow::{
+    instruction::EscrowInstruction,
+    state::{CampaignData, ContributionReceipt},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program, sysvar,
+};
+
+const GOAL: u64 = 1_000;
+
+struct Setup {
+    program_id: Pubkey,
+    creator: Keypair,
+    mint: Pubkey,
+    campaign: Pubkey,
+    vault: Pubkey,
+    creator_token: Pubkey,
+    contributors: Vec<(Keypair, Pubkey)>, // with their token account
+}
+
+impl Setup {
+    fn receipt(&self, contributor: &Keypair) -> Pubkey {
+        pda(
+            &self.program_id,
+            &[
+                b"receipt",
+                self.campaign.as_ref(),
+                contributor.pubkey().as_ref(),
+            ],
+        )
+    }
+}
+
+fn setup() -> (ProgramTest, Setup) {
+    let program_id = Pubkey::new_unique();
+    let creator = Keypair::new();
+    let mint = Pubkey::new_unique();
+    let campaign = pda(
+        &program_id,
+        &[
+            b"campaign",
+            creator.pubkey().as_ref(),
+            mint.as_ref(),
+            PASS.as_ref(),
+        ],
+    );
+    let vault = pda(&program_id, &[b"vault", campaign.as_ref()]);
+    let creator_token = Pubkey::new_unique();
+    let contributors: Vec<_> = (0..2)
+        .map(|_| (Keypair::new(), Pubkey::new_unique()))
+        .collect();
+
+    let mut program_test = program_test(program_id);
+    program_test.add_account(mint, mint_account(0));
+    program_test.add_account(creator.pubkey(), system_account());
+    program_test.add_account(creator_token, token_account(mint, creator.pubkey(), 0));
+    for (contributor, token) in contributors.iter() {
+        program_test.add_account(contributor.pubkey(), system_account());
+        program_test.add_account(*token, token_account(mint, contributor.pubkey(), FUNDS));
+    }
+    (
+        program_test,
+        Setup {
+            program_id,
+            creator,
+            mint,
+            campaign,
+            vault,
+            creator_token,
+            contributors,
+        },
+    )
+}
+
+/// A campaign past its deadline that raised `raised`, all of it from the first contributor
+fn add_ended_campaign(program_test: &mut ProgramTest, setup: &Setup, raised: u64) {
+    let bump = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &setup.program_id).1;
+    let campaign_data = CampaignData {
+        is_initialized: true,
+        is_withdrawn: false,
+        pubkey_creator: setup.creator.pubkey(),
+        pubkey_mint: setup.mint,
+        goal: GOAL,
+        deadline: now() - 1,
+        raised,
+        escrow_bump: bump(&[
+            b"campaign",
+            setup.creator.pubkey().as_ref(),
+            setup.mint.as_ref(),
+            PASS.as_ref(),
+        ]),
+        vault_bump: bump(&[b"vault", setup.campaign.as_ref()]),
+    };
+    let contributor = &setup.contributors[0].0;
+    let receipt = ContributionReceipt {
+        is_initialized: true,
+        pubkey_campaign: setup.campaign,
+        pubkey_contributor: contributor.pubkey(),
+        amount: raised,
+        bump: bump(&[
+            b"receipt",
+            setup.campaign.as_ref(),
+            contributor.pubkey().as_ref(),
+        ]),
+    };
+    program_test.add_account(
+        setup.campaign,
+        program_account(&campaign_data, setup.program_id),
+    );
+    program_test.add_account(
+        setup.receipt(contributor),
+        program_account(&receipt, setup.program_id),
+    );
+    program_test.add_account(
+        setup.vault,
+        token_account(setup.mint, setup.campaign, raised),
+    );
+}
+
+fn contribute(setup: &Setup, contributor: usize, amount: u64) -> Instruction {
+    let (contributor, token) = &setup.contributors[contributor];
+    instruction(
+        setup.program_id,
+        EscrowInstruction::Contribute { amount, pass: PASS },
+        vec![
+            AccountMeta::new(setup.campaign, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(setup.receipt(contributor), false),
+            AccountMeta::new(*token, false),
+            AccountMeta::new(contributor.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+fn withdraw(setup: &Setup) -> Instruction {
+    instruction(
+        setup.program_id,
+        EscrowInstruction::WithdrawCampaign { pass: PASS },
+        vec![
+            AccountMeta::new(setup.campaign, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(setup.creator_token, false),
+            AccountMeta::new_readonly(setup.creator.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+fn refund(setup: &Setup, contributor: usize, receipt_of: usize) -> Instruction {
+    let (contributor, token) = &setup.contributors[contributor];
+    instruction(
+        setup.program_id,
+        EscrowInstruction::Refund { pass: PASS },
+        vec![
+            AccountMeta::new_readonly(setup.campaign, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(setup.receipt(&setup.contributors[receipt_of].0), false),
+            AccountMeta::new(*token, false),
+            AccountMeta::new_readonly(contributor.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_contribute_until_deadline() {
+    let (program_test, setup) = setup();
+    let mut context = Context::start(program_test).await;
+    let (first, second) = (&setup.contributors[0].0, &setup.contributors[1].0);
+
+    let init = instruction(
+        setup.program_id,
+        EscrowInstruction::InitCampaign {
+            goal: GOAL,
+            deadline: now() + 3_600,
+            pass: PASS,
+        },
+        vec![
+            AccountMeta::new(setup.campaign, false),
+            AccountMeta::new_readonly(setup.mint, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(setup.creator.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    );
+    assert!(context.send(&[init], &[&setup.creator]).await);
+
+    assert!(context.send(&[contribute(&setup, 0, 300)], &[first]).await);
+    assert!(context.send(&[contribute(&setup, 0, 400)], &[first]).await);
+    assert!(context.send(&[contribute(&setup, 1, 500)], &[second]).await);
+    assert!(!context.send(&[contribute(&setup, 1, 0)], &[second]).await);
+
+    let receipt: ContributionReceipt = context.read(setup.receipt(first)).await;
+    assert_eq!(receipt.amount, 700);
+    let campaign_data: CampaignData = context.read(setup.campaign).await;
+    assert_eq!(campaign_data.raised, 1_200);
+    assert_eq!(context.balance(setup.vault).await, 1_200);
+
+    // nothing leaves the vault before the deadline, goal met or not
+    assert!(!context.send(&[withdraw(&setup)], &[&setup.creator]).await);
+    assert!(!context.send(&[refund(&setup, 0, 0)], &[first]).await);
+}
+
+#[tokio::test]
+async fn test_withdraw_met_goal() {
+    let (mut program_test, setup) = setup();
+    add_ended_campaign(&mut program_test, &setup, GOAL);
+    let mut context = Context::start(program_test).await;
+    let first = &setup.contributors[0].0;
+
+    assert!(!context.send(&[contribute(&setup, 0, 1)], &[first]).await);
+    assert!(!context.send(&[refund(&setup, 0, 0)], &[first]).await);
+
+    assert!(context.send(&[withdraw(&setup)], &[&setup.creator]).await);
+    assert_eq!(context.balance(setup.creator_token).await, GOAL);
+    assert!(!context.send(&[withdraw(&setup)], &[&setup.creator]).await);
+}
+
+#[tokio::test]
+async fn test_refund_missed_goal() {
+    let (mut program_test, setup) = setup();
+    add_ended_campaign(&mut program_test, &setup, GOAL - 1);
+    let mut context = Context::start(program_test).await;
+    let (first, second) = (&setup.contributors[0].0, &setup.contributors[1].0);
+
+    assert!(!context.send(&[withdraw(&setup)], &[&setup.creator]).await);
+    // a receipt only refunds its own contributor
+    assert!(!context.send(&[refund(&setup, 1, 0)], &[second]).await);
+
+    assert!(context.send(&[refund(&setup, 0, 0)], &[first]).await);
+    assert_eq!(
+        context.balance(setup.contributors[0].1).await,
+        FUNDS + GOAL - 1
+    );
+    assert_eq!(context.balance(setup.vault).await, 0);
+    assert!(!context.send(&[refund(&setup, 0, 0)], &[first]).await);
+}
@@ -0,0 +1,145 @@
+mod common;
+
+use common::*;
+use escrow::state::{EscrowData, EscrowState, EscrowTerms, Prerequisite};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+const SIZE_X: u64 = 1_000;
+const SIZE_Y: u64 = 2_000;
+
+/// A plain escrow and a second one between the same parties chained to it
+struct Setup {
+    first: Swap,
+    chained: Swap,
+}
+
+fn setup() -> (ProgramTest, Setup) {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let first = Swap::new(&mut program_test, program_id);
+    let chained = first.with_pass([8; 32]);
+    (program_test, Setup { first, chained })
+}
+
+fn chained_terms(prerequisite: Pubkey) -> EscrowTerms {
+    EscrowTerms {
+        prerequisite: Some(Prerequisite {
+            pubkey_escrow: prerequisite,
+            pass: PASS,
+        }),
+        ..EscrowTerms::default()
+    }
+}
+
+/// Appends the prerequisite escrow, which chained escrows read when paying out
+fn with_prerequisite(mut instruction: Instruction, prerequisite: Pubkey) -> Instruction {
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(prerequisite, false));
+    instruction
+}
+
+/// Started with both escrows initialized, and funded when `funded`
+async fn start(funded: bool) -> (Context, Setup) {
+    let (program_test, setup) = setup();
+    let (first, chained) = (&setup.first, &setup.chained);
+    let mut context = Context::start(program_test).await;
+    let payer = context.payer();
+    let parties = [&first.alice, &first.bob];
+
+    let init = first.init(&payer, SIZE_X, SIZE_Y, EscrowTerms::default());
+    assert!(context.send(&[init], &parties).await);
+    let terms = chained_terms(first.escrow);
+    let init = with_prerequisite(chained.init(&payer, SIZE_X, SIZE_Y, terms), first.escrow);
+    assert!(context.send(&[init], &parties).await);
+    if !funded {
+        return (context, setup);
+    }
+    for swap in [first, chained].iter() {
+        let alice_deposit = swap.deposit(&swap.alice, SIZE_X);
+        let bob_deposit = swap.deposit(&swap.bob, SIZE_Y);
+        assert!(context.send(&[alice_deposit, bob_deposit], &parties).await);
+    }
+    (context, setup)
+}
+
+#[tokio::test]
+async fn test_chained_waits_for_prerequisite() {
+    let (mut context, setup) = start(true).await;
+    let (first, chained) = (&setup.first, &setup.chained);
+    let escrow_data: EscrowData = context.read(chained.escrow).await;
+    assert_eq!(escrow_data.pubkey_prerequisite, first.escrow);
+
+    let without_prerequisite = chained.withdraw_alice();
+    assert!(!context.send(&[without_prerequisite], &[&first.alice]).await);
+    let early = with_prerequisite(chained.withdraw_alice(), first.escrow);
+    assert!(!context.send(&[early], &[&first.alice]).await);
+
+    // a prerequisite that only one side withdrew from is not completed yet
+    let first_alice = first.withdraw_alice();
+    assert!(context.send(&[first_alice], &[&first.alice]).await);
+    let half_done = with_prerequisite(chained.withdraw_alice(), first.escrow);
+    assert!(!context.send(&[half_done], &[&first.alice]).await);
+    assert!(context.send(&[first.withdraw_bob()], &[&first.bob]).await);
+
+    let wrong_prerequisite = with_prerequisite(chained.withdraw_alice(), chained.escrow);
+    assert!(!context.send(&[wrong_prerequisite], &[&first.alice]).await);
+    let alice_withdrawal = with_prerequisite(chained.withdraw_alice(), first.escrow);
+    assert!(context.send(&[alice_withdrawal], &[&first.alice]).await);
+    let bob_withdrawal = with_prerequisite(chained.withdraw_bob(), first.escrow);
+    assert!(context.send(&[bob_withdrawal], &[&first.bob]).await);
+
+    assert_eq!(context.balance(first.alice_y).await, FUNDS + 2 * SIZE_Y);
+    assert_eq!(context.balance(first.bob_x).await, FUNDS + 2 * SIZE_X);
+    let escrow_data: EscrowData = context.read(chained.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Completed);
+}
+
+#[tokio::test]
+async fn test_chained_refunds_are_not_gated() {
+    let (mut context, setup) = start(false).await;
+    let (first, chained) = (&setup.first, &setup.chained);
+    let deposit = chained.deposit(&chained.alice, SIZE_X);
+    assert!(context.send(&[deposit], &[&chained.alice]).await);
+
+    let refund = chained.withdrawal(&chained.alice, chained.alice_x, chained.vault_x);
+    assert!(context.send(&[refund], &[&chained.alice]).await);
+    assert_eq!(context.balance(chained.alice_x).await, FUNDS);
+    let escrow_data: EscrowData = context.read(first.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Initialized);
+}
+
+#[tokio::test]
+async fn test_init_rejects_invalid_prerequisites() {
+    let (program_test, setup) = setup();
+    let (first, chained) = (&setup.first, &setup.chained);
+    let mut context = Context::start(program_test).await;
+    let payer = context.payer();
+    let parties = [&first.alice, &first.bob];
+
+    // the first escrow does not exist yet
+    let terms = chained_terms(first.escrow);
+    let init = with_prerequisite(chained.init(&payer, SIZE_X, SIZE_Y, terms), first.escrow);
+    assert!(!context.send(&[init], &parties).await);
+
+    let itself = EscrowTerms {
+        prerequisite: Some(Prerequisite {
+            pubkey_escrow: chained.escrow,
+            pass: chained.pass,
+        }),
+        ..EscrowTerms::default()
+    };
+    let init = with_prerequisite(chained.init(&payer, SIZE_X, SIZE_Y, itself), chained.escrow);
+    assert!(!context.send(&[init], &parties).await);
+
+    let init = first.init(&payer, SIZE_X, SIZE_Y, EscrowTerms::default());
+    assert!(context.send(&[init], &parties).await);
+    let terms = chained_terms(first.escrow);
+    let mismatch = with_prerequisite(chained.init(&payer, SIZE_X, SIZE_Y, terms), payer);
+    assert!(!context.send(&[mismatch], &parties).await);
+    assert!(context.get_account(chained.escrow).await.is_none());
+}
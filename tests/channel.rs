@@ -0,0 +1,235 @@
+mod common;
+
+use common::*;
+use escrow::{
+    instruction::EscrowInstruction,
+    state::{ChannelBalance, ChannelTerms, EscrowData, EscrowState, EscrowTerms},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    sysvar,
+};
+
+const SIZE_X: u64 = 1_000;
+const SIZE_Y: u64 = 2_000;
+const CHALLENGE_PERIOD: i64 = 3_600;
+
+fn submit(swap: &Swap, submitter: &Keypair, balance: ChannelBalance) -> Instruction {
+    instruction(
+        swap.program_id,
+        EscrowInstruction::SubmitChannelState {
+            balance,
+            pass: PASS,
+        },
+        vec![
+            AccountMeta::new(swap.escrow, false),
+            AccountMeta::new_readonly(submitter.pubkey(), true),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+/// `balance` co-signed by `signers`, submitted by alice
+fn signed_submit(swap: &Swap, balance: ChannelBalance, signers: &[&Keypair]) -> Vec<Instruction> {
+    let message = balance.message(&swap.escrow);
+    let mut instructions: Vec<_> = signers
+        .iter()
+        .map(|signer| ed25519_instruction(signer, &message))
+        .collect();
+    instructions.push(submit(swap, &swap.alice, balance));
+    instructions
+}
+
+fn settle(swap: &Swap) -> Instruction {
+    instruction(
+        swap.program_id,
+        EscrowInstruction::SettleChannel { pass: PASS },
+        vec![
+            AccountMeta::new(swap.escrow, false),
+            AccountMeta::new(swap.vault_x, false),
+            AccountMeta::new(swap.vault_y, false),
+            AccountMeta::new(swap.alice_x, false),
+            AccountMeta::new(swap.alice_y, false),
+            AccountMeta::new(swap.bob_x, false),
+            AccountMeta::new(swap.bob_y, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+fn close(swap: &Swap, closer: &Keypair) -> Instruction {
+    instruction(
+        swap.program_id,
+        EscrowInstruction::CloseChannel { pass: PASS },
+        vec![
+            AccountMeta::new(swap.escrow, false),
+            AccountMeta::new_readonly(closer.pubkey(), true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+fn balance(nonce: u64, alice_x: u64, alice_y: u64) -> ChannelBalance {
+    ChannelBalance {
+        nonce,
+        alice_x,
+        alice_y,
+    }
+}
+
+/// A funded channel, `Settling` until `closes_at` when `balance` is given and `Committed` otherwise
+fn add_channel(
+    program_test: &mut ProgramTest,
+    swap: &Swap,
+    balance: Option<(ChannelBalance, i64)>,
+) {
+    let mut escrow_data = swap.escrow_data(SIZE_X, SIZE_Y);
+    escrow_data.state = EscrowState::Committed;
+    escrow_data.deposited_x = SIZE_X;
+    escrow_data.deposited_y = SIZE_Y;
+    escrow_data.channel = ChannelTerms {
+        challenge_period: CHALLENGE_PERIOD,
+    };
+    if let Some((balance, closes_at)) = balance {
+        escrow_data.state = EscrowState::Settling;
+        escrow_data.channel_balance = balance;
+        escrow_data.channel_closes_at = closes_at;
+    }
+    swap.add_escrow(program_test, escrow_data);
+}
+
+#[tokio::test]
+async fn test_submit_channel_state() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    add_ed25519_program(&mut program_test);
+    let swap = Swap::new(&mut program_test, program_id);
+    let mut context = Context::start(program_test).await;
+    let parties = [&swap.alice, &swap.bob];
+
+    let terms = EscrowTerms {
+        channel: Some(ChannelTerms {
+            challenge_period: CHALLENGE_PERIOD,
+        }),
+        ..EscrowTerms::default()
+    };
+    let init = swap.init(&context.payer(), SIZE_X, SIZE_Y, terms);
+    assert!(context.send(&[init], &parties).await);
+    let alice_deposit = swap.deposit(&swap.alice, SIZE_X);
+    let bob_deposit = swap.deposit(&swap.bob, SIZE_Y);
+    assert!(context.send(&[alice_deposit, bob_deposit], &parties).await);
+
+    let first = balance(1, 400, 700);
+    let submit_first = signed_submit(&swap, first, &parties);
+    assert!(context.send(&submit_first, &[&swap.alice]).await);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Settling);
+    assert_eq!(escrow_data.channel_balance, first);
+    assert!((escrow_data.channel_closes_at - now() - CHALLENGE_PERIOD).abs() < 60);
+
+    // the challenge period runs, only newer co-signed balances within the deposits replace it
+    assert!(!context.send(&[settle(&swap)], &[]).await);
+    let stale = signed_submit(&swap, balance(1, 0, 0), &parties);
+    assert!(!context.send(&stale, &[&swap.alice]).await);
+    let excessive = signed_submit(&swap, balance(2, SIZE_X + 1, 0), &parties);
+    assert!(!context.send(&excessive, &[&swap.alice]).await);
+
+    let newer = balance(2, 100, 0);
+    let submit_newer = signed_submit(&swap, newer, &parties);
+    assert!(context.send(&submit_newer, &[&swap.alice]).await);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.channel_balance, newer);
+}
+
+#[tokio::test]
+async fn test_submit_requires_both_signatures() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    add_ed25519_program(&mut program_test);
+    let swap = Swap::new(&mut program_test, program_id);
+    add_channel(&mut program_test, &swap, None);
+    let mut context = Context::start(program_test).await;
+    let update = balance(1, 0, SIZE_Y);
+
+    let unsigned = submit(&swap, &swap.alice, update);
+    assert!(!context.send(&[unsigned], &[&swap.alice]).await);
+    let alice_only = signed_submit(&swap, update, &[&swap.alice]);
+    assert!(!context.send(&alice_only, &[&swap.alice]).await);
+
+    // signatures of another balance do not vouch for this one
+    let message = balance(1, SIZE_X, 0).message(&swap.escrow);
+    let mismatched = vec![
+        ed25519_instruction(&swap.alice, &message),
+        ed25519_instruction(&swap.bob, &message),
+        submit(&swap, &swap.alice, update),
+    ];
+    assert!(!context.send(&mismatched, &[&swap.alice]).await);
+
+    let outsider = Keypair::new();
+    let mut by_outsider = signed_submit(&swap, update, &[&swap.alice, &swap.bob]);
+    by_outsider[2] = submit(&swap, &outsider, update);
+    assert!(!context.send(&by_outsider, &[&outsider]).await);
+
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Committed);
+}
+
+#[tokio::test]
+async fn test_settle_channel() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    add_ed25519_program(&mut program_test);
+    let swap = Swap::new(&mut program_test, program_id);
+    let settled = balance(3, 400, 700);
+    add_channel(&mut program_test, &swap, Some((settled, now() - 1)));
+    let mut context = Context::start(program_test).await;
+
+    let late = signed_submit(&swap, balance(4, 0, 0), &[&swap.alice, &swap.bob]);
+    assert!(!context.send(&late, &[&swap.alice]).await);
+
+    assert!(context.send(&[settle(&swap)], &[]).await);
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS + 400);
+    assert_eq!(context.balance(swap.alice_y).await, FUNDS + 700);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS + SIZE_X - 400);
+    assert_eq!(context.balance(swap.bob_y).await, FUNDS + SIZE_Y - 700);
+    assert_eq!(context.balance(swap.vault_x).await, 0);
+    assert_eq!(context.balance(swap.vault_y).await, 0);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Completed);
+
+    assert!(!context.send(&[settle(&swap)], &[]).await);
+}
+
+#[tokio::test]
+async fn test_close_channel() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    add_ed25519_program(&mut program_test);
+    let swap = Swap::new(&mut program_test, program_id);
+    add_channel(&mut program_test, &swap, None);
+    let mut context = Context::start(program_test).await;
+
+    let outsider = Keypair::new();
+    let by_outsider = close(&swap, &outsider);
+    assert!(!context.send(&[by_outsider], &[&outsider]).await);
+
+    let by_bob = close(&swap, &swap.bob);
+    assert!(context.send(&[by_bob], &[&swap.bob]).await);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Settling);
+    assert_eq!(escrow_data.channel_balance, balance(0, SIZE_X, 0));
+    let again = close(&swap, &swap.alice);
+    assert!(!context.send(&[again], &[&swap.alice]).await);
+
+    // a co-signed balance still overrides the deposit split during the challenge period
+    let update = balance(1, 0, SIZE_Y);
+    let submit_update = signed_submit(&swap, update, &[&swap.alice, &swap.bob]);
+    assert!(context.send(&submit_update, &[&swap.alice]).await);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.channel_balance, update);
+}
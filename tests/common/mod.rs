@@ -0,0 +1,494 @@
+#![allow(dead_code)]
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use escrow::{
+    instruction::EscrowInstruction,
+    processor::Processor,
+    state::{EscrowData, EscrowTerms},
+};
+use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, program_pack::Pack};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    system_program, sysvar,
+    transaction::Transaction,
+};
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+use std::{
+    collections::HashSet,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub const PASS: [u8; 32] = [7; 32];
+/// Balance of every token account a fixture funds
+pub const FUNDS: u64 = 1_000_000;
+/// Lamports of every party a fixture creates, enough to post bonds
+pub const LAMPORTS: u64 = 1_000_000_000;
+
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+pub fn program_test(program_id: Pubkey) -> ProgramTest {
+    ProgramTest::new("escrow", program_id, processor!(Processor::process))
+}
+
+pub fn pda(program_id: &Pubkey, seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, program_id).0
+}
+
+pub fn token_account(mint: Pubkey, owner: Pubkey, amount: u64) -> Account {
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount {
+        mint,
+        owner,
+        amount,
+        state: AccountState::Initialized,
+        ..TokenAccount::default()
+    }
+    .pack_into_slice(&mut data);
+    Account {
+        lamports: LAMPORTS,
+        data,
+        owner: spl_token::id(),
+        ..Account::default()
+    }
+}
+
+pub fn mint_account(decimals: u8) -> Account {
+    let mut data = vec![0; Mint::LEN];
+    Mint {
+        is_initialized: true,
+        decimals,
+        supply: u64::MAX,
+        ..Mint::default()
+    }
+    .pack_into_slice(&mut data);
+    Account {
+        lamports: LAMPORTS,
+        data,
+        owner: spl_token::id(),
+        ..Account::default()
+    }
+}
+
+/// A program owned account holding `data`, for state the test does not build through instructions
+pub fn program_account<T: BorshSerialize>(data: &T, program_id: Pubkey) -> Account {
+    Account {
+        lamports: LAMPORTS,
+        data: data.try_to_vec().unwrap(),
+        owner: program_id,
+        ..Account::default()
+    }
+}
+
+pub fn system_account() -> Account {
+    Account {
+        lamports: LAMPORTS,
+        ..Account::default()
+    }
+}
+
+pub fn instruction(
+    program_id: Pubkey,
+    instruction: EscrowInstruction,
+    accounts: Vec<AccountMeta>,
+) -> Instruction {
+    Instruction::new_with_bytes(program_id, &instruction.try_to_vec().unwrap(), accounts)
+}
+
+fn accept_signature(
+    _program_id: &Pubkey,
+    _accounts: &[AccountInfo],
+    _instruction_data: &[u8],
+) -> ProgramResult {
+    Ok(())
+}
+
+/// Stands in for the Ed25519 program, which this runtime does not provide. The escrow program only
+/// reads the instruction through the instructions sysvar, the signatures are checked by the tests building them
+pub fn add_ed25519_program(program_test: &mut ProgramTest) {
+    program_test.add_program(
+        "ed25519",
+        escrow::ed25519::id(),
+        processor!(accept_signature),
+    );
+}
+
+/// An Ed25519 program instruction carrying `signer`'s signature of `message`
+pub fn ed25519_instruction(signer: &Keypair, message: &[u8]) -> Instruction {
+    const OFFSETS_END: u16 = 2 + 14;
+    let public_key_offset = OFFSETS_END;
+    let signature_offset = public_key_offset + 32;
+    let message_data_offset = signature_offset + 64;
+    let signature = signer.sign_message(message);
+    assert!(signature.verify(signer.pubkey().as_ref(), message));
+
+    let mut data = vec![1, 0];
+    for value in [
+        signature_offset,
+        u16::MAX,
+        public_key_offset,
+        u16::MAX,
+        message_data_offset,
+        message.len() as u16,
+        u16::MAX,
+    ]
+    .iter()
+    {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(signer.pubkey().as_ref());
+    data.extend_from_slice(signature.as_ref());
+    data.extend_from_slice(message);
+    Instruction::new_with_bytes(escrow::ed25519::id(), &data, vec![])
+}
+
+/// A started test validator, paying for every transaction with its payer
+pub struct Context {
+    pub context: ProgramTestContext,
+    sent: HashSet<Signature>,
+}
+
+impl Context {
+    pub async fn start(program_test: ProgramTest) -> Self {
+        Context {
+            context: program_test.start_with_context().await,
+            sent: HashSet::new(),
+        }
+    }
+
+    pub fn payer(&self) -> Pubkey {
+        self.context.payer.pubkey()
+    }
+
+    /// Sends `instructions` signed by `signers`, returns whether the transaction went through.
+    /// A repeated transaction waits for a new blockhash so the bank does not drop it as a duplicate
+    pub async fn send(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> bool {
+        let mut keypairs = vec![&self.context.payer];
+        keypairs.extend_from_slice(signers);
+        let mut transaction = Transaction::new_with_payer(instructions, Some(&self.payer()));
+        let banks_client = &mut self.context.banks_client;
+        let mut recent_blockhash = banks_client.get_recent_blockhash().await.unwrap();
+        transaction.sign(&keypairs, recent_blockhash);
+        while self.sent.contains(&transaction.signatures[0]) {
+            recent_blockhash = banks_client
+                .get_new_blockhash(&recent_blockhash)
+                .await
+                .unwrap()
+                .0;
+            transaction.sign(&keypairs, recent_blockhash);
+        }
+        self.sent.insert(transaction.signatures[0]);
+        banks_client.process_transaction(transaction).await.is_ok()
+    }
+
+    pub async fn get_account(&mut self, pubkey: Pubkey) -> Option<Account> {
+        self.context
+            .banks_client
+            .get_account(pubkey)
+            .await
+            .expect("get_account")
+    }
+
+    pub async fn balance(&mut self, token: Pubkey) -> u64 {
+        let account = self
+            .get_account(token)
+            .await
+            .expect("token account not found");
+        TokenAccount::unpack(&account.data).unwrap().amount
+    }
+
+    pub async fn lamports(&mut self, pubkey: Pubkey) -> u64 {
+        self.get_account(pubkey)
+            .await
+            .map_or(0, |account| account.lamports)
+    }
+
+    pub async fn read<T: BorshDeserialize>(&mut self, pubkey: Pubkey) -> T {
+        let account = self.get_account(pubkey).await.expect("account not found");
+        T::try_from_slice(&account.data).unwrap()
+    }
+
+    /// Moves the bank forward to `slot`, for tests depending on the slot
+    pub fn warp_to_slot(&mut self, slot: u64) {
+        self.context.warp_to_slot(slot).unwrap();
+    }
+}
+
+/// Alice swapping X for bob's Y, both funded with `FUNDS` of each mint
+pub struct Swap {
+    pub program_id: Pubkey,
+    pub pass: [u8; 32],
+    pub alice: Keypair,
+    pub bob: Keypair,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub escrow: Pubkey,
+    pub vault_x: Pubkey,
+    pub vault_y: Pubkey,
+    pub alice_x: Pubkey,
+    pub alice_y: Pubkey,
+    pub bob_x: Pubkey,
+    pub bob_y: Pubkey,
+}
+
+impl Swap {
+    pub fn new(program_test: &mut ProgramTest, program_id: Pubkey) -> Self {
+        let alice = Keypair::new();
+        let bob = Keypair::new();
+        let mint_x = Pubkey::new_unique();
+        let mint_y = Pubkey::new_unique();
+        let swap = Swap {
+            program_id,
+            pass: PASS,
+            escrow: Pubkey::default(),
+            vault_x: Pubkey::default(),
+            vault_y: Pubkey::default(),
+            alice,
+            bob,
+            mint_x,
+            mint_y,
+            alice_x: Pubkey::new_unique(),
+            alice_y: Pubkey::new_unique(),
+            bob_x: Pubkey::new_unique(),
+            bob_y: Pubkey::new_unique(),
+        }
+        .with_pass(PASS);
+        swap.add_accounts(program_test);
+        swap
+    }
+
+    /// Another escrow under `pass` between the same parties, mints and token accounts
+    pub fn with_pass(&self, pass: [u8; 32]) -> Self {
+        let (escrow, vault_x, vault_y) = (
+            self.pda(b"escrow", pass).0,
+            self.pda(b"vault_x", pass).0,
+            self.pda(b"vault_y", pass).0,
+        );
+        Swap {
+            program_id: self.program_id,
+            pass,
+            escrow,
+            vault_x,
+            vault_y,
+            alice: Keypair::from_bytes(&self.alice.to_bytes()).unwrap(),
+            bob: Keypair::from_bytes(&self.bob.to_bytes()).unwrap(),
+            ..*self
+        }
+    }
+
+    fn pda(&self, prefix: &[u8], pass: [u8; 32]) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                prefix,
+                self.alice.pubkey().as_ref(),
+                self.bob.pubkey().as_ref(),
+                self.mint_x.as_ref(),
+                self.mint_y.as_ref(),
+                pass.as_ref(),
+            ],
+            &self.program_id,
+        )
+    }
+
+    fn add_accounts(&self, program_test: &mut ProgramTest) {
+        program_test.add_account(self.mint_x, mint_account(0));
+        program_test.add_account(self.mint_y, mint_account(0));
+        program_test.add_account(self.alice.pubkey(), system_account());
+        program_test.add_account(self.bob.pubkey(), system_account());
+        program_test.add_account(
+            self.alice_x,
+            token_account(self.mint_x, self.alice.pubkey(), FUNDS),
+        );
+        program_test.add_account(
+            self.alice_y,
+            token_account(self.mint_y, self.alice.pubkey(), FUNDS),
+        );
+        program_test.add_account(
+            self.bob_x,
+            token_account(self.mint_x, self.bob.pubkey(), FUNDS),
+        );
+        program_test.add_account(
+            self.bob_y,
+            token_account(self.mint_y, self.bob.pubkey(), FUNDS),
+        );
+    }
+
+    /// Puts `escrow_data` at the escrow address and funds the vaults with what it says was deposited
+    pub fn add_escrow(&self, program_test: &mut ProgramTest, escrow_data: EscrowData) {
+        program_test.add_account(
+            self.vault_x,
+            token_account(self.mint_x, self.escrow, escrow_data.deposited_x),
+        );
+        program_test.add_account(
+            self.vault_y,
+            token_account(self.mint_y, self.escrow, escrow_data.deposited_y),
+        );
+        program_test.add_account(self.escrow, program_account(&escrow_data, self.program_id));
+    }
+
+    /// Escrow data for this swap with correct keys and bumps, in `Initialized` state
+    pub fn escrow_data(&self, size_x: u64, size_y: u64) -> EscrowData {
+        let bump = |prefix: &[u8]| self.pda(prefix, self.pass).1;
+        EscrowData {
+            size_x,
+            size_y,
+            pubkey_alice: self.alice.pubkey(),
+            pubkey_bob: self.bob.pubkey(),
+            pubkey_mint_x: self.mint_x,
+            pubkey_mint_y: self.mint_y,
+            state: escrow::state::EscrowState::Initialized,
+            escrow_bump: bump(b"escrow"),
+            vault_x_bump: bump(b"vault_x"),
+            vault_y_bump: bump(b"vault_y"),
+            pubkey_initiator: self.alice.pubkey(),
+            ..EscrowData::default()
+        }
+    }
+
+    /// `InitEscrow` signed by both parties, which skips the `Accept` handshake
+    pub fn init(
+        &self,
+        payer: &Pubkey,
+        amount_x: u64,
+        amount_y: u64,
+        terms: EscrowTerms,
+    ) -> Instruction {
+        self.init_signed(payer, true, true, amount_x, amount_y, terms)
+    }
+
+    /// `InitEscrow` signed by `initiator` alone, leaving the escrow `Proposed` to the counterparty
+    pub fn open(
+        &self,
+        payer: &Pubkey,
+        initiator: &Keypair,
+        amount_x: u64,
+        amount_y: u64,
+        terms: EscrowTerms,
+    ) -> Instruction {
+        let by_alice = initiator.pubkey() == self.alice.pubkey();
+        self.init_signed(payer, by_alice, !by_alice, amount_x, amount_y, terms)
+    }
+
+    fn init_signed(
+        &self,
+        payer: &Pubkey,
+        alice_signs: bool,
+        bob_signs: bool,
+        amount_x: u64,
+        amount_y: u64,
+        terms: EscrowTerms,
+    ) -> Instruction {
+        instruction(
+            self.program_id,
+            EscrowInstruction::InitEscrow {
+                amount_x,
+                amount_y,
+                pass: self.pass,
+                terms: Box::new(terms),
+            },
+            vec![
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new_readonly(self.mint_x, false),
+                AccountMeta::new_readonly(self.mint_y, false),
+                AccountMeta::new(self.vault_x, false),
+                AccountMeta::new(self.vault_y, false),
+                AccountMeta::new(*payer, true),
+                AccountMeta::new(self.alice.pubkey(), alice_signs),
+                AccountMeta::new(self.bob.pubkey(), bob_signs),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        )
+    }
+
+    /// `Accept` of `amount_x` and `amount_y` by `acceptor`, append the system program and clock for bonded escrows
+    pub fn accept(&self, acceptor: &Keypair, amount_x: u64, amount_y: u64) -> Instruction {
+        instruction(
+            self.program_id,
+            EscrowInstruction::Accept {
+                amount_x,
+                amount_y,
+                pass: self.pass,
+            },
+            vec![
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new(acceptor.pubkey(), true),
+            ],
+        )
+    }
+
+    /// `Deposit` of `amount` by alice into vault x or by bob into vault y
+    pub fn deposit(&self, depositor: &Keypair, amount: u64) -> Instruction {
+        let (token, vault) = if depositor.pubkey() == self.alice.pubkey() {
+            (self.alice_x, self.vault_x)
+        } else {
+            (self.bob_y, self.vault_y)
+        };
+        instruction(
+            self.program_id,
+            EscrowInstruction::Deposit {
+                pass: self.pass,
+                amount,
+            },
+            vec![
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new(token, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new_readonly(depositor.pubkey(), true),
+                AccountMeta::new_readonly(spl_token::id(), false),
+            ],
+        )
+    }
+
+    /// `Withdrawal` by `taker` into `token` out of `vault`
+    pub fn withdrawal(&self, taker: &Keypair, token: Pubkey, vault: Pubkey) -> Instruction {
+        instruction(
+            self.program_id,
+            EscrowInstruction::Withdrawal { pass: self.pass },
+            vec![
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new(token, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new(taker.pubkey(), true),
+                AccountMeta::new_readonly(spl_token::id(), false),
+            ],
+        )
+    }
+
+    /// `MutualRefund` signed by both parties
+    pub fn mutual_refund(&self) -> Instruction {
+        instruction(
+            self.program_id,
+            EscrowInstruction::MutualRefund { pass: self.pass },
+            vec![
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new(self.vault_x, false),
+                AccountMeta::new(self.vault_y, false),
+                AccountMeta::new(self.alice_x, false),
+                AccountMeta::new(self.bob_y, false),
+                AccountMeta::new(self.alice.pubkey(), true),
+                AccountMeta::new(self.bob.pubkey(), true),
+                AccountMeta::new_readonly(spl_token::id(), false),
+            ],
+        )
+    }
+
+    /// Alice's withdrawal of Y once committed
+    pub fn withdraw_alice(&self) -> Instruction {
+        self.withdrawal(&self.alice, self.alice_y, self.vault_y)
+    }
+
+    /// Bob's withdrawal of X once committed
+    pub fn withdraw_bob(&self) -> Instruction {
+        self.withdrawal(&self.bob, self.bob_x, self.vault_x)
+    }
+}
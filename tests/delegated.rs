@@ -0,0 +1,138 @@
+mod common;
+
+use common::*;
+use escrow::{
+    instruction::EscrowInstruction,
+    state::{EscrowData, EscrowState},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program, sysvar,
+};
+
+const SIZE_X: u64 = 1_000;
+const SIZE_Y: u64 = 2_000;
+
+/// A delegated escrow opened by alice
+async fn setup() -> (Context, Swap) {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let mut context = Context::start(program_test).await;
+
+    let init = instruction(
+        program_id,
+        EscrowInstruction::InitDelegatedEscrow {
+            amount_x: SIZE_X,
+            amount_y: SIZE_Y,
+            pass: PASS,
+        },
+        vec![
+            AccountMeta::new(swap.escrow, false),
+            AccountMeta::new_readonly(swap.mint_x, false),
+            AccountMeta::new_readonly(swap.mint_y, false),
+            AccountMeta::new(context.payer(), true),
+            AccountMeta::new_readonly(swap.alice.pubkey(), true),
+            AccountMeta::new_readonly(swap.bob.pubkey(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    );
+    assert!(context.send(&[init], &[&swap.alice]).await);
+    (context, swap)
+}
+
+/// Allowance of `amount` to the escrow account on `owner`'s `token` account
+fn approve(swap: &Swap, token: Pubkey, owner: &Keypair, amount: u64) -> Instruction {
+    spl_token::instruction::approve(
+        &spl_token::id(),
+        &token,
+        &swap.escrow,
+        &owner.pubkey(),
+        &[],
+        amount,
+    )
+    .unwrap()
+}
+
+fn settle(swap: &Swap) -> Instruction {
+    instruction(
+        swap.program_id,
+        EscrowInstruction::SettleDelegated { pass: PASS },
+        vec![
+            AccountMeta::new(swap.escrow, false),
+            AccountMeta::new(swap.alice_x, false),
+            AccountMeta::new(swap.alice_y, false),
+            AccountMeta::new(swap.bob_x, false),
+            AccountMeta::new(swap.bob_y, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_settle_delegated() {
+    let (mut context, swap) = setup().await;
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Proposed);
+    assert!(escrow_data.delegated);
+
+    let alice_allowance = approve(&swap, swap.alice_x, &swap.alice, SIZE_X);
+    assert!(context.send(&[alice_allowance], &[&swap.alice]).await);
+    let bob_allowance = approve(&swap, swap.bob_y, &swap.bob, SIZE_Y);
+    assert!(context.send(&[bob_allowance], &[&swap.bob]).await);
+    // allowances alone do not settle an escrow bob has not accepted
+    assert!(!context.send(&[settle(&swap)], &[]).await);
+
+    let accept = swap.accept(&swap.bob, SIZE_X, SIZE_Y);
+    assert!(context.send(&[accept], &[&swap.bob]).await);
+    assert!(context.send(&[settle(&swap)], &[]).await);
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS - SIZE_X);
+    assert_eq!(context.balance(swap.alice_y).await, FUNDS + SIZE_Y);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS + SIZE_X);
+    assert_eq!(context.balance(swap.bob_y).await, FUNDS - SIZE_Y);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Completed);
+
+    assert!(!context.send(&[settle(&swap)], &[]).await);
+}
+
+#[tokio::test]
+async fn test_settle_requires_both_allowances() {
+    let (mut context, swap) = setup().await;
+    let accept = swap.accept(&swap.bob, SIZE_X, SIZE_Y);
+    assert!(context.send(&[accept], &[&swap.bob]).await);
+
+    let alice_allowance = approve(&swap, swap.alice_x, &swap.alice, SIZE_X);
+    assert!(context.send(&[alice_allowance], &[&swap.alice]).await);
+    assert!(!context.send(&[settle(&swap)], &[]).await);
+    let short_allowance = approve(&swap, swap.bob_y, &swap.bob, SIZE_Y - 1);
+    assert!(context.send(&[short_allowance], &[&swap.bob]).await);
+    assert!(!context.send(&[settle(&swap)], &[]).await);
+
+    // neither leg moved
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS);
+    assert_eq!(context.balance(swap.bob_y).await, FUNDS);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Initialized);
+}
+
+#[tokio::test]
+async fn test_accept_delegated() {
+    let (mut context, swap) = setup().await;
+
+    let by_initiator = swap.accept(&swap.alice, SIZE_X, SIZE_Y);
+    assert!(!context.send(&[by_initiator], &[&swap.alice]).await);
+    let other_amounts = swap.accept(&swap.bob, SIZE_X, SIZE_Y + 1);
+    assert!(!context.send(&[other_amounts], &[&swap.bob]).await);
+
+    let accept = swap.accept(&swap.bob, SIZE_X, SIZE_Y);
+    assert!(context.send(&[accept], &[&swap.bob]).await);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Initialized);
+    let again = swap.accept(&swap.bob, SIZE_X, SIZE_Y);
+    assert!(!context.send(&[again], &[&swap.bob]).await);
+}
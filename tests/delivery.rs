@@ -0,0 +1,169 @@
+mod common;
+
+use common::*;
+use escrow::{instruction::EscrowInstruction, state::DeliveryData};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program, sysvar,
+};
+
+const AMOUNT: u64 = 1_000;
+
+/// Alice pays bob in Y once the attestor confirms the delivery
+struct Setup {
+    swap: Swap,
+    attestor: Keypair,
+    delivery: Pubkey,
+    vault: Pubkey,
+}
+
+fn setup() -> (ProgramTest, Setup) {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    add_ed25519_program(&mut program_test);
+    let swap = Swap::new(&mut program_test, program_id);
+    let delivery = pda(
+        &program_id,
+        &[
+            b"delivery",
+            swap.alice.pubkey().as_ref(),
+            swap.bob.pubkey().as_ref(),
+            swap.mint_y.as_ref(),
+            PASS.as_ref(),
+        ],
+    );
+    let vault = pda(&program_id, &[b"vault", delivery.as_ref()]);
+    (
+        program_test,
+        Setup {
+            swap,
+            attestor: Keypair::new(),
+            delivery,
+            vault,
+        },
+    )
+}
+
+fn init(setup: &Setup, amount: u64, attestor: Pubkey, deadline: i64) -> Instruction {
+    let swap = &setup.swap;
+    instruction(
+        swap.program_id,
+        EscrowInstruction::InitDelivery {
+            amount,
+            attestor,
+            deadline,
+            pass: PASS,
+        },
+        vec![
+            AccountMeta::new(setup.delivery, false),
+            AccountMeta::new_readonly(swap.mint_y, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(swap.alice.pubkey(), true),
+            AccountMeta::new(swap.alice_y, false),
+            AccountMeta::new_readonly(swap.bob.pubkey(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Started with alice's payment locked until `deadline`
+async fn start(deadline: i64) -> (Context, Setup) {
+    let (program_test, setup) = setup();
+    let mut context = Context::start(program_test).await;
+    let init = init(&setup, AMOUNT, setup.attestor.pubkey(), deadline);
+    assert!(context.send(&[init], &[&setup.swap.alice]).await);
+    (context, setup)
+}
+
+fn confirm(setup: &Setup) -> Instruction {
+    instruction(
+        setup.swap.program_id,
+        EscrowInstruction::ConfirmDelivery { pass: PASS },
+        vec![
+            AccountMeta::new(setup.delivery, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(setup.swap.bob_y, false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+/// `ConfirmDelivery` preceded by `signer`'s signature of the attestation message for `delivery`
+fn attested_confirm(setup: &Setup, signer: &Keypair, delivery: &Pubkey) -> [Instruction; 2] {
+    let message = DeliveryData::attestation_message(delivery);
+    [ed25519_instruction(signer, &message), confirm(setup)]
+}
+
+fn refund(setup: &Setup, token: Pubkey) -> Instruction {
+    instruction(
+        setup.swap.program_id,
+        EscrowInstruction::RefundDelivery { pass: PASS },
+        vec![
+            AccountMeta::new(setup.delivery, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(token, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_confirm_delivery() {
+    let (mut context, setup) = start(now() + 3_600).await;
+    let (swap, attestor) = (&setup.swap, &setup.attestor);
+    assert_eq!(context.balance(setup.vault).await, AMOUNT);
+    assert_eq!(context.balance(swap.alice_y).await, FUNDS - AMOUNT);
+
+    assert!(!context.send(&[confirm(&setup)], &[]).await);
+    let by_bob = attested_confirm(&setup, &swap.bob, &setup.delivery);
+    assert!(!context.send(&by_bob, &[]).await);
+    let other_delivery = attested_confirm(&setup, attestor, &Pubkey::new_unique());
+    assert!(!context.send(&other_delivery, &[]).await);
+    assert!(!context.send(&[refund(&setup, swap.alice_y)], &[]).await);
+
+    let attested = attested_confirm(&setup, attestor, &setup.delivery);
+    assert!(context.send(&attested, &[]).await);
+    assert_eq!(context.balance(swap.bob_y).await, FUNDS + AMOUNT);
+    assert_eq!(context.balance(setup.vault).await, 0);
+    let delivery_data: DeliveryData = context.read(setup.delivery).await;
+    assert!(delivery_data.is_settled);
+
+    let again = attested_confirm(&setup, attestor, &setup.delivery);
+    assert!(!context.send(&again, &[]).await);
+}
+
+#[tokio::test]
+async fn test_refund_delivery() {
+    let (mut context, setup) = start(now() - 3_600).await;
+    let swap = &setup.swap;
+
+    let late = attested_confirm(&setup, &setup.attestor, &setup.delivery);
+    assert!(!context.send(&late, &[]).await);
+    assert!(!context.send(&[refund(&setup, swap.bob_y)], &[]).await);
+
+    assert!(context.send(&[refund(&setup, swap.alice_y)], &[]).await);
+    assert_eq!(context.balance(swap.alice_y).await, FUNDS);
+    assert_eq!(context.balance(setup.vault).await, 0);
+    assert!(!context.send(&[refund(&setup, swap.alice_y)], &[]).await);
+}
+
+#[tokio::test]
+async fn test_init_rejects_invalid_deliveries() {
+    let (program_test, setup) = setup();
+    let mut context = Context::start(program_test).await;
+    let attestor = setup.attestor.pubkey();
+
+    for (amount, attestor) in [(0, attestor), (AMOUNT, Pubkey::default())].iter() {
+        let init = init(&setup, *amount, *attestor, now() + 3_600);
+        assert!(!context.send(&[init], &[&setup.swap.alice]).await);
+    }
+    assert!(context.get_account(setup.delivery).await.is_none());
+}
@@ -0,0 +1,273 @@
+mod common;
+
+use common::*;
+use escrow::{instruction::EscrowInstruction, state::DistributorData};
+use solana_program::keccak;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program, sysvar,
+};
+
+const TOTAL: u64 = 600;
+
+/// Alice distributes X to bob, carol and dave, owed 300, 200 and 100
+struct Setup {
+    swap: Swap,
+    carol_x: Pubkey,
+    distributor: Pubkey,
+    vault: Pubkey,
+    bitmap: Pubkey,
+    tree: Tree,
+}
+
+/// A three leaf Merkle tree, `root = pair(pair(leaf 0, leaf 1), leaf 2)`
+struct Tree {
+    leaves: [[u8; 32]; 3],
+    root: [u8; 32],
+}
+
+fn pair(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+    if a <= b {
+        keccak::hashv(&[&a, &b]).0
+    } else {
+        keccak::hashv(&[&b, &a]).0
+    }
+}
+
+impl Tree {
+    fn new(owed: [(Pubkey, u64); 3]) -> Self {
+        let mut leaves = [[0; 32]; 3];
+        for (index, (recipient, amount)) in owed.iter().enumerate() {
+            leaves[index] = DistributorData::leaf(index as u32, recipient, *amount);
+        }
+        let root = pair(pair(leaves[0], leaves[1]), leaves[2]);
+        Tree { leaves, root }
+    }
+
+    fn proof(&self, index: usize) -> Vec<[u8; 32]> {
+        match index {
+            0 => vec![self.leaves[1], self.leaves[2]],
+            1 => vec![self.leaves[0], self.leaves[2]],
+            _ => vec![pair(self.leaves[0], self.leaves[1])],
+        }
+    }
+}
+
+fn distributor_data(tree: &Tree) -> DistributorData {
+    DistributorData {
+        is_initialized: true,
+        is_reclaimed: false,
+        pubkey_depositor: Pubkey::default(),
+        pubkey_mint: Pubkey::default(),
+        merkle_root: tree.root,
+        leaf_count: 3,
+        total: TOTAL,
+        claimed: 0,
+        expiry: 0,
+        escrow_bump: 0,
+        vault_bump: 0,
+        bitmap_bump: 0,
+    }
+}
+
+fn setup() -> (ProgramTest, Setup) {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let carol = Pubkey::new_unique();
+    let carol_x = Pubkey::new_unique();
+    program_test.add_account(carol_x, token_account(swap.mint_x, carol, 0));
+    let dave = Pubkey::new_unique();
+    let tree = Tree::new([(swap.bob.pubkey(), 300), (carol, 200), (dave, 100)]);
+    let distributor = pda(
+        &program_id,
+        &[
+            b"distributor",
+            swap.alice.pubkey().as_ref(),
+            swap.mint_x.as_ref(),
+            PASS.as_ref(),
+        ],
+    );
+    let vault = pda(&program_id, &[b"vault", distributor.as_ref()]);
+    let bitmap = pda(&program_id, &[b"bitmap", distributor.as_ref()]);
+    (
+        program_test,
+        Setup {
+            swap,
+            carol_x,
+            distributor,
+            vault,
+            bitmap,
+            tree,
+        },
+    )
+}
+
+/// Started with alice's distribution funded, expiring at `expiry`
+async fn start(expiry: i64) -> (Context, Setup) {
+    let (program_test, setup) = setup();
+    let swap = &setup.swap;
+    let mut context = Context::start(program_test).await;
+
+    let init = instruction(
+        swap.program_id,
+        EscrowInstruction::InitDistributor {
+            merkle_root: setup.tree.root,
+            leaf_count: 3,
+            total: TOTAL,
+            expiry,
+            pass: PASS,
+        },
+        vec![
+            AccountMeta::new(setup.distributor, false),
+            AccountMeta::new_readonly(swap.mint_x, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(setup.bitmap, false),
+            AccountMeta::new(swap.alice.pubkey(), true),
+            AccountMeta::new(swap.alice_x, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    );
+    assert!(context.send(&[init], &[&swap.alice]).await);
+    (context, setup)
+}
+
+fn claim(setup: &Setup, index: u32, amount: u64, token: Pubkey) -> Instruction {
+    instruction(
+        setup.swap.program_id,
+        EscrowInstruction::ClaimDistribution {
+            index,
+            amount,
+            proof: setup.tree.proof(index as usize),
+            pass: PASS,
+        },
+        vec![
+            AccountMeta::new(setup.distributor, false),
+            AccountMeta::new(setup.bitmap, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(token, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+fn reclaim(setup: &Setup, depositor: &Keypair) -> Instruction {
+    let swap = &setup.swap;
+    instruction(
+        swap.program_id,
+        EscrowInstruction::ReclaimDistribution { pass: PASS },
+        vec![
+            AccountMeta::new(setup.distributor, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(swap.alice_x, false),
+            AccountMeta::new_readonly(depositor.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+#[test]
+fn test_verify_proof() {
+    let (bob, carol, dave) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let tree = Tree::new([(bob, 300), (carol, 200), (dave, 100)]);
+    let distributor_data = distributor_data(&tree);
+
+    for (index, leaf) in tree.leaves.iter().enumerate() {
+        assert!(distributor_data.verify_proof(&tree.proof(index), *leaf));
+    }
+    let inflated = DistributorData::leaf(0, &bob, 301);
+    assert!(!distributor_data.verify_proof(&tree.proof(0), inflated));
+    let moved = DistributorData::leaf(1, &bob, 300);
+    assert!(!distributor_data.verify_proof(&tree.proof(1), moved));
+    assert!(!distributor_data.verify_proof(&tree.proof(1), tree.leaves[0]));
+    assert!(!distributor_data.verify_proof(&[], tree.leaves[0]));
+
+    // a single leaf is its own root
+    let single = DistributorData {
+        merkle_root: tree.leaves[2],
+        ..distributor_data
+    };
+    assert!(single.verify_proof(&[], tree.leaves[2]));
+}
+
+#[test]
+fn test_bitmap_len() {
+    let tree = Tree::new([(Pubkey::default(), 0); 3]);
+    let bitmap_len = |leaf_count| {
+        DistributorData {
+            leaf_count,
+            ..distributor_data(&tree)
+        }
+        .bitmap_len()
+    };
+    assert_eq!(bitmap_len(1), 1);
+    assert_eq!(bitmap_len(8), 1);
+    assert_eq!(bitmap_len(9), 2);
+    assert_eq!(bitmap_len(u32::MAX), 1 << 29);
+}
+
+#[tokio::test]
+async fn test_claim_distribution() {
+    let (mut context, setup) = start(now() + 3_600).await;
+    let swap = &setup.swap;
+    assert_eq!(context.balance(setup.vault).await, TOTAL);
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS - TOTAL);
+
+    let inflated = claim(&setup, 0, 301, swap.bob_x);
+    assert!(!context.send(&[inflated], &[]).await);
+    let to_carol = claim(&setup, 0, 300, setup.carol_x);
+    assert!(!context.send(&[to_carol], &[]).await);
+    let out_of_range = claim(&setup, 3, 300, swap.bob_x);
+    assert!(!context.send(&[out_of_range], &[]).await);
+
+    let bob_claim = claim(&setup, 0, 300, swap.bob_x);
+    let carol_claim = claim(&setup, 1, 200, setup.carol_x);
+    let claimed_twice = bob_claim.clone();
+    assert!(context.send(&[bob_claim], &[]).await);
+    assert!(context.send(&[carol_claim], &[]).await);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS + 300);
+    assert_eq!(context.balance(setup.carol_x).await, 200);
+    assert_eq!(context.balance(setup.vault).await, 100);
+
+    // the bitmap remembers both claims
+    let bitmap = context.get_account(setup.bitmap).await.unwrap();
+    assert_eq!(bitmap.data, vec![0b011]);
+    assert!(!context.send(&[claimed_twice], &[]).await);
+    let distributor_data: DistributorData = context.read(setup.distributor).await;
+    assert_eq!(distributor_data.claimed, 500);
+
+    let early = reclaim(&setup, &swap.alice);
+    assert!(!context.send(&[early], &[&swap.alice]).await);
+}
+
+#[tokio::test]
+async fn test_reclaim_distribution() {
+    let (mut context, setup) = start(now() - 3_600).await;
+    let swap = &setup.swap;
+
+    let expired = claim(&setup, 0, 300, swap.bob_x);
+    assert!(!context.send(&[expired], &[]).await);
+    let by_bob = reclaim(&setup, &swap.bob);
+    assert!(!context.send(&[by_bob], &[&swap.bob]).await);
+
+    let by_alice = reclaim(&setup, &swap.alice);
+    assert!(context.send(&[by_alice], &[&swap.alice]).await);
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS);
+    assert_eq!(context.balance(setup.vault).await, 0);
+    let distributor_data: DistributorData = context.read(setup.distributor).await;
+    assert!(distributor_data.is_reclaimed);
+
+    let again = reclaim(&setup, &swap.alice);
+    assert!(!context.send(&[again], &[&swap.alice]).await);
+}
@@ -0,0 +1,221 @@
+mod common;
+
+use common::*;
+use escrow::{
+    instruction::EscrowInstruction,
+    state::{dutch_price, DutchAuctionData, DutchAuctionTerms},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program, sysvar,
+};
+
+const AMOUNT_X: u64 = 1_000;
+const TERMS: DutchAuctionTerms = DutchAuctionTerms {
+    amount_x: AMOUNT_X,
+    start_price: 1_000,
+    end_price: 0,
+    start_slot: 1_000,
+    end_slot: 2_000,
+};
+
+/// Alice sells X, bob buys with Y
+struct Setup {
+    swap: Swap,
+    auction: Pubkey,
+    vault: Pubkey,
+}
+
+fn setup() -> (ProgramTest, Setup) {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let auction = pda(
+        &program_id,
+        &[
+            b"dutch_auction",
+            swap.alice.pubkey().as_ref(),
+            swap.mint_x.as_ref(),
+            swap.mint_y.as_ref(),
+            PASS.as_ref(),
+        ],
+    );
+    let vault = pda(&program_id, &[b"vault", auction.as_ref()]);
+    (
+        program_test,
+        Setup {
+            swap,
+            auction,
+            vault,
+        },
+    )
+}
+
+/// Started with alice's auction initialized along `terms`
+async fn start(terms: DutchAuctionTerms) -> (Context, Setup) {
+    let (program_test, setup) = setup();
+    let mut context = Context::start(program_test).await;
+    let init = init(&setup, terms);
+    assert!(context.send(&[init], &[&setup.swap.alice]).await);
+    (context, setup)
+}
+
+fn init(setup: &Setup, terms: DutchAuctionTerms) -> Instruction {
+    let swap = &setup.swap;
+    instruction(
+        swap.program_id,
+        EscrowInstruction::InitDutchAuction { terms, pass: PASS },
+        vec![
+            AccountMeta::new(setup.auction, false),
+            AccountMeta::new_readonly(swap.mint_x, false),
+            AccountMeta::new_readonly(swap.mint_y, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(swap.alice.pubkey(), true),
+            AccountMeta::new(swap.alice_x, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+fn buy(setup: &Setup, max_price: u64) -> Instruction {
+    let swap = &setup.swap;
+    instruction(
+        swap.program_id,
+        EscrowInstruction::BuyDutch {
+            max_price,
+            pass: PASS,
+        },
+        vec![
+            AccountMeta::new(setup.auction, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(swap.bob_y, false),
+            AccountMeta::new(swap.alice_y, false),
+            AccountMeta::new(swap.bob_x, false),
+            AccountMeta::new_readonly(swap.bob.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+fn cancel(setup: &Setup, seller: &Keypair, token: Pubkey) -> Instruction {
+    instruction(
+        setup.swap.program_id,
+        EscrowInstruction::CancelDutchAuction { pass: PASS },
+        vec![
+            AccountMeta::new(setup.auction, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(token, false),
+            AccountMeta::new_readonly(seller.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+#[test]
+fn test_dutch_price() {
+    let falling = |slot| dutch_price(1_000, 0, 100, 200, slot);
+    assert_eq!(falling(0), 1_000);
+    assert_eq!(falling(100), 1_000);
+    assert_eq!(falling(101), 990);
+    assert_eq!(falling(150), 500);
+    assert_eq!(falling(199), 10);
+    assert_eq!(falling(200), 0);
+    assert_eq!(falling(u64::MAX), 0);
+
+    let rising = |slot| dutch_price(100, 200, 0, 10, slot);
+    assert_eq!(rising(0), 100);
+    assert_eq!(rising(5), 150);
+    assert_eq!(rising(10), 200);
+
+    // an empty or inverted range jumps to the end price after the start
+    for end_slot in [100, 50].iter() {
+        assert_eq!(dutch_price(1_000, 0, 100, *end_slot, 100), 1_000);
+        assert_eq!(dutch_price(1_000, 0, 100, *end_slot, 101), 0);
+    }
+
+    let extreme = dutch_price(u64::MAX, 0, 0, u64::MAX, u64::MAX / 2);
+    assert_eq!(extreme, u64::MAX - u64::MAX / 2);
+}
+
+#[tokio::test]
+async fn test_buy_dutch() {
+    let (mut context, setup) = start(TERMS).await;
+    let swap = &setup.swap;
+    assert_eq!(context.balance(setup.vault).await, AMOUNT_X);
+
+    assert!(!context.send(&[buy(&setup, u64::MAX)], &[&swap.bob]).await);
+
+    context.warp_to_slot(1_500);
+    assert!(!context.send(&[buy(&setup, 499)], &[&swap.bob]).await);
+    assert!(context.send(&[buy(&setup, 500)], &[&swap.bob]).await);
+    assert_eq!(context.balance(swap.alice_y).await, FUNDS + 500);
+    assert_eq!(context.balance(swap.bob_y).await, FUNDS - 500);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS + AMOUNT_X);
+    assert_eq!(context.balance(setup.vault).await, 0);
+    let auction_data: DutchAuctionData = context.read(setup.auction).await;
+    assert!(auction_data.is_closed);
+
+    assert!(!context.send(&[buy(&setup, u64::MAX)], &[&swap.bob]).await);
+    let cancel = cancel(&setup, &swap.alice, swap.alice_x);
+    assert!(!context.send(&[cancel], &[&swap.alice]).await);
+}
+
+#[tokio::test]
+async fn test_buy_at_end_price() {
+    let terms = DutchAuctionTerms {
+        end_price: 100,
+        ..TERMS
+    };
+    let (mut context, setup) = start(terms).await;
+    let swap = &setup.swap;
+
+    context.warp_to_slot(5_000);
+    assert!(context.send(&[buy(&setup, u64::MAX)], &[&swap.bob]).await);
+    assert_eq!(context.balance(swap.alice_y).await, FUNDS + 100);
+}
+
+#[tokio::test]
+async fn test_cancel_dutch_auction() {
+    let (mut context, setup) = start(TERMS).await;
+    let swap = &setup.swap;
+
+    let by_bob = cancel(&setup, &swap.bob, swap.bob_x);
+    assert!(!context.send(&[by_bob], &[&swap.bob]).await);
+    let by_alice = cancel(&setup, &swap.alice, swap.alice_x);
+    assert!(context.send(&[by_alice], &[&swap.alice]).await);
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS);
+    assert_eq!(context.balance(setup.vault).await, 0);
+
+    context.warp_to_slot(1_500);
+    assert!(!context.send(&[buy(&setup, u64::MAX)], &[&swap.bob]).await);
+}
+
+#[tokio::test]
+async fn test_init_rejects_invalid_terms() {
+    let invalid = [
+        DutchAuctionTerms {
+            amount_x: 0,
+            ..TERMS
+        },
+        DutchAuctionTerms {
+            end_price: TERMS.start_price + 1,
+            ..TERMS
+        },
+        DutchAuctionTerms {
+            end_slot: TERMS.start_slot,
+            ..TERMS
+        },
+    ];
+    let (program_test, setup) = setup();
+    let mut context = Context::start(program_test).await;
+    for terms in invalid.iter() {
+        let init = init(&setup, *terms);
+        assert!(!context.send(&[init], &[&setup.swap.alice]).await);
+    }
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use common::*;
+use escrow::state::{EscrowData, EscrowState, EscrowTerms, VestingSchedule};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    sysvar,
+};
+
+const SIZE_X: u64 = 1_000;
+const SIZE_Y: u64 = 2_000;
+
+/// Forwards read the clock on every withdrawal
+fn with_clock(mut instruction: Instruction) -> Instruction {
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(sysvar::clock::id(), false));
+    instruction
+}
+
+/// A forward settling after `settle_after`, with alice's leg deposited
+async fn forward(settle_after: i64) -> (Context, Swap) {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let mut context = Context::start(program_test).await;
+
+    let terms = EscrowTerms {
+        settle_after,
+        ..EscrowTerms::default()
+    };
+    let init = swap.init(&context.payer(), SIZE_X, SIZE_Y, terms);
+    let deposit = swap.deposit(&swap.alice, SIZE_X);
+    let parties = [&swap.alice, &swap.bob];
+    assert!(context.send(&[init, deposit], &parties).await);
+    (context, swap)
+}
+
+#[tokio::test]
+async fn test_forward_waits_for_settle_after() {
+    let (mut context, swap) = forward(now() + 3_600).await;
+
+    let refund = with_clock(swap.withdrawal(&swap.alice, swap.alice_x, swap.vault_x));
+    assert!(!context.send(&[refund], &[&swap.alice]).await);
+
+    let deposit = swap.deposit(&swap.bob, SIZE_Y);
+    assert!(context.send(&[deposit], &[&swap.bob]).await);
+    let withdrawal = with_clock(swap.withdraw_alice());
+    assert!(!context.send(&[withdrawal], &[&swap.alice]).await);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Committed);
+}
+
+#[tokio::test]
+async fn test_forward_settles_after_settle_after() {
+    let (mut context, swap) = forward(now() - 3_600).await;
+    let deposit = swap.deposit(&swap.bob, SIZE_Y);
+    assert!(context.send(&[deposit], &[&swap.bob]).await);
+
+    assert!(!context.send(&[swap.withdraw_alice()], &[&swap.alice]).await);
+    let alice_withdrawal = with_clock(swap.withdraw_alice());
+    assert!(context.send(&[alice_withdrawal], &[&swap.alice]).await);
+    let bob_withdrawal = with_clock(swap.withdraw_bob());
+    assert!(context.send(&[bob_withdrawal], &[&swap.bob]).await);
+
+    assert_eq!(context.balance(swap.alice_y).await, FUNDS + SIZE_Y);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS + SIZE_X);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Completed);
+}
+
+#[tokio::test]
+async fn test_mutual_refund() {
+    let (mut context, swap) = forward(now() + 3_600).await;
+    let deposit = swap.deposit(&swap.bob, SIZE_Y);
+    assert!(context.send(&[deposit], &[&swap.bob]).await);
+
+    let mut alice_alone = swap.mutual_refund();
+    alice_alone.accounts[6].is_signer = false;
+    assert!(!context.send(&[alice_alone], &[&swap.alice]).await);
+
+    let parties = [&swap.alice, &swap.bob];
+    assert!(context.send(&[swap.mutual_refund()], &parties).await);
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS);
+    assert_eq!(context.balance(swap.bob_y).await, FUNDS);
+    assert_eq!(context.balance(swap.vault_x).await, 0);
+    assert_eq!(context.balance(swap.vault_y).await, 0);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Initialized);
+    assert_eq!(escrow_data.deposited_x, 0);
+    assert_eq!(escrow_data.deposited_y, 0);
+
+    // the refunded escrow can be funded again
+    let deposit = swap.deposit(&swap.alice, SIZE_X);
+    assert!(context.send(&[deposit], &[&swap.alice]).await);
+}
+
+#[tokio::test]
+async fn test_init_rejects_invalid_forwards() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let mut context = Context::start(program_test).await;
+
+    let vesting = EscrowTerms {
+        settle_after: now(),
+        vesting: Some(VestingSchedule {
+            start_ts: 10,
+            cliff_ts: 10,
+            end_ts: 20,
+        }),
+        ..EscrowTerms::default()
+    };
+    let negative = EscrowTerms {
+        settle_after: -1,
+        ..EscrowTerms::default()
+    };
+    for (terms, size_y) in [(negative, SIZE_Y), (vesting, 0)] {
+        let init = swap.init(&context.payer(), SIZE_X, size_y, terms);
+        assert!(!context.send(&[init], &[&swap.alice, &swap.bob]).await);
+    }
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::*;
+use escrow::state::{EscrowData, EscrowState, EscrowTerms};
+use solana_program_test::*;
+use solana_sdk::{pubkey::Pubkey, signature::Signer};
+
+const SIZE_X: u64 = 1_000;
+const SIZE_Y: u64 = 2_000;
+
+async fn setup() -> (Context, Swap) {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    (Context::start(program_test).await, swap)
+}
+
+#[tokio::test]
+async fn test_open_by_bob_and_accept() {
+    let (mut context, swap) = setup().await;
+
+    let open = swap.open(
+        &context.payer(),
+        &swap.bob,
+        SIZE_X,
+        SIZE_Y,
+        EscrowTerms::default(),
+    );
+    assert!(context.send(&[open], &[&swap.bob]).await);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Proposed);
+    assert_eq!(escrow_data.pubkey_initiator, swap.bob.pubkey());
+
+    let deposit = swap.deposit(&swap.bob, SIZE_Y);
+    assert!(!context.send(&[deposit], &[&swap.bob]).await);
+    let by_initiator = swap.accept(&swap.bob, SIZE_X, SIZE_Y);
+    assert!(!context.send(&[by_initiator], &[&swap.bob]).await);
+    let other_amounts = swap.accept(&swap.alice, SIZE_X - 1, SIZE_Y);
+    assert!(!context.send(&[other_amounts], &[&swap.alice]).await);
+
+    let accept = swap.accept(&swap.alice, SIZE_X, SIZE_Y);
+    assert!(context.send(&[accept], &[&swap.alice]).await);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Initialized);
+
+    let alice_deposit = swap.deposit(&swap.alice, SIZE_X);
+    let bob_deposit = swap.deposit(&swap.bob, SIZE_Y);
+    let parties = [&swap.alice, &swap.bob];
+    assert!(context.send(&[alice_deposit, bob_deposit], &parties).await);
+    assert!(context.send(&[swap.withdraw_alice()], &[&swap.alice]).await);
+    assert!(context.send(&[swap.withdraw_bob()], &[&swap.bob]).await);
+    assert_eq!(context.balance(swap.alice_y).await, FUNDS + SIZE_Y);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS + SIZE_X);
+}
+
+#[tokio::test]
+async fn test_open_requires_a_signer() {
+    let (mut context, swap) = setup().await;
+
+    let mut unsigned = swap.open(
+        &context.payer(),
+        &swap.alice,
+        SIZE_X,
+        SIZE_Y,
+        EscrowTerms::default(),
+    );
+    unsigned.accounts[6].is_signer = false;
+    assert!(!context.send(&[unsigned], &[]).await);
+    assert!(context.get_account(swap.escrow).await.is_none());
+}
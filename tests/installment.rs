@@ -0,0 +1,74 @@
+mod common;
+
+use common::*;
+use escrow::state::{EscrowData, EscrowState, EscrowTerms};
+use solana_program_test::*;
+use solana_sdk::pubkey::Pubkey;
+
+const SIZE_X: u64 = 1_000;
+const SIZE_Y: u64 = 2_000;
+
+async fn setup() -> (Context, Swap) {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let mut context = Context::start(program_test).await;
+
+    let init = swap.init(&context.payer(), SIZE_X, SIZE_Y, EscrowTerms::default());
+    assert!(context.send(&[init], &[&swap.alice, &swap.bob]).await);
+    (context, swap)
+}
+
+#[tokio::test]
+async fn test_installments_fund_a_leg() {
+    let (mut context, swap) = setup().await;
+
+    for installment in [400, 500].iter() {
+        let deposit = swap.deposit(&swap.alice, *installment);
+        assert!(context.send(&[deposit], &[&swap.alice]).await);
+    }
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Initialized);
+    assert_eq!(escrow_data.deposited_x, 900);
+    assert_eq!(context.balance(swap.vault_x).await, 900);
+
+    let zero = swap.deposit(&swap.alice, 0);
+    assert!(!context.send(&[zero], &[&swap.alice]).await);
+    let excessive = swap.deposit(&swap.alice, 101);
+    assert!(!context.send(&[excessive], &[&swap.alice]).await);
+    let last = swap.deposit(&swap.alice, 100);
+    assert!(context.send(&[last], &[&swap.alice]).await);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::DepositAlice);
+
+    let half = swap.deposit(&swap.bob, SIZE_Y / 2);
+    assert!(context.send(&[half], &[&swap.bob]).await);
+    assert!(!context.send(&[swap.withdraw_alice()], &[&swap.alice]).await);
+    let other_half = swap.deposit(&swap.bob, SIZE_Y / 2);
+    assert!(context.send(&[other_half], &[&swap.bob]).await);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Committed);
+
+    assert!(context.send(&[swap.withdraw_alice()], &[&swap.alice]).await);
+    assert!(context.send(&[swap.withdraw_bob()], &[&swap.bob]).await);
+    assert_eq!(context.balance(swap.alice_y).await, FUNDS + SIZE_Y);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS + SIZE_X);
+}
+
+#[tokio::test]
+async fn test_refund_partial_leg() {
+    let (mut context, swap) = setup().await;
+    let deposit = swap.deposit(&swap.bob, 700);
+    assert!(context.send(&[deposit], &[&swap.bob]).await);
+
+    let refund = swap.withdrawal(&swap.bob, swap.bob_y, swap.vault_y);
+    assert!(context.send(&[refund], &[&swap.bob]).await);
+    assert_eq!(context.balance(swap.bob_y).await, FUNDS);
+    assert_eq!(context.balance(swap.vault_y).await, 0);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Initialized);
+    assert_eq!(escrow_data.deposited_y, 0);
+
+    let nothing_left = swap.withdrawal(&swap.bob, swap.bob_y, swap.vault_y);
+    assert!(!context.send(&[nothing_left], &[&swap.bob]).await);
+}
@@ -0,0 +1,199 @@
+mod common;
+
+use common::*;
+use escrow::{
+    instruction::EscrowInstruction,
+    state::{EscrowData, EscrowState, EscrowTerms, LoanTerms},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    sysvar,
+};
+
+const COLLATERAL: u64 = 2_000;
+const PRINCIPAL: u64 = 1_000;
+const INTEREST_BPS: u16 = 500;
+const REPAYMENT: u64 = 1_050;
+
+fn fund(swap: &Swap) -> Instruction {
+    instruction(
+        swap.program_id,
+        EscrowInstruction::Fund { pass: PASS },
+        vec![
+            AccountMeta::new(swap.escrow, false),
+            AccountMeta::new(swap.bob_y, false),
+            AccountMeta::new(swap.alice_y, false),
+            AccountMeta::new_readonly(swap.bob.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+fn repay(swap: &Swap) -> Instruction {
+    instruction(
+        swap.program_id,
+        EscrowInstruction::Repay { pass: PASS },
+        vec![
+            AccountMeta::new(swap.escrow, false),
+            AccountMeta::new(swap.alice_y, false),
+            AccountMeta::new(swap.bob_y, false),
+            AccountMeta::new(swap.vault_x, false),
+            AccountMeta::new(swap.alice_x, false),
+            AccountMeta::new_readonly(swap.alice.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+fn liquidate(swap: &Swap, lender: &Keypair, token: Pubkey) -> Instruction {
+    instruction(
+        swap.program_id,
+        EscrowInstruction::Liquidate { pass: PASS },
+        vec![
+            AccountMeta::new(swap.escrow, false),
+            AccountMeta::new(swap.vault_x, false),
+            AccountMeta::new(token, false),
+            AccountMeta::new_readonly(lender.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+fn loan_terms(due_ts: i64) -> EscrowTerms {
+    EscrowTerms {
+        loan: Some(LoanTerms {
+            interest_bps: INTEREST_BPS,
+            due_ts,
+        }),
+        ..EscrowTerms::default()
+    }
+}
+
+#[test]
+fn test_repayment_amount() {
+    let terms = LoanTerms {
+        interest_bps: INTEREST_BPS,
+        due_ts: 1,
+    };
+    assert_eq!(terms.repayment_amount(PRINCIPAL), Some(REPAYMENT));
+    assert_eq!(terms.repayment_amount(0), Some(0));
+    // interest rounds down in favour of the borrower
+    assert_eq!(terms.repayment_amount(19), Some(19));
+    assert_eq!(terms.repayment_amount(u64::MAX), None);
+
+    let free = LoanTerms {
+        interest_bps: 0,
+        ..terms
+    };
+    assert_eq!(free.repayment_amount(u64::MAX), Some(u64::MAX));
+}
+
+#[tokio::test]
+async fn test_fund_and_repay() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let mut context = Context::start(program_test).await;
+
+    let terms = loan_terms(now() + 3_600);
+    let init = swap.init(&context.payer(), COLLATERAL, PRINCIPAL, terms);
+    let collateral = swap.deposit(&swap.alice, COLLATERAL);
+    let parties = [&swap.alice, &swap.bob];
+    assert!(context.send(&[init, collateral], &parties).await);
+
+    assert!(!context.send(&[repay(&swap)], &[&swap.alice]).await);
+    assert!(context.send(&[fund(&swap)], &[&swap.bob]).await);
+    assert_eq!(context.balance(swap.alice_y).await, FUNDS + PRINCIPAL);
+    assert_eq!(context.balance(swap.bob_y).await, FUNDS - PRINCIPAL);
+    assert!(!context.send(&[fund(&swap)], &[&swap.bob]).await);
+
+    let early = liquidate(&swap, &swap.bob, swap.bob_x);
+    assert!(!context.send(&[early], &[&swap.bob]).await);
+
+    assert!(context.send(&[repay(&swap)], &[&swap.alice]).await);
+    assert_eq!(
+        context.balance(swap.alice_y).await,
+        FUNDS + PRINCIPAL - REPAYMENT
+    );
+    assert_eq!(
+        context.balance(swap.bob_y).await,
+        FUNDS - PRINCIPAL + REPAYMENT
+    );
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS);
+    assert_eq!(context.balance(swap.vault_x).await, 0);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Completed);
+}
+
+#[tokio::test]
+async fn test_fund_past_due() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let mut context = Context::start(program_test).await;
+
+    let terms = loan_terms(now() - 3_600);
+    let init = swap.init(&context.payer(), COLLATERAL, PRINCIPAL, terms);
+    let collateral = swap.deposit(&swap.alice, COLLATERAL);
+    let parties = [&swap.alice, &swap.bob];
+    assert!(context.send(&[init, collateral], &parties).await);
+
+    assert!(!context.send(&[fund(&swap)], &[&swap.bob]).await);
+    assert_eq!(context.balance(swap.alice_y).await, FUNDS);
+}
+
+#[tokio::test]
+async fn test_liquidate_overdue() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let mut escrow_data = swap.escrow_data(COLLATERAL, PRINCIPAL);
+    escrow_data.state = EscrowState::Funded;
+    escrow_data.deposited_x = COLLATERAL;
+    escrow_data.loan = LoanTerms {
+        interest_bps: INTEREST_BPS,
+        due_ts: now() - 1,
+    };
+    swap.add_escrow(&mut program_test, escrow_data);
+    let mut context = Context::start(program_test).await;
+
+    assert!(!context.send(&[repay(&swap)], &[&swap.alice]).await);
+    let by_alice = liquidate(&swap, &swap.alice, swap.alice_x);
+    assert!(!context.send(&[by_alice], &[&swap.alice]).await);
+
+    let by_bob = liquidate(&swap, &swap.bob, swap.bob_x);
+    assert!(context.send(&[by_bob], &[&swap.bob]).await);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS + COLLATERAL);
+    assert_eq!(context.balance(swap.vault_x).await, 0);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Completed);
+}
+
+#[tokio::test]
+async fn test_init_rejects_invalid_loans() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let mut context = Context::start(program_test).await;
+
+    let with_approver = EscrowTerms {
+        approvers: vec![Pubkey::new_unique()],
+        threshold: 1,
+        ..loan_terms(now() + 3_600)
+    };
+    let invalid = vec![
+        (loan_terms(0), PRINCIPAL),
+        (loan_terms(now() + 3_600), u64::MAX),
+        (with_approver, PRINCIPAL),
+    ];
+    for (terms, principal) in invalid {
+        let init = swap.init(&context.payer(), COLLATERAL, principal, terms);
+        assert!(!context.send(&[init], &[&swap.alice, &swap.bob]).await);
+    }
+}
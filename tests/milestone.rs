@@ -0,0 +1,186 @@
+mod common;
+
+use common::*;
+use escrow::{instruction::EscrowInstruction, state::MilestoneEscrowData};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program, sysvar,
+};
+
+const AMOUNTS: [u64; 3] = [100, 200, 300];
+
+struct Setup {
+    program_id: Pubkey,
+    payer: Keypair,
+    arbiter: Keypair,
+    payee: Pubkey,
+    mint: Pubkey,
+    escrow: Pubkey,
+    vault: Pubkey,
+    payer_token: Pubkey,
+    payee_token: Pubkey,
+}
+
+fn setup() -> (ProgramTest, Setup) {
+    let program_id = Pubkey::new_unique();
+    let payer = Keypair::new();
+    let payee = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    let escrow = pda(
+        &program_id,
+        &[
+            b"milestone",
+            payer.pubkey().as_ref(),
+            payee.as_ref(),
+            mint.as_ref(),
+            PASS.as_ref(),
+        ],
+    );
+    let vault = pda(&program_id, &[b"vault", escrow.as_ref()]);
+    let payer_token = Pubkey::new_unique();
+    let payee_token = Pubkey::new_unique();
+
+    let mut program_test = program_test(program_id);
+    program_test.add_account(mint, mint_account(0));
+    program_test.add_account(payer.pubkey(), system_account());
+    program_test.add_account(payer_token, token_account(mint, payer.pubkey(), FUNDS));
+    program_test.add_account(payee_token, token_account(mint, payee, 0));
+    (
+        program_test,
+        Setup {
+            program_id,
+            payer,
+            arbiter: Keypair::new(),
+            payee,
+            mint,
+            escrow,
+            vault,
+            payer_token,
+            payee_token,
+        },
+    )
+}
+
+fn init(setup: &Setup, amounts: &[u64], refund_after: i64) -> Instruction {
+    instruction(
+        setup.program_id,
+        EscrowInstruction::InitMilestoneEscrow {
+            amounts: amounts.to_vec(),
+            refund_after,
+            pass: PASS,
+        },
+        vec![
+            AccountMeta::new(setup.escrow, false),
+            AccountMeta::new_readonly(setup.mint, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(setup.payer.pubkey(), true),
+            AccountMeta::new_readonly(setup.payee, false),
+            AccountMeta::new_readonly(setup.arbiter.pubkey(), false),
+            AccountMeta::new(setup.payer_token, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+fn release(setup: &Setup, index: u8, signer: &Keypair) -> Instruction {
+    instruction(
+        setup.program_id,
+        EscrowInstruction::ReleaseMilestone { index, pass: PASS },
+        vec![
+            AccountMeta::new(setup.escrow, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(setup.payee_token, false),
+            AccountMeta::new_readonly(signer.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+fn refund(setup: &Setup) -> Instruction {
+    instruction(
+        setup.program_id,
+        EscrowInstruction::RefundMilestones { pass: PASS },
+        vec![
+            AccountMeta::new(setup.escrow, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(setup.payer_token, false),
+            AccountMeta::new_readonly(setup.payer.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_release_milestones() {
+    let (program_test, setup) = setup();
+    let mut context = Context::start(program_test).await;
+    let total: u64 = AMOUNTS.iter().sum();
+
+    let init = init(&setup, &AMOUNTS, now() + 3_600);
+    assert!(context.send(&[init], &[&setup.payer]).await);
+    assert_eq!(context.balance(setup.vault).await, total);
+    assert_eq!(context.balance(setup.payer_token).await, FUNDS - total);
+
+    let by_arbiter = release(&setup, 1, &setup.arbiter);
+    assert!(context.send(&[by_arbiter], &[&setup.arbiter]).await);
+    assert_eq!(context.balance(setup.payee_token).await, AMOUNTS[1]);
+
+    let again = release(&setup, 1, &setup.payer);
+    assert!(!context.send(&[again], &[&setup.payer]).await);
+    let outsider = Keypair::new();
+    let by_outsider = release(&setup, 0, &outsider);
+    assert!(!context.send(&[by_outsider], &[&outsider]).await);
+    let out_of_range = release(&setup, 3, &setup.payer);
+    assert!(!context.send(&[out_of_range], &[&setup.payer]).await);
+
+    let by_payer = release(&setup, 0, &setup.payer);
+    assert!(context.send(&[by_payer], &[&setup.payer]).await);
+    assert_eq!(
+        context.balance(setup.payee_token).await,
+        AMOUNTS[0] + AMOUNTS[1]
+    );
+
+    assert!(!context.send(&[refund(&setup)], &[&setup.payer]).await);
+    let escrow_data: MilestoneEscrowData = context.read(setup.escrow).await;
+    assert_eq!(escrow_data.settled, 0b011);
+    assert_eq!(escrow_data.unsettled_amount(), AMOUNTS[2]);
+}
+
+#[tokio::test]
+async fn test_refund_unreleased_milestones() {
+    let (program_test, setup) = setup();
+    let mut context = Context::start(program_test).await;
+    let total: u64 = AMOUNTS.iter().sum();
+
+    let init = init(&setup, &AMOUNTS, now() - 3_600);
+    assert!(context.send(&[init], &[&setup.payer]).await);
+    let release = release(&setup, 2, &setup.arbiter);
+    assert!(context.send(&[release], &[&setup.arbiter]).await);
+
+    assert!(context.send(&[refund(&setup)], &[&setup.payer]).await);
+    assert_eq!(
+        context.balance(setup.payer_token).await,
+        FUNDS - total + AMOUNTS[0] + AMOUNTS[1]
+    );
+    assert_eq!(context.balance(setup.payee_token).await, AMOUNTS[2]);
+    assert_eq!(context.balance(setup.vault).await, 0);
+
+    assert!(!context.send(&[refund(&setup)], &[&setup.payer]).await);
+}
+
+#[tokio::test]
+async fn test_init_rejects_invalid_milestones() {
+    let (program_test, setup) = setup();
+    let mut context = Context::start(program_test).await;
+
+    for amounts in [vec![], vec![100, 0], vec![1; 9], vec![u64::MAX, 1]].iter() {
+        let init = init(&setup, amounts, now());
+        assert!(!context.send(&[init], &[&setup.payer]).await);
+    }
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use common::*;
+use escrow::{
+    instruction::EscrowInstruction,
+    state::{EscrowData, EscrowState, EscrowTerms},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+/// Alice swaps 1,000 X for 2,000 Y, and in the reversed escrow bob swaps 600 X for 500 Y
+struct Setup {
+    swap: Swap,
+    reversed: Swap,
+}
+
+/// The swap with alice and bob trading places, between the same token accounts
+fn reversed(swap: &Swap, pass: [u8; 32]) -> Swap {
+    Swap {
+        program_id: swap.program_id,
+        pass,
+        alice: Keypair::from_bytes(&swap.bob.to_bytes()).unwrap(),
+        bob: Keypair::from_bytes(&swap.alice.to_bytes()).unwrap(),
+        mint_x: swap.mint_x,
+        mint_y: swap.mint_y,
+        escrow: Pubkey::default(),
+        vault_x: Pubkey::default(),
+        vault_y: Pubkey::default(),
+        alice_x: swap.bob_x,
+        alice_y: swap.bob_y,
+        bob_x: swap.alice_x,
+        bob_y: swap.alice_y,
+    }
+    .with_pass(pass)
+}
+
+/// Started with both escrows initialized and the first one committed when `committed`
+async fn start(committed: bool) -> (Context, Setup) {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let reversed = reversed(&swap, [8; 32]);
+    let mut context = Context::start(program_test).await;
+    let payer = context.payer();
+    let parties = [&swap.alice, &swap.bob];
+
+    let init = swap.init(&payer, 1_000, 2_000, EscrowTerms::default());
+    let reversed_init = reversed.init(&payer, 600, 500, EscrowTerms::default());
+    assert!(context.send(&[init, reversed_init], &parties).await);
+    if committed {
+        let alice_deposit = swap.deposit(&swap.alice, 1_000);
+        let bob_deposit = swap.deposit(&swap.bob, 2_000);
+        assert!(context.send(&[alice_deposit, bob_deposit], &parties).await);
+    }
+    (context, Setup { swap, reversed })
+}
+
+/// `NetSettle` of `escrows` by alice and bob, each committed escrow followed by its vaults
+fn net_settle(swap: &Swap, escrows: &[(&Swap, bool)]) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(swap.alice.pubkey(), true),
+        AccountMeta::new_readonly(swap.bob.pubkey(), true),
+        AccountMeta::new(swap.alice_x, false),
+        AccountMeta::new(swap.alice_y, false),
+        AccountMeta::new(swap.bob_x, false),
+        AccountMeta::new(swap.bob_y, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    for (escrow, committed) in escrows.iter() {
+        accounts.push(AccountMeta::new(escrow.escrow, false));
+        if *committed {
+            accounts.push(AccountMeta::new(escrow.vault_x, false));
+            accounts.push(AccountMeta::new(escrow.vault_y, false));
+        }
+    }
+    instruction(
+        swap.program_id,
+        EscrowInstruction::NetSettle {
+            passes: escrows.iter().map(|(escrow, _)| escrow.pass).collect(),
+        },
+        accounts,
+    )
+}
+
+#[tokio::test]
+async fn test_net_settle_unfunded() {
+    let (mut context, setup) = start(false).await;
+    let (swap, reversed) = (&setup.swap, &setup.reversed);
+    let parties = [&swap.alice, &swap.bob];
+
+    let mut alice_alone = net_settle(swap, &[(swap, false), (reversed, false)]);
+    alice_alone.accounts[1].is_signer = false;
+    assert!(!context.send(&[alice_alone], &[&swap.alice]).await);
+    let duplicate = net_settle(swap, &[(swap, false), (swap, false)]);
+    assert!(!context.send(&[duplicate], &parties).await);
+
+    let settle = net_settle(swap, &[(swap, false), (reversed, false)]);
+    assert!(context.send(&[settle], &parties).await);
+    // only the net 400 X and 1,500 Y move
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS - 400);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS + 400);
+    assert_eq!(context.balance(swap.alice_y).await, FUNDS + 1_500);
+    assert_eq!(context.balance(swap.bob_y).await, FUNDS - 1_500);
+    for escrow in [swap, reversed].iter() {
+        let escrow_data: EscrowData = context.read(escrow.escrow).await;
+        assert_eq!(escrow_data.state, EscrowState::Completed);
+    }
+
+    let again = net_settle(swap, &[(swap, false), (reversed, false)]);
+    assert!(!context.send(&[again], &parties).await);
+}
+
+#[tokio::test]
+async fn test_net_settle_committed() {
+    let (mut context, setup) = start(true).await;
+    let (swap, reversed) = (&setup.swap, &setup.reversed);
+    let parties = [&swap.alice, &swap.bob];
+
+    let without_vaults = net_settle(swap, &[(swap, false), (reversed, false)]);
+    assert!(!context.send(&[without_vaults], &parties).await);
+
+    // the vaults cover the unfunded escrow, neither party pays out of their own account
+    let settle = net_settle(swap, &[(swap, true), (reversed, false)]);
+    assert!(context.send(&[settle], &parties).await);
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS - 1_000 + 600);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS + 400);
+    assert_eq!(context.balance(swap.alice_y).await, FUNDS + 1_500);
+    assert_eq!(context.balance(swap.bob_y).await, FUNDS - 2_000 + 500);
+    assert_eq!(context.balance(swap.vault_x).await, 0);
+    assert_eq!(context.balance(swap.vault_y).await, 0);
+    for escrow in [swap, reversed].iter() {
+        let escrow_data: EscrowData = context.read(escrow.escrow).await;
+        assert_eq!(escrow_data.state, EscrowState::Completed);
+    }
+}
+
+#[tokio::test]
+async fn test_net_settle_rejects_partly_funded_escrows() {
+    let (mut context, setup) = start(false).await;
+    let (swap, reversed) = (&setup.swap, &setup.reversed);
+    let parties = [&swap.alice, &swap.bob];
+
+    let deposit = reversed.deposit(&reversed.alice, 600);
+    assert!(context.send(&[deposit], &[&reversed.alice]).await);
+    for committed in [false, true].iter() {
+        let settle = net_settle(swap, &[(swap, false), (reversed, *committed)]);
+        assert!(!context.send(&[settle], &parties).await);
+    }
+    assert_eq!(context.balance(swap.alice_y).await, FUNDS);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Initialized);
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use common::*;
+use escrow::{instruction::EscrowInstruction, state::Offer};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program, sysvar,
+};
+
+const AMOUNT_X: u64 = 1_000;
+const AMOUNT_Y: u64 = 2_000;
+
+/// Alice makes the offers, bob takes them
+async fn setup() -> (Context, Swap) {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    add_ed25519_program(&mut program_test);
+    let swap = Swap::new(&mut program_test, program_id);
+    let mut context = Context::start(program_test).await;
+
+    let approve = spl_token::instruction::approve(
+        &spl_token::id(),
+        &swap.alice_x,
+        &delegate(&swap),
+        &swap.alice.pubkey(),
+        &[],
+        FUNDS,
+    )
+    .unwrap();
+    assert!(context.send(&[approve], &[&swap.alice]).await);
+    (context, swap)
+}
+
+fn delegate(swap: &Swap) -> Pubkey {
+    pda(
+        &swap.program_id,
+        &[b"delegate", swap.alice.pubkey().as_ref()],
+    )
+}
+
+fn offer(swap: &Swap, nonce: u64) -> Offer {
+    Offer {
+        pubkey_maker: swap.alice.pubkey(),
+        pubkey_mint_x: swap.mint_x,
+        pubkey_mint_y: swap.mint_y,
+        amount_x: AMOUNT_X,
+        amount_y: AMOUNT_Y,
+        expiry: now() + 3_600,
+        nonce,
+    }
+}
+
+fn take(swap: &Swap, offer: Offer) -> Instruction {
+    let nonce = pda(
+        &swap.program_id,
+        &[
+            b"nonce",
+            offer.pubkey_maker.as_ref(),
+            &offer.nonce.to_le_bytes(),
+        ],
+    );
+    instruction(
+        swap.program_id,
+        EscrowInstruction::TakeOffer { offer },
+        vec![
+            AccountMeta::new(nonce, false),
+            AccountMeta::new(swap.alice_x, false),
+            AccountMeta::new(swap.alice_y, false),
+            AccountMeta::new(swap.bob_x, false),
+            AccountMeta::new(swap.bob_y, false),
+            AccountMeta::new_readonly(delegate(swap), false),
+            AccountMeta::new(swap.bob.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// `offer` signed by `signer` and taken by bob
+fn signed_take(swap: &Swap, offer: Offer, signer: &Keypair) -> Vec<Instruction> {
+    vec![
+        ed25519_instruction(signer, &offer.message()),
+        take(swap, offer),
+    ]
+}
+
+#[tokio::test]
+async fn test_take_offer_once() {
+    let (mut context, swap) = setup().await;
+    let offer = offer(&swap, 1);
+
+    let first = signed_take(&swap, offer, &swap.alice);
+    assert!(context.send(&first, &[&swap.bob]).await);
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS - AMOUNT_X);
+    assert_eq!(context.balance(swap.alice_y).await, FUNDS + AMOUNT_Y);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS + AMOUNT_X);
+    assert_eq!(context.balance(swap.bob_y).await, FUNDS - AMOUNT_Y);
+
+    // replaying the signed offer, or any other offer under the same nonce, hits the consumed nonce
+    let replay = signed_take(&swap, offer, &swap.alice);
+    assert!(!context.send(&replay, &[&swap.bob]).await);
+    let same_nonce = Offer {
+        amount_y: 1,
+        ..offer
+    };
+    let reused = signed_take(&swap, same_nonce, &swap.alice);
+    assert!(!context.send(&reused, &[&swap.bob]).await);
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS - AMOUNT_X);
+
+    let next = signed_take(&swap, Offer { nonce: 2, ..offer }, &swap.alice);
+    assert!(context.send(&next, &[&swap.bob]).await);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS + 2 * AMOUNT_X);
+}
+
+#[tokio::test]
+async fn test_take_offer_requires_maker_signature() {
+    let (mut context, swap) = setup().await;
+    let offer = offer(&swap, 1);
+
+    let unsigned = take(&swap, offer);
+    assert!(!context.send(&[unsigned], &[&swap.bob]).await);
+    let by_taker = signed_take(&swap, offer, &swap.bob);
+    assert!(!context.send(&by_taker, &[&swap.bob]).await);
+    let cheaper = Offer {
+        amount_y: 1,
+        ..offer
+    };
+    let tampered = vec![
+        ed25519_instruction(&swap.alice, &offer.message()),
+        take(&swap, cheaper),
+    ];
+    assert!(!context.send(&tampered, &[&swap.bob]).await);
+
+    let expired = Offer {
+        expiry: now() - 3_600,
+        ..offer
+    };
+    let late = signed_take(&swap, expired, &swap.alice);
+    assert!(!context.send(&late, &[&swap.bob]).await);
+
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS);
+}
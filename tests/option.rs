@@ -0,0 +1,219 @@
+mod common;
+
+use common::*;
+use escrow::{
+    instruction::EscrowInstruction,
+    state::{OptionData, OptionState, OptionTerms},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program, sysvar,
+};
+
+const AMOUNT_X: u64 = 1_000;
+const PREMIUM: u64 = 50;
+const STRIKE: u64 = 2_000;
+
+/// Alice writes the options, bob buys them
+struct Setup {
+    swap: Swap,
+    option: Pubkey,
+    vault: Pubkey,
+}
+
+fn setup() -> (ProgramTest, Setup) {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let option = pda(
+        &program_id,
+        &[
+            b"option",
+            swap.alice.pubkey().as_ref(),
+            swap.mint_x.as_ref(),
+            swap.mint_y.as_ref(),
+            PASS.as_ref(),
+        ],
+    );
+    let vault = pda(&program_id, &[b"vault", option.as_ref()]);
+    (
+        program_test,
+        Setup {
+            swap,
+            option,
+            vault,
+        },
+    )
+}
+
+fn terms(exercise_start: i64, expiry: i64) -> OptionTerms {
+    OptionTerms {
+        amount_x: AMOUNT_X,
+        premium: PREMIUM,
+        strike: STRIKE,
+        exercise_start,
+        expiry,
+    }
+}
+
+fn write(setup: &Setup, terms: OptionTerms) -> Instruction {
+    let swap = &setup.swap;
+    instruction(
+        swap.program_id,
+        EscrowInstruction::WriteOption { terms, pass: PASS },
+        vec![
+            AccountMeta::new(setup.option, false),
+            AccountMeta::new_readonly(swap.mint_x, false),
+            AccountMeta::new_readonly(swap.mint_y, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(swap.alice.pubkey(), true),
+            AccountMeta::new(swap.alice_x, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+fn buy(setup: &Setup) -> Instruction {
+    let swap = &setup.swap;
+    instruction(
+        swap.program_id,
+        EscrowInstruction::BuyOption { pass: PASS },
+        vec![
+            AccountMeta::new(setup.option, false),
+            AccountMeta::new(swap.bob_y, false),
+            AccountMeta::new(swap.alice_y, false),
+            AccountMeta::new_readonly(swap.bob.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+fn exercise(setup: &Setup, holder: &Keypair) -> Instruction {
+    let swap = &setup.swap;
+    instruction(
+        swap.program_id,
+        EscrowInstruction::ExerciseOption { pass: PASS },
+        vec![
+            AccountMeta::new(setup.option, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(swap.bob_x, false),
+            AccountMeta::new(swap.bob_y, false),
+            AccountMeta::new(swap.alice_y, false),
+            AccountMeta::new_readonly(holder.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+fn reclaim(setup: &Setup) -> Instruction {
+    let swap = &setup.swap;
+    instruction(
+        swap.program_id,
+        EscrowInstruction::ReclaimOption { pass: PASS },
+        vec![
+            AccountMeta::new(setup.option, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(swap.alice_x, false),
+            AccountMeta::new_readonly(swap.alice.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_buy_and_exercise() {
+    let (program_test, setup) = setup();
+    let swap = &setup.swap;
+    let mut context = Context::start(program_test).await;
+
+    let write = write(&setup, terms(now() - 60, now() + 3_600));
+    assert!(context.send(&[write], &[&swap.alice]).await);
+    assert_eq!(context.balance(setup.vault).await, AMOUNT_X);
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS - AMOUNT_X);
+
+    assert!(context.send(&[buy(&setup)], &[&swap.bob]).await);
+    assert_eq!(context.balance(swap.alice_y).await, FUNDS + PREMIUM);
+    let option_data: OptionData = context.read(setup.option).await;
+    assert_eq!(option_data.state, OptionState::Held);
+    assert_eq!(option_data.pubkey_holder, swap.bob.pubkey());
+    assert!(!context.send(&[buy(&setup)], &[&swap.bob]).await);
+
+    let by_writer = exercise(&setup, &swap.alice);
+    assert!(!context.send(&[by_writer], &[&swap.alice]).await);
+    let by_holder = exercise(&setup, &swap.bob);
+    assert!(context.send(&[by_holder], &[&swap.bob]).await);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS + AMOUNT_X);
+    assert_eq!(context.balance(swap.bob_y).await, FUNDS - PREMIUM - STRIKE);
+    assert_eq!(
+        context.balance(swap.alice_y).await,
+        FUNDS + PREMIUM + STRIKE
+    );
+    assert_eq!(context.balance(setup.vault).await, 0);
+
+    assert!(!context.send(&[reclaim(&setup)], &[&swap.alice]).await);
+}
+
+#[tokio::test]
+async fn test_exercise_before_window() {
+    let (program_test, setup) = setup();
+    let swap = &setup.swap;
+    let mut context = Context::start(program_test).await;
+
+    let write = write(&setup, terms(now() + 1_800, now() + 3_600));
+    assert!(context.send(&[write], &[&swap.alice]).await);
+    assert!(context.send(&[buy(&setup)], &[&swap.bob]).await);
+
+    let early = exercise(&setup, &swap.bob);
+    assert!(!context.send(&[early], &[&swap.bob]).await);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS);
+    // the writer cannot take the underlying back while the option runs
+    assert!(!context.send(&[reclaim(&setup)], &[&swap.alice]).await);
+}
+
+#[tokio::test]
+async fn test_reclaim_expired() {
+    let (program_test, setup) = setup();
+    let swap = &setup.swap;
+    let mut context = Context::start(program_test).await;
+
+    let write = write(&setup, terms(now() - 7_200, now() - 3_600));
+    assert!(context.send(&[write], &[&swap.alice]).await);
+    assert!(!context.send(&[buy(&setup)], &[&swap.bob]).await);
+
+    assert!(context.send(&[reclaim(&setup)], &[&swap.alice]).await);
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS);
+    let option_data: OptionData = context.read(setup.option).await;
+    assert_eq!(option_data.state, OptionState::Reclaimed);
+    assert!(!context.send(&[reclaim(&setup)], &[&swap.alice]).await);
+}
+
+#[tokio::test]
+async fn test_write_rejects_invalid_terms() {
+    let (program_test, setup) = setup();
+    let mut context = Context::start(program_test).await;
+    let valid = terms(now(), now() + 3_600);
+
+    let invalid = [
+        OptionTerms {
+            amount_x: 0,
+            ..valid
+        },
+        OptionTerms { strike: 0, ..valid },
+        OptionTerms {
+            exercise_start: valid.expiry,
+            ..valid
+        },
+    ];
+    for terms in invalid.iter() {
+        let write = write(&setup, *terms);
+        assert!(!context.send(&[write], &[&setup.swap.alice]).await);
+    }
+}
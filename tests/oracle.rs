@@ -0,0 +1,214 @@
+use borsh::BorshSerialize;
+use escrow::{
+    instruction::EscrowInstruction,
+    processor::Processor,
+    state::{EscrowData, EscrowState, OracleCondition, PriceFeed},
+};
+use solana_program::program_pack::Pack;
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    sysvar,
+    transaction::Transaction,
+};
+use spl_token::state::{Account as TokenAccount, AccountState};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const PASS: [u8; 32] = [7; 32];
+const SIZE_X: u64 = 1_000;
+const SIZE_Y: u64 = 2_000;
+
+struct Setup {
+    program_id: Pubkey,
+    alice: Keypair,
+    escrow: Pubkey,
+    vault_y: Pubkey,
+    alice_y: Pubkey,
+    oracle: Pubkey,
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+fn token_account(mint: Pubkey, owner: Pubkey, amount: u64) -> Account {
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount {
+        mint,
+        owner,
+        amount,
+        state: AccountState::Initialized,
+        ..TokenAccount::default()
+    }
+    .pack_into_slice(&mut data);
+    Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: spl_token::id(),
+        ..Account::default()
+    }
+}
+
+/// Committed X/Y escrow gated on an X/Y price of at least 100, with the mock oracle holding `feed`
+fn setup(feed: PriceFeed) -> (ProgramTest, Setup) {
+    let program_id = Pubkey::new_unique();
+    let alice = Keypair::new();
+    let bob = Pubkey::new_unique();
+    let mint_x = Pubkey::new_unique();
+    let mint_y = Pubkey::new_unique();
+    let oracle = Pubkey::new_unique();
+    let pda = |prefix: &[u8]| {
+        Pubkey::find_program_address(
+            &[
+                prefix,
+                alice.pubkey().as_ref(),
+                bob.as_ref(),
+                mint_x.as_ref(),
+                mint_y.as_ref(),
+                PASS.as_ref(),
+            ],
+            &program_id,
+        )
+    };
+    let (escrow, escrow_bump) = pda(b"escrow");
+    let (vault_x, vault_x_bump) = pda(b"vault_x");
+    let (vault_y, vault_y_bump) = pda(b"vault_y");
+    let alice_y = Pubkey::new_unique();
+
+    let mut program_test = ProgramTest::new("escrow", program_id, processor!(Processor::process));
+    let escrow_data = EscrowData {
+        size_x: SIZE_X,
+        size_y: SIZE_Y,
+        pubkey_alice: alice.pubkey(),
+        pubkey_bob: bob,
+        pubkey_mint_x: mint_x,
+        pubkey_mint_y: mint_y,
+        state: EscrowState::Committed,
+        escrow_bump,
+        vault_x_bump,
+        vault_y_bump,
+        deposited_x: SIZE_X,
+        deposited_y: SIZE_Y,
+        oracle: OracleCondition {
+            pubkey_oracle: oracle,
+            price: 100,
+            above: true,
+            max_staleness: 60,
+            max_confidence: 5,
+        },
+        ..EscrowData::default()
+    };
+    program_test.add_account(
+        escrow,
+        Account {
+            lamports: 1_000_000_000,
+            data: escrow_data.try_to_vec().unwrap(),
+            owner: program_id,
+            ..Account::default()
+        },
+    );
+    program_test.add_account(vault_x, token_account(mint_x, escrow, SIZE_X));
+    program_test.add_account(vault_y, token_account(mint_y, escrow, SIZE_Y));
+    program_test.add_account(alice_y, token_account(mint_y, alice.pubkey(), 0));
+    program_test.add_account(
+        oracle,
+        Account {
+            lamports: 1_000_000_000,
+            data: feed.try_to_vec().unwrap(),
+            owner: Pubkey::new_unique(),
+            ..Account::default()
+        },
+    );
+
+    (
+        program_test,
+        Setup {
+            program_id,
+            alice,
+            escrow,
+            vault_y,
+            alice_y,
+            oracle,
+        },
+    )
+}
+
+/// Alice withdraws Y from the committed escrow, returns whether the transaction went through
+async fn withdraw(program_test: ProgramTest, setup: &Setup) -> (bool, BanksClient) {
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let mut transaction = Transaction::new_with_payer(
+        &[Instruction::new_with_bytes(
+            setup.program_id,
+            &EscrowInstruction::Withdrawal { pass: PASS }
+                .try_to_vec()
+                .unwrap(),
+            vec![
+                AccountMeta::new(setup.escrow, false),
+                AccountMeta::new(setup.alice_y, false),
+                AccountMeta::new(setup.vault_y, false),
+                AccountMeta::new_readonly(setup.alice.pubkey(), true),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(sysvar::clock::id(), false),
+                AccountMeta::new_readonly(setup.oracle, false),
+            ],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &setup.alice], recent_blockhash);
+    let ok = banks_client.process_transaction(transaction).await.is_ok();
+    (ok, banks_client)
+}
+
+#[tokio::test]
+async fn test_withdrawal_when_price_condition_holds() {
+    let (program_test, setup) = setup(PriceFeed {
+        price: 110,
+        confidence: 2,
+        publish_time: now(),
+    });
+    let (ok, mut banks_client) = withdraw(program_test, &setup).await;
+    assert!(ok);
+
+    let alice_y = banks_client
+        .get_account(setup.alice_y)
+        .await
+        .expect("get_account")
+        .expect("alice_y not found");
+    assert_eq!(TokenAccount::unpack(&alice_y.data).unwrap().amount, SIZE_Y);
+}
+
+#[tokio::test]
+async fn test_withdrawal_below_price_bound() {
+    let (program_test, setup) = setup(PriceFeed {
+        price: 90,
+        confidence: 2,
+        publish_time: now(),
+    });
+    assert!(!withdraw(program_test, &setup).await.0);
+}
+
+#[tokio::test]
+async fn test_withdrawal_with_stale_price() {
+    let (program_test, setup) = setup(PriceFeed {
+        price: 110,
+        confidence: 2,
+        publish_time: now() - 3_600,
+    });
+    assert!(!withdraw(program_test, &setup).await.0);
+}
+
+#[tokio::test]
+async fn test_withdrawal_with_wide_confidence() {
+    let (program_test, setup) = setup(PriceFeed {
+        price: 110,
+        confidence: 50,
+        publish_time: now(),
+    });
+    assert!(!withdraw(program_test, &setup).await.0);
+}
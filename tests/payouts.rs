@@ -0,0 +1,147 @@
+mod common;
+
+use common::*;
+use escrow::{
+    instruction::EscrowInstruction,
+    state::{EscrowData, EscrowState, EscrowTerms, Payout, MAX_PAYOUTS},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const SIZE_X: u64 = 1_000;
+const SIZE_Y: u64 = 2_000;
+
+/// Bob splits the X he withdraws between carol and dave
+struct Setup {
+    swap: Swap,
+    carol_x: Pubkey,
+    dave_x: Pubkey,
+    split: Vec<Payout>,
+}
+
+async fn setup() -> (Context, Setup) {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let (carol, dave) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (carol_x, dave_x) = (Pubkey::new_unique(), Pubkey::new_unique());
+    program_test.add_account(carol_x, token_account(swap.mint_x, carol, 0));
+    program_test.add_account(dave_x, token_account(swap.mint_x, dave, 0));
+    let mut context = Context::start(program_test).await;
+
+    let init = swap.init(&context.payer(), SIZE_X, SIZE_Y, EscrowTerms::default());
+    assert!(context.send(&[init], &[&swap.alice, &swap.bob]).await);
+    let split = vec![
+        Payout {
+            recipient: carol,
+            bps: 3_333,
+        },
+        Payout {
+            recipient: dave,
+            bps: 6_667,
+        },
+    ];
+    (
+        context,
+        Setup {
+            swap,
+            carol_x,
+            dave_x,
+            split,
+        },
+    )
+}
+
+fn set_payouts(swap: &Swap, party: &Keypair, recipients: Vec<Payout>) -> Instruction {
+    instruction(
+        swap.program_id,
+        EscrowInstruction::SetPayouts {
+            recipients,
+            pass: PASS,
+        },
+        vec![
+            AccountMeta::new(swap.escrow, false),
+            AccountMeta::new_readonly(party.pubkey(), true),
+        ],
+    )
+}
+
+#[test]
+fn test_share_of() {
+    let payout = |bps| Payout {
+        recipient: Pubkey::default(),
+        bps,
+    };
+    assert_eq!(payout(10_000).share_of(1_000), 1_000);
+    assert_eq!(payout(2_500).share_of(1_000), 250);
+    assert_eq!(payout(3_333).share_of(1_000), 333);
+    assert_eq!(payout(1).share_of(9_999), 0);
+    assert_eq!(payout(10_000).share_of(u64::MAX), u64::MAX);
+    assert_eq!(payout(5_000).share_of(u64::MAX), u64::MAX / 2);
+}
+
+#[tokio::test]
+async fn test_split_withdrawal() {
+    let (mut context, setup) = setup().await;
+    let swap = &setup.swap;
+
+    let split = set_payouts(swap, &swap.bob, setup.split.clone());
+    assert!(context.send(&[split], &[&swap.bob]).await);
+    let alice_deposit = swap.deposit(&swap.alice, SIZE_X);
+    let bob_deposit = swap.deposit(&swap.bob, SIZE_Y);
+    let parties = [&swap.alice, &swap.bob];
+    assert!(context.send(&[alice_deposit, bob_deposit], &parties).await);
+
+    // the first recipient's token account takes the place of bob's
+    assert!(!context.send(&[swap.withdraw_bob()], &[&swap.bob]).await);
+    let without_dave = swap.withdrawal(&swap.bob, setup.carol_x, swap.vault_x);
+    let mut withdrawal = without_dave.clone();
+    assert!(!context.send(&[without_dave], &[&swap.bob]).await);
+    withdrawal
+        .accounts
+        .push(AccountMeta::new(setup.dave_x, false));
+    assert!(context.send(&[withdrawal], &[&swap.bob]).await);
+
+    // the rounding dust goes to the first recipient
+    assert_eq!(context.balance(setup.carol_x).await, 334);
+    assert_eq!(context.balance(setup.dave_x).await, 666);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS);
+    assert_eq!(context.balance(swap.vault_x).await, 0);
+
+    // alice set no payouts and withdraws alone
+    assert!(context.send(&[swap.withdraw_alice()], &[&swap.alice]).await);
+    assert_eq!(context.balance(swap.alice_y).await, FUNDS + SIZE_Y);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Completed);
+}
+
+#[tokio::test]
+async fn test_set_payouts_rejects_invalid_splits() {
+    let (mut context, setup) = setup().await;
+    let swap = &setup.swap;
+    let payout = |bps| Payout {
+        recipient: Pubkey::new_unique(),
+        bps,
+    };
+
+    let invalid = vec![
+        vec![payout(5_000), payout(4_999)],
+        vec![payout(10_000), payout(0)],
+        vec![payout(2_000); MAX_PAYOUTS + 1],
+    ];
+    for recipients in invalid {
+        let split = set_payouts(swap, &swap.alice, recipients);
+        assert!(!context.send(&[split], &[&swap.alice]).await);
+    }
+    let outsider = Keypair::new();
+    let by_outsider = set_payouts(swap, &outsider, setup.split.clone());
+    assert!(!context.send(&[by_outsider], &[&outsider]).await);
+
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.payout_count_alice, 0);
+    assert_eq!(escrow_data.payout_count_bob, 0);
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use common::*;
+use escrow::{
+    instruction::EscrowInstruction,
+    state::{EscrowData, EscrowState, EscrowTerms},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const SIZE_X: u64 = 1_000;
+const SIZE_Y: u64 = 2_000;
+
+/// An escrow both parties initialized, nobody deposited yet
+async fn setup() -> (Context, Swap) {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let mut context = Context::start(program_test).await;
+
+    let init = swap.init(&context.payer(), SIZE_X, SIZE_Y, EscrowTerms::default());
+    assert!(context.send(&[init], &[&swap.alice, &swap.bob]).await);
+    (context, swap)
+}
+
+fn propose(swap: &Swap, proposer: &Keypair, amount_x: u64, amount_y: u64) -> Instruction {
+    instruction(
+        swap.program_id,
+        EscrowInstruction::Propose {
+            amount_x,
+            amount_y,
+            pass: PASS,
+        },
+        vec![
+            AccountMeta::new(swap.escrow, false),
+            AccountMeta::new_readonly(proposer.pubkey(), true),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_accept_proposal() {
+    let (mut context, swap) = setup().await;
+
+    let first = propose(&swap, &swap.bob, SIZE_X, SIZE_Y + 500);
+    assert!(context.send(&[first], &[&swap.bob]).await);
+    let counter = propose(&swap, &swap.alice, SIZE_X, SIZE_Y + 1_000);
+    assert!(context.send(&[counter], &[&swap.alice]).await);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.pubkey_proposer, swap.alice.pubkey());
+    assert_eq!(escrow_data.size_y, SIZE_Y);
+
+    let by_proposer = swap.accept(&swap.alice, SIZE_X, SIZE_Y + 1_000);
+    assert!(!context.send(&[by_proposer], &[&swap.alice]).await);
+    let replaced = swap.accept(&swap.bob, SIZE_X, SIZE_Y + 500);
+    assert!(!context.send(&[replaced], &[&swap.bob]).await);
+
+    let accept = swap.accept(&swap.bob, SIZE_X, SIZE_Y + 1_000);
+    assert!(context.send(&[accept], &[&swap.bob]).await);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Initialized);
+    assert_eq!(escrow_data.size_x, SIZE_X);
+    assert_eq!(escrow_data.size_y, SIZE_Y + 1_000);
+    assert_eq!(escrow_data.pubkey_proposer, Pubkey::default());
+
+    // nothing left to accept
+    let again = swap.accept(&swap.bob, SIZE_X, SIZE_Y + 1_000);
+    assert!(!context.send(&[again], &[&swap.bob]).await);
+}
+
+#[tokio::test]
+async fn test_propose_requires_a_party() {
+    let (mut context, swap) = setup().await;
+    let outsider = Keypair::new();
+
+    let by_outsider = propose(&swap, &outsider, SIZE_X, 1);
+    assert!(!context.send(&[by_outsider], &[&outsider]).await);
+    let mut unsigned = propose(&swap, &swap.bob, SIZE_X, 1);
+    unsigned.accounts[1].is_signer = false;
+    assert!(!context.send(&[unsigned], &[]).await);
+
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.pubkey_proposer, Pubkey::default());
+}
+
+#[tokio::test]
+async fn test_propose_before_deposits_only() {
+    let (mut context, swap) = setup().await;
+    let proposal = propose(&swap, &swap.bob, SIZE_X, SIZE_Y + 500);
+    assert!(context.send(&[proposal], &[&swap.bob]).await);
+
+    let deposit = swap.deposit(&swap.alice, SIZE_X);
+    assert!(context.send(&[deposit], &[&swap.alice]).await);
+    let accept = swap.accept(&swap.alice, SIZE_X, SIZE_Y + 500);
+    assert!(!context.send(&[accept], &[&swap.alice]).await);
+    let late = propose(&swap, &swap.bob, SIZE_X, SIZE_Y + 1_000);
+    assert!(!context.send(&[late], &[&swap.bob]).await);
+
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.size_y, SIZE_Y);
+}
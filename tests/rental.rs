@@ -0,0 +1,146 @@
+mod common;
+
+use common::*;
+use escrow::{
+    instruction::EscrowInstruction,
+    state::{EscrowData, EscrowState, EscrowTerms, RentalTerms},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::Signer,
+    sysvar,
+};
+
+const COLLATERAL: u64 = 500;
+const FEE: u64 = 20;
+const DURATION: i64 = 3_600;
+
+fn rent(swap: &Swap) -> Instruction {
+    instruction(
+        swap.program_id,
+        EscrowInstruction::Rent { pass: PASS },
+        vec![
+            AccountMeta::new(swap.escrow, false),
+            AccountMeta::new(swap.bob_y, false),
+            AccountMeta::new(swap.vault_y, false),
+            AccountMeta::new(swap.alice_y, false),
+            AccountMeta::new(swap.vault_x, false),
+            AccountMeta::new(swap.bob_x, false),
+            AccountMeta::new_readonly(swap.bob.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+fn return_rental(swap: &Swap) -> Instruction {
+    instruction(
+        swap.program_id,
+        EscrowInstruction::ReturnRental { pass: PASS },
+        vec![
+            AccountMeta::new(swap.escrow, false),
+            AccountMeta::new(swap.bob_x, false),
+            AccountMeta::new(swap.alice_x, false),
+            AccountMeta::new(swap.vault_y, false),
+            AccountMeta::new(swap.bob_y, false),
+            AccountMeta::new_readonly(swap.bob.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+fn claim_collateral(swap: &Swap) -> Instruction {
+    instruction(
+        swap.program_id,
+        EscrowInstruction::ClaimCollateral { pass: PASS },
+        vec![
+            AccountMeta::new(swap.escrow, false),
+            AccountMeta::new(swap.vault_y, false),
+            AccountMeta::new(swap.alice_y, false),
+            AccountMeta::new_readonly(swap.alice.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+fn rental_terms(duration: i64) -> EscrowTerms {
+    EscrowTerms {
+        rental: Some(RentalTerms { fee: FEE, duration }),
+        ..EscrowTerms::default()
+    }
+}
+
+#[tokio::test]
+async fn test_rent_and_return() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let mut context = Context::start(program_test).await;
+
+    let init = swap.init(&context.payer(), 1, COLLATERAL, rental_terms(DURATION));
+    let listing = swap.deposit(&swap.alice, 1);
+    let parties = [&swap.alice, &swap.bob];
+    assert!(context.send(&[init, listing], &parties).await);
+
+    assert!(context.send(&[rent(&swap)], &[&swap.bob]).await);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS + 1);
+    assert_eq!(context.balance(swap.vault_y).await, COLLATERAL);
+    assert_eq!(context.balance(swap.alice_y).await, FUNDS + FEE);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Rented);
+    assert!((escrow_data.rental_due_ts - now() - DURATION).abs() < 60);
+
+    assert!(!context.send(&[rent(&swap)], &[&swap.bob]).await);
+    let early = claim_collateral(&swap);
+    assert!(!context.send(&[early], &[&swap.alice]).await);
+
+    assert!(context.send(&[return_rental(&swap)], &[&swap.bob]).await);
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS);
+    assert_eq!(context.balance(swap.bob_y).await, FUNDS - FEE);
+    assert_eq!(context.balance(swap.vault_y).await, 0);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Completed);
+}
+
+#[tokio::test]
+async fn test_claim_collateral_when_overdue() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let mut escrow_data = swap.escrow_data(1, COLLATERAL);
+    escrow_data.state = EscrowState::Rented;
+    escrow_data.deposited_y = COLLATERAL;
+    escrow_data.rental = RentalTerms {
+        fee: FEE,
+        duration: DURATION,
+    };
+    escrow_data.rental_due_ts = now() - 1;
+    swap.add_escrow(&mut program_test, escrow_data);
+    let mut context = Context::start(program_test).await;
+
+    assert!(!context.send(&[return_rental(&swap)], &[&swap.bob]).await);
+
+    let claim = claim_collateral(&swap);
+    assert!(context.send(&[claim], &[&swap.alice]).await);
+    assert_eq!(context.balance(swap.alice_y).await, FUNDS + COLLATERAL);
+    assert_eq!(context.balance(swap.vault_y).await, 0);
+    let again = claim_collateral(&swap);
+    assert!(!context.send(&[again], &[&swap.alice]).await);
+}
+
+#[tokio::test]
+async fn test_init_rejects_invalid_rentals() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let mut context = Context::start(program_test).await;
+
+    for (terms, size_x) in [(rental_terms(0), 1), (rental_terms(DURATION), 2)].iter() {
+        let init = swap.init(&context.payer(), *size_x, COLLATERAL, terms.clone());
+        assert!(!context.send(&[init], &[&swap.alice, &swap.bob]).await);
+    }
+}
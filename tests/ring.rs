@@ -0,0 +1,253 @@
+mod common;
+
+use common::*;
+use escrow::{
+    instruction::EscrowInstruction,
+    state::{RingData, RingLeg},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program, sysvar,
+};
+
+/// Alice's X goes to bob, bob's Y to carol and carol's Z back to alice
+struct Setup {
+    swap: Swap,
+    carol: Keypair,
+    alice_z: Pubkey,
+    carol_y: Pubkey,
+    carol_z: Pubkey,
+    ring: Pubkey,
+    legs: Vec<RingLeg>,
+}
+
+fn setup() -> (ProgramTest, Setup) {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let carol = Keypair::new();
+    let (mint_z, alice_z, carol_y, carol_z) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    program_test.add_account(carol.pubkey(), system_account());
+    program_test.add_account(mint_z, mint_account(0));
+    program_test.add_account(alice_z, token_account(mint_z, swap.alice.pubkey(), 0));
+    program_test.add_account(carol_y, token_account(swap.mint_y, carol.pubkey(), 0));
+    program_test.add_account(carol_z, token_account(mint_z, carol.pubkey(), FUNDS));
+    let ring = pda(
+        &program_id,
+        &[b"ring", swap.alice.pubkey().as_ref(), PASS.as_ref()],
+    );
+    let leg = |participant, mint, amount, owed_from| RingLeg {
+        participant,
+        mint,
+        amount,
+        owed_from,
+        ..RingLeg::default()
+    };
+    let legs = vec![
+        leg(swap.alice.pubkey(), swap.mint_x, 100, 2),
+        leg(swap.bob.pubkey(), swap.mint_y, 200, 0),
+        leg(carol.pubkey(), mint_z, 300, 1),
+    ];
+    (
+        program_test,
+        Setup {
+            swap,
+            carol,
+            alice_z,
+            carol_y,
+            carol_z,
+            ring,
+            legs,
+        },
+    )
+}
+
+fn init(setup: &Setup, legs: Vec<RingLeg>) -> Instruction {
+    instruction(
+        setup.swap.program_id,
+        EscrowInstruction::InitRing { legs, pass: PASS },
+        vec![
+            AccountMeta::new(setup.ring, false),
+            AccountMeta::new(setup.swap.alice.pubkey(), true),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Started with the ring initialized by alice
+async fn start() -> (Context, Setup) {
+    let (program_test, setup) = setup();
+    let mut context = Context::start(program_test).await;
+    let init = init(&setup, setup.legs.clone());
+    assert!(context.send(&[init], &[&setup.swap.alice]).await);
+    (context, setup)
+}
+
+fn vault(setup: &Setup, index: u8) -> Pubkey {
+    pda(
+        &setup.swap.program_id,
+        &[b"ring_vault", setup.ring.as_ref(), &[index]],
+    )
+}
+
+fn deposit(setup: &Setup, index: u8, participant: &Keypair, token: Pubkey) -> Instruction {
+    instruction(
+        setup.swap.program_id,
+        EscrowInstruction::RingDeposit { index, pass: PASS },
+        vec![
+            AccountMeta::new(setup.ring, false),
+            AccountMeta::new_readonly(setup.legs[index as usize].mint, false),
+            AccountMeta::new(vault(setup, index), false),
+            AccountMeta::new(token, false),
+            AccountMeta::new(participant.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// `RingWithdraw` of leg `index` out of the vault of leg `source`
+fn withdraw(
+    setup: &Setup,
+    index: u8,
+    source: u8,
+    participant: &Keypair,
+    token: Pubkey,
+) -> Instruction {
+    instruction(
+        setup.swap.program_id,
+        EscrowInstruction::RingWithdraw { index, pass: PASS },
+        vec![
+            AccountMeta::new(setup.ring, false),
+            AccountMeta::new(vault(setup, source), false),
+            AccountMeta::new(token, false),
+            AccountMeta::new_readonly(participant.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn test_ring_completes() {
+    let (mut context, setup) = start().await;
+    let (swap, carol) = (&setup.swap, &setup.carol);
+
+    let alice_deposit = deposit(&setup, 0, &swap.alice, swap.alice_x);
+    assert!(context.send(&[alice_deposit], &[&swap.alice]).await);
+    let bob_deposit = deposit(&setup, 1, &swap.bob, swap.bob_y);
+    assert!(context.send(&[bob_deposit], &[&swap.bob]).await);
+    // bob is owed alice's X, which stays locked until carol deposits
+    let early = withdraw(&setup, 1, 0, &swap.bob, swap.bob_x);
+    assert!(!context.send(&[early], &[&swap.bob]).await);
+
+    let carol_deposit = deposit(&setup, 2, carol, setup.carol_z);
+    assert!(context.send(&[carol_deposit], &[carol]).await);
+    let ring_data: RingData = context.read(setup.ring).await;
+    assert!(ring_data.is_complete());
+
+    let refund = withdraw(&setup, 2, 2, carol, setup.carol_z);
+    assert!(!context.send(&[refund], &[carol]).await);
+    let by_alice = withdraw(&setup, 1, 0, &swap.alice, swap.bob_x);
+    assert!(!context.send(&[by_alice], &[&swap.alice]).await);
+
+    let alice_withdrawal = withdraw(&setup, 0, 2, &swap.alice, setup.alice_z);
+    assert!(context.send(&[alice_withdrawal], &[&swap.alice]).await);
+    let bob_withdrawal = withdraw(&setup, 1, 0, &swap.bob, swap.bob_x);
+    assert!(context.send(&[bob_withdrawal], &[&swap.bob]).await);
+    let carol_withdrawal = withdraw(&setup, 2, 1, carol, setup.carol_y);
+    assert!(context.send(&[carol_withdrawal], &[carol]).await);
+
+    assert_eq!(context.balance(setup.alice_z).await, 300);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS + 100);
+    assert_eq!(context.balance(setup.carol_y).await, 200);
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS - 100);
+    assert_eq!(context.balance(swap.bob_y).await, FUNDS - 200);
+    assert_eq!(context.balance(setup.carol_z).await, FUNDS - 300);
+    for index in 0..3 {
+        assert_eq!(context.balance(vault(&setup, index)).await, 0);
+    }
+
+    let again = withdraw(&setup, 0, 2, &swap.alice, setup.alice_z);
+    assert!(!context.send(&[again], &[&swap.alice]).await);
+}
+
+#[tokio::test]
+async fn test_ring_refund_before_completion() {
+    let (mut context, setup) = start().await;
+    let swap = &setup.swap;
+
+    let by_bob = deposit(&setup, 0, &swap.bob, swap.bob_x);
+    assert!(!context.send(&[by_bob], &[&swap.bob]).await);
+    let nothing = withdraw(&setup, 0, 0, &swap.alice, swap.alice_x);
+    assert!(!context.send(&[nothing], &[&swap.alice]).await);
+
+    let alice_deposit = deposit(&setup, 0, &swap.alice, swap.alice_x);
+    assert!(context.send(&[alice_deposit], &[&swap.alice]).await);
+    let twice = deposit(&setup, 0, &swap.alice, swap.alice_x);
+    assert!(!context.send(&[twice], &[&swap.alice]).await);
+
+    let refund = withdraw(&setup, 0, 0, &swap.alice, swap.alice_x);
+    assert!(context.send(&[refund], &[&swap.alice]).await);
+    assert_eq!(context.balance(swap.alice_x).await, FUNDS);
+    let ring_data: RingData = context.read(setup.ring).await;
+    assert!(!ring_data.legs()[0].deposited);
+
+    // the vault is reused by the next deposit
+    let redeposit = deposit(&setup, 0, &swap.alice, swap.alice_x);
+    assert!(context.send(&[redeposit], &[&swap.alice]).await);
+    assert_eq!(context.balance(vault(&setup, 0)).await, 100);
+}
+
+#[tokio::test]
+async fn test_init_rejects_invalid_rings() {
+    let (program_test, setup) = setup();
+    let mut context = Context::start(program_test).await;
+    let with = |index: usize, leg: RingLeg| {
+        let mut legs = setup.legs.clone();
+        legs[index] = leg;
+        legs
+    };
+    let leg = setup.legs[2];
+
+    let invalid = vec![
+        setup.legs[..1].to_vec(),
+        with(2, RingLeg { amount: 0, ..leg }),
+        with(
+            2,
+            RingLeg {
+                owed_from: 2,
+                ..leg
+            },
+        ),
+        with(
+            2,
+            RingLeg {
+                owed_from: 3,
+                ..leg
+            },
+        ),
+        with(
+            2,
+            RingLeg {
+                owed_from: 0,
+                ..leg
+            },
+        ),
+        vec![setup.legs[0]; 9],
+    ];
+    for legs in invalid {
+        let init = init(&setup, legs);
+        assert!(!context.send(&[init], &[&setup.swap.alice]).await);
+    }
+    assert!(context.get_account(setup.ring).await.is_none());
+}
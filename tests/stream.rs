@@ -0,0 +1,270 @@
+mod common;
+
+use common::*;
+use escrow::{instruction::EscrowInstruction, state::StreamData};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program, sysvar,
+};
+
+const RATE: u64 = 10;
+const BUDGET: u64 = 5_000;
+
+struct Setup {
+    program_id: Pubkey,
+    payer: Keypair,
+    recipient: Keypair,
+    mint: Pubkey,
+    stream: Pubkey,
+    vault: Pubkey,
+    payer_token: Pubkey,
+    recipient_token: Pubkey,
+}
+
+fn setup() -> (ProgramTest, Setup) {
+    let program_id = Pubkey::new_unique();
+    let payer = Keypair::new();
+    let recipient = Keypair::new();
+    let mint = Pubkey::new_unique();
+    let stream = pda(
+        &program_id,
+        &[
+            b"stream",
+            payer.pubkey().as_ref(),
+            recipient.pubkey().as_ref(),
+            mint.as_ref(),
+            PASS.as_ref(),
+        ],
+    );
+    let vault = pda(&program_id, &[b"vault", stream.as_ref()]);
+    let payer_token = Pubkey::new_unique();
+    let recipient_token = Pubkey::new_unique();
+
+    let mut program_test = program_test(program_id);
+    program_test.add_account(mint, mint_account(0));
+    program_test.add_account(payer.pubkey(), system_account());
+    program_test.add_account(payer_token, token_account(mint, payer.pubkey(), FUNDS));
+    program_test.add_account(recipient_token, token_account(mint, recipient.pubkey(), 0));
+    (
+        program_test,
+        Setup {
+            program_id,
+            payer,
+            recipient,
+            mint,
+            stream,
+            vault,
+            payer_token,
+            recipient_token,
+        },
+    )
+}
+
+/// A running stream of `BUDGET` at `RATE` whose last checkpoint, with nothing streamed, was `elapsed` seconds ago
+fn add_stream(program_test: &mut ProgramTest, setup: &Setup, elapsed: i64) {
+    let bump = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &setup.program_id).1;
+    let stream_data = StreamData {
+        is_initialized: true,
+        is_cancelled: false,
+        pubkey_payer: setup.payer.pubkey(),
+        pubkey_recipient: setup.recipient.pubkey(),
+        pubkey_mint: setup.mint,
+        rate_per_second: RATE,
+        deposited: BUDGET,
+        withdrawn: 0,
+        checkpoint_ts: now() - elapsed,
+        checkpoint_streamed: 0,
+        escrow_bump: bump(&[
+            b"stream",
+            setup.payer.pubkey().as_ref(),
+            setup.recipient.pubkey().as_ref(),
+            setup.mint.as_ref(),
+            PASS.as_ref(),
+        ]),
+        vault_bump: bump(&[b"vault", setup.stream.as_ref()]),
+    };
+    program_test.add_account(
+        setup.stream,
+        program_account(&stream_data, setup.program_id),
+    );
+    program_test.add_account(setup.vault, token_account(setup.mint, setup.stream, BUDGET));
+}
+
+fn withdraw(setup: &Setup) -> Instruction {
+    instruction(
+        setup.program_id,
+        EscrowInstruction::WithdrawStream { pass: PASS },
+        vec![
+            AccountMeta::new(setup.stream, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(setup.recipient_token, false),
+            AccountMeta::new_readonly(setup.recipient.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+fn top_up(setup: &Setup, amount: u64) -> Instruction {
+    instruction(
+        setup.program_id,
+        EscrowInstruction::TopUpStream { amount, pass: PASS },
+        vec![
+            AccountMeta::new(setup.stream, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(setup.payer_token, false),
+            AccountMeta::new_readonly(setup.payer.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+fn cancel(setup: &Setup) -> Instruction {
+    instruction(
+        setup.program_id,
+        EscrowInstruction::CancelStream { pass: PASS },
+        vec![
+            AccountMeta::new(setup.stream, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(setup.payer_token, false),
+            AccountMeta::new(setup.recipient_token, false),
+            AccountMeta::new_readonly(setup.payer.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+fn stream_data(checkpoint_streamed: u64, deposited: u64) -> StreamData {
+    StreamData {
+        is_initialized: true,
+        is_cancelled: false,
+        pubkey_payer: Pubkey::default(),
+        pubkey_recipient: Pubkey::default(),
+        pubkey_mint: Pubkey::default(),
+        rate_per_second: RATE,
+        deposited,
+        withdrawn: 0,
+        checkpoint_ts: 1_000,
+        checkpoint_streamed,
+        escrow_bump: 0,
+        vault_bump: 0,
+    }
+}
+
+#[test]
+fn test_streamed_amount() {
+    let stream = stream_data(0, BUDGET);
+    assert_eq!(stream.streamed_amount(0), 0);
+    assert_eq!(stream.streamed_amount(1_000), 0);
+    assert_eq!(stream.streamed_amount(1_001), RATE);
+    assert_eq!(stream.streamed_amount(1_100), 100 * RATE);
+    assert_eq!(stream.streamed_amount(i64::MAX), BUDGET);
+
+    let topped_up = stream_data(1_000, BUDGET);
+    assert_eq!(topped_up.streamed_amount(1_010), 1_000 + 10 * RATE);
+
+    let cancelled = StreamData {
+        is_cancelled: true,
+        ..stream_data(1_000, 1_000)
+    };
+    assert_eq!(cancelled.streamed_amount(i64::MAX), 1_000);
+}
+
+#[tokio::test]
+async fn test_init_stream() {
+    let (program_test, setup) = setup();
+    let mut context = Context::start(program_test).await;
+    let before = now();
+
+    let init = instruction(
+        setup.program_id,
+        EscrowInstruction::InitStream {
+            rate_per_second: RATE,
+            amount: BUDGET,
+            pass: PASS,
+        },
+        vec![
+            AccountMeta::new(setup.stream, false),
+            AccountMeta::new_readonly(setup.mint, false),
+            AccountMeta::new(setup.vault, false),
+            AccountMeta::new(setup.payer.pubkey(), true),
+            AccountMeta::new_readonly(setup.recipient.pubkey(), false),
+            AccountMeta::new(setup.payer_token, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    );
+    assert!(context.send(&[init], &[&setup.payer]).await);
+
+    assert_eq!(context.balance(setup.vault).await, BUDGET);
+    assert_eq!(context.balance(setup.payer_token).await, FUNDS - BUDGET);
+    let stream_data: StreamData = context.read(setup.stream).await;
+    assert_eq!(stream_data.rate_per_second, RATE);
+    assert_eq!(stream_data.deposited, BUDGET);
+    assert!((stream_data.checkpoint_ts - before).abs() < 60);
+}
+
+#[tokio::test]
+async fn test_withdraw_streamed() {
+    let (mut program_test, setup) = setup();
+    add_stream(&mut program_test, &setup, 100);
+    let mut context = Context::start(program_test).await;
+
+    assert!(context.send(&[withdraw(&setup)], &[&setup.recipient]).await);
+    let withdrawn = context.balance(setup.recipient_token).await;
+    assert!((100 * RATE..200 * RATE).contains(&withdrawn));
+
+    let stranger = Keypair::new();
+    let mut by_stranger = withdraw(&setup);
+    by_stranger.accounts[3].pubkey = stranger.pubkey();
+    assert!(!context.send(&[by_stranger], &[&stranger]).await);
+}
+
+#[tokio::test]
+async fn test_top_up_dry_stream() {
+    let (mut program_test, setup) = setup();
+    add_stream(&mut program_test, &setup, 10_000);
+    let mut context = Context::start(program_test).await;
+
+    assert!(context.send(&[withdraw(&setup)], &[&setup.recipient]).await);
+    assert_eq!(context.balance(setup.recipient_token).await, BUDGET);
+    assert!(!context.send(&[withdraw(&setup)], &[&setup.recipient]).await);
+
+    // the time the stream ran dry does not accrue against the top up
+    assert!(
+        context
+            .send(&[top_up(&setup, BUDGET)], &[&setup.payer])
+            .await
+    );
+    let stream_data: StreamData = context.read(setup.stream).await;
+    assert_eq!(stream_data.checkpoint_streamed, BUDGET);
+    assert_eq!(stream_data.deposited, 2 * BUDGET);
+    assert_eq!(context.balance(setup.vault).await, BUDGET);
+}
+
+#[tokio::test]
+async fn test_cancel_stream() {
+    let (mut program_test, setup) = setup();
+    add_stream(&mut program_test, &setup, 100);
+    let mut context = Context::start(program_test).await;
+
+    assert!(context.send(&[cancel(&setup)], &[&setup.payer]).await);
+    let owed = context.balance(setup.recipient_token).await;
+    assert!((100 * RATE..200 * RATE).contains(&owed));
+    assert_eq!(
+        context.balance(setup.payer_token).await,
+        FUNDS + BUDGET - owed
+    );
+    assert_eq!(context.balance(setup.vault).await, 0);
+
+    assert!(!context.send(&[withdraw(&setup)], &[&setup.recipient]).await);
+    assert!(!context.send(&[top_up(&setup, 1)], &[&setup.payer]).await);
+    assert!(!context.send(&[cancel(&setup)], &[&setup.payer]).await);
+}
@@ -0,0 +1,171 @@
+mod common;
+
+use common::*;
+use escrow::{
+    instruction::EscrowInstruction,
+    state::{EscrowData, EscrowState, EscrowTerms, VestingSchedule},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::Signer,
+    sysvar,
+};
+
+const GRANT: u64 = 1_000;
+
+fn claim(swap: &Swap) -> Instruction {
+    instruction(
+        swap.program_id,
+        EscrowInstruction::Claim { pass: PASS },
+        vec![
+            AccountMeta::new(swap.escrow, false),
+            AccountMeta::new(swap.bob_x, false),
+            AccountMeta::new(swap.vault_x, false),
+            AccountMeta::new_readonly(swap.bob.pubkey(), true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    )
+}
+
+/// Funded grant of `GRANT` vesting to bob along `schedule`
+async fn grant(schedule: VestingSchedule) -> (Context, Swap) {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let mut context = Context::start(program_test).await;
+
+    let terms = EscrowTerms {
+        vesting: Some(schedule),
+        ..EscrowTerms::default()
+    };
+    let init = swap.init(&context.payer(), GRANT, 0, terms);
+    let deposit = swap.deposit(&swap.alice, GRANT);
+    let parties = [&swap.alice, &swap.bob];
+    assert!(context.send(&[init, deposit], &parties).await);
+    (context, swap)
+}
+
+#[test]
+fn test_vested_amount() {
+    let schedule = VestingSchedule {
+        start_ts: 1_000,
+        cliff_ts: 1_250,
+        end_ts: 2_000,
+    };
+    assert_eq!(schedule.vested_amount(GRANT, 0), 0);
+    assert_eq!(schedule.vested_amount(GRANT, 1_249), 0);
+    assert_eq!(schedule.vested_amount(GRANT, 1_250), 250);
+    assert_eq!(schedule.vested_amount(GRANT, 1_500), 500);
+    assert_eq!(schedule.vested_amount(GRANT, 1_999), 999);
+    assert_eq!(schedule.vested_amount(GRANT, 2_000), GRANT);
+    assert_eq!(schedule.vested_amount(GRANT, i64::MAX), GRANT);
+    assert_eq!(schedule.vested_amount(u64::MAX, 1_500), u64::MAX / 2);
+}
+
+#[test]
+fn test_vesting_schedule_validity() {
+    let valid = VestingSchedule {
+        start_ts: 10,
+        cliff_ts: 10,
+        end_ts: 20,
+    };
+    assert!(valid.is_valid());
+    let invalid = [
+        VestingSchedule {
+            end_ts: 10,
+            ..valid
+        },
+        VestingSchedule {
+            cliff_ts: 21,
+            ..valid
+        },
+        VestingSchedule {
+            cliff_ts: 9,
+            ..valid
+        },
+        VestingSchedule::default(),
+    ];
+    for schedule in invalid.iter() {
+        assert!(!schedule.is_valid(), "{:?}", schedule);
+    }
+}
+
+#[tokio::test]
+async fn test_claim_vested_part() {
+    let now = now();
+    let (mut context, swap) = grant(VestingSchedule {
+        start_ts: now - 1_000,
+        cliff_ts: now - 500,
+        end_ts: now + 1_000,
+    })
+    .await;
+
+    assert!(context.send(&[claim(&swap)], &[&swap.bob]).await);
+    let claimed = context.balance(swap.bob_x).await - FUNDS;
+    assert!(claimed > 300 && claimed < 700, "claimed {}", claimed);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.claimed, claimed);
+    assert_eq!(escrow_data.state, EscrowState::DepositAlice);
+
+    // the grant only leaves the vault through Claim
+    assert!(!context.send(&[swap.withdraw_bob()], &[&swap.bob]).await);
+    let deposit = swap.deposit(&swap.bob, 1);
+    assert!(!context.send(&[deposit], &[&swap.bob]).await);
+}
+
+#[tokio::test]
+async fn test_claim_before_cliff() {
+    let now = now();
+    let (mut context, swap) = grant(VestingSchedule {
+        start_ts: now - 1_000,
+        cliff_ts: now + 1_000,
+        end_ts: now + 2_000,
+    })
+    .await;
+
+    assert!(!context.send(&[claim(&swap)], &[&swap.bob]).await);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS);
+}
+
+#[tokio::test]
+async fn test_claim_fully_vested() {
+    let now = now();
+    let (mut context, swap) = grant(VestingSchedule {
+        start_ts: now - 2_000,
+        cliff_ts: now - 1_000,
+        end_ts: now - 1,
+    })
+    .await;
+
+    assert!(context.send(&[claim(&swap)], &[&swap.bob]).await);
+    assert_eq!(context.balance(swap.bob_x).await, FUNDS + GRANT);
+    let escrow_data: EscrowData = context.read(swap.escrow).await;
+    assert_eq!(escrow_data.state, EscrowState::Completed);
+
+    assert!(!context.send(&[claim(&swap)], &[&swap.bob]).await);
+}
+
+#[tokio::test]
+async fn test_init_rejects_invalid_vesting() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let swap = Swap::new(&mut program_test, program_id);
+    let mut context = Context::start(program_test).await;
+    let schedule = VestingSchedule {
+        start_ts: 10,
+        cliff_ts: 10,
+        end_ts: 20,
+    };
+
+    for (schedule, size_y) in [(schedule, 1), (VestingSchedule::default(), 0)].iter() {
+        let terms = EscrowTerms {
+            vesting: Some(*schedule),
+            ..EscrowTerms::default()
+        };
+        let init = swap.init(&context.payer(), GRANT, *size_y, terms);
+        assert!(!context.send(&[init], &[&swap.alice, &swap.bob]).await);
+    }
+}